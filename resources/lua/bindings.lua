@@ -18,12 +18,19 @@ bind("ctrl-d", "delete_right")
 bind("ctrl-h", "delete")
 bind("ctrl-k", "delete_to_end")
 bind("ctrl-u", "delete_from_start")
+bind("ctrl-z", "undo")
+bind("alt-z", "redo")
+bind("ctrl-y", "yank")
+bind("alt-y", "yank_pop")
+bind("alt-enter", "newline")
+bind("alt-m", "multiline")
 
 -- Scrolling
 bind("home", "scroll_top")
 bind("end", "scroll_bottom")
 bind("pageup", "scroll_up")
 bind("pagedown", "scroll_down")
+bind("alt-v", "scroll_mode")
 
 -- ctrl + up/down
 blight.bind("ctrl-up", search.find_up)
@@ -41,6 +48,7 @@ blight.bind("up", history.previous_command)
 blight.bind("down", history.next_command)
 blight.bind("ctrl-p", history.previous_command)
 blight.bind("ctrl-n", history.next_command)
+bind("ctrl-r", "history_search")
 
 -- Toggle tag rendering
 blight.bind("ctrl-t", function()
@@ -137,7 +137,7 @@ end
 
 mud.add_input_listener(function(line)
     reset()
-    if line:source() == "user" then
+    if line:source() == "user" and not line:secret() then
         local str = line:line()
         if str ~= commands[#commands] and #str > 0 then
             if settings.get("smart_history") then
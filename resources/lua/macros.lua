@@ -218,6 +218,80 @@ end)
 alias.add("^/stop_log$", function()
     log.stop()
 end)
+
+-- Replay
+local listed_logs = {}
+alias.add("^/logs(?: (\\S+))?$", function(m)
+    local world = m[2] ~= "" and m[2] or nil
+    if world then
+        listed_logs = log.list(world)
+        if #listed_logs == 0 then
+            info("No logs found for: " .. world)
+        end
+        for i, name in ipairs(listed_logs) do
+            info(cformat("<bgreen>%3d<reset> %s", i, name))
+        end
+    else
+        local worlds = log.list()
+        if #worlds == 0 then
+            info("No logs found")
+        end
+        for _, name in ipairs(worlds) do
+            info(name)
+        end
+    end
+end)
+alias.add("^/replay.*$", function(m)
+    local args = get_args(m[1])
+    if args[2] == "stop" and #args == 2 then
+        log.stop_replay()
+    elseif #args >= 2 and #args <= 4 then
+        local name = listed_logs[tonumber(args[2])] or args[2]
+        local speed = tonumber(args[3] or "1")
+        local triggers = args[4] == "triggers"
+        if not speed or (args[4] and not triggers) then
+            info("USAGE: /replay <logfile> [speed] [triggers]")
+            return
+        end
+        local ok, err = pcall(log.replay, name, speed, triggers)
+        if not ok then
+            error(err)
+        end
+    else
+        info("USAGE: /replay <logfile> [speed] [triggers]", "USAGE: /replay stop")
+    end
+end)
+alias.add("^/logsearch(?: (\\S+)(?: (\\S+))?(?: (\\S+))?)?$", function(m)
+    local pattern, world, since = m[2], m[3], m[4]
+    if pattern == "" then
+        info("USAGE: /logsearch <regex> [world] [since]")
+        return
+    end
+    local function is_since(arg)
+        return arg:match("^%d+[hdw]$") or arg:match("^%d%d%d%d%-%d%d%-%d%d$")
+    end
+    if since == "" and is_since(world) then
+        world, since = "", world
+    end
+    local ok, err = pcall(
+        log.show_search,
+        pattern,
+        world ~= "" and world or nil,
+        since ~= "" and since or nil
+    )
+    if not ok then
+        error(err)
+    end
+end)
+alias.add("^/export.*$", function(m)
+    local args = get_args(m[1])
+    local lines = tonumber(args[4] or "")
+    if args[2] == "html" and (#args == 3 or (#args == 4 and lines)) then
+        blight.export_html(args[3], lines)
+    else
+        info("USAGE: /export html <file> [lines]")
+    end
+end)
 alias.add("^/load.*$", function(m)
     local args = get_args(m[1])
     if args[1] == "/load" then
@@ -323,6 +397,149 @@ alias.add("^/help.*$", function(m)
     end
 end)
 
+-- Themes
+alias.add("^/theme.*$", function(m)
+    local args = get_args(m[1])
+    if #args > 1 then
+        local ok, err = pcall(blight.theme, args[2])
+        if not ok then
+            error(err)
+        end
+    else
+        local current = blight.theme()
+        for _, name in ipairs(blight.themes()) do
+            if name == current then
+                info(cformat("<bgreen>%s<reset> (active)", name))
+            else
+                info(name)
+            end
+        end
+    end
+end)
+
+-- Wrapping
+alias.add("^/wrap.*$", function(m)
+    local args = get_args(m[1])
+    if #args > 2 then
+        local words = args[3] ~= "chars"
+        local indent = tonumber(args[4] or "0")
+        local ok, err = pcall(blight.wrap, args[2], { words = words, indent = indent })
+        if not ok then
+            error(err)
+        end
+    else
+        for _, window in ipairs({ "output", "reader" }) do
+            local options = blight.wrap(window)
+            info(cformat("%s: <bgreen>%s<reset>, indent %d", window,
+                options.words and "words" or "chars", options.indent))
+        end
+    end
+end)
+
+-- Bell
+alias.add("^/bell( .*)?$", function(m)
+    local args = get_args(m[1])
+    if #args > 1 then
+        local sound = #args > 2 and table.concat(args, " ", 3) or nil
+        local ok, err = pcall(blight.bell, args[2], sound)
+        if not ok then
+            error(err)
+        end
+    else
+        local action, sound = blight.bell()
+        if sound then
+            info(cformat("Bell: <bgreen>%s<reset>, sound %s", action, sound))
+        else
+            info(cformat("Bell: <bgreen>%s<reset>", action))
+        end
+    end
+end)
+
+-- Colors
+alias.add("^/colordepth( .*)?$", function(m)
+    local args = get_args(m[1])
+    local ok, depth = pcall(blight.color_depth, args[2])
+    if not ok then
+        error(depth)
+    end
+    info(cformat("Color depth: <bgreen>%s<reset>", depth))
+end)
+
+local function show_palette(world)
+    local palette = blight.palette(world)
+    local lines = {}
+    for _, layer in ipairs({ "fg", "bg" }) do
+        local colors = {}
+        for color in pairs(palette[layer]) do
+            colors[#colors + 1] = color
+        end
+        table.sort(colors)
+        for _, color in ipairs(colors) do
+            local suffix = layer == "bg" and " (background)" or ""
+            lines[#lines + 1] = string.format("%s -> %s%s", color, palette[layer][color], suffix)
+        end
+    end
+    local title = world and ("Palette for " .. world) or "Palette"
+    if #lines == 0 then
+        info(title .. " is empty")
+    else
+        info(title .. ":", table.unpack(lines))
+    end
+end
+
+alias.add("^/palette( .*)?$", function(m)
+    local args = get_args(m[1])
+    if #args < 2 then
+        show_palette()
+    elseif args[2] == "clear" then
+        blight.clear_palette(args[3])
+        info(args[3] and ("Cleared the palette for " .. args[3]) or "Cleared the palette")
+    elseif #args == 2 then
+        show_palette(args[2])
+    else
+        local to = args[3] ~= "none" and args[3] or nil
+        local options = {}
+        for i = 4, #args do
+            if args[i] == "bg" then
+                options.bg = true
+            else
+                options.world = args[i]
+            end
+        end
+        local ok, err = pcall(blight.remap_color, args[2], to, options)
+        if not ok then
+            error(err)
+        end
+    end
+end)
+
+alias.add("^/vt( .*)?$", function(m)
+    local args = get_args(m[1])
+    if #args < 2 then
+        local cols, rows = blight.vt_size()
+        info(
+            string.format("The VT pane is %dx%d", cols, rows),
+            "USAGE: /vt <world> [on|off]",
+            "USAGE: /vt size <cols> <rows>"
+        )
+    elseif args[2] == "size" then
+        local ok, cols, rows = pcall(blight.vt_size, tonumber(args[3]), tonumber(args[4]))
+        if not ok then
+            error(cols)
+        end
+        info(string.format("The VT pane is %dx%d", cols, rows))
+    else
+        local enabled = nil
+        if args[3] == "on" then
+            enabled = true
+        elseif args[3] == "off" then
+            enabled = false
+        end
+        local on = blight.vt_mode(args[2], enabled)
+        info(string.format("Screen drawing for %s goes to %s", args[2], on and "the VT pane" or "the output"))
+    end
+end)
+
 -- Search
 alias.add("^(?:/search|/s ).*$", function(m)
     local args = get_args(m[1])
@@ -332,3 +549,7 @@ alias.add("^(?:/search|/s ).*$", function(m)
         search.search(args[2])
     end
 end)
+
+alias.add("^/grep(?: (.*))?$", function(m)
+    search.grep(m[2])
+end)
@@ -8,6 +8,14 @@ function mod.search(str)
     blight.find_backward(search_pattern)
 end
 
+function mod.grep(str)
+    if str and str ~= "" then
+        blight.grep(regex.new(str))
+    else
+        blight.grep()
+    end
+end
+
 function mod.find_up()
     if search_pattern then
         blight.find_backward(search_pattern)
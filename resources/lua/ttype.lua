@@ -5,6 +5,7 @@ local term = os.getenv("TERM") or "xterm-256color"
 
 local index = 1
 local auto_reader_mode = true
+local auto_color = true
 
 local mod = {}
 
@@ -25,8 +26,20 @@ local mtts = 0x0
 mtts = mtts | mod.MTTS_VT100
 mtts = mtts | mod.MTTS_ANSI
 mtts = mtts | mod.MTTS_UTF8
-mtts = mtts | mod.MTTS_256_COLOR
-mtts = mtts | mod.MTTS_TRUE_COLOR
+
+local COLOR_FLAGS = mod.MTTS_256_COLOR | mod.MTTS_TRUE_COLOR
+
+-- Only claim the colors the terminal can show, see `blight.color_depth()`
+local function color_flags()
+    local depth = blight.color_depth()
+    if depth == "truecolor" then
+        return COLOR_FLAGS
+    elseif depth == "256" then
+        return mod.MTTS_256_COLOR
+    else
+        return 0
+    end
+end
 
 local NEGOTIATION_STACK = {}
 
@@ -34,6 +47,9 @@ local reader_mode = false
 
 local function init()
     index = 1
+    if auto_color then
+        mtts = (mtts & ~COLOR_FLAGS) | color_flags()
+    end
     if auto_reader_mode then
         if (tts.is_available() and tts.is_enabled()) or blight.is_reader_mode() then
             mtts = mtts | mod.MTTS_SCREEN_READER
@@ -101,6 +117,7 @@ end
 
 function mod.set_mtts(new_mtts)
     mtts = new_mtts
+    auto_color = false
     Info(string.format("Set MTTS: '0x%X'", mtts))
 end
 
@@ -110,6 +127,9 @@ function mod.add_option(mtts_opt)
     if mtts_opt & mod.MTTS_SCREEN_READER then
         auto_reader_mode = false
     end
+    if mtts_opt & COLOR_FLAGS ~= 0 then
+        auto_color = false
+    end
     Info(string.format("Updated MTTS 0x%X | 0x%X = 0x%X", old_mtts, mtts_opt, mtts))
 end
 
@@ -119,6 +139,9 @@ function mod.rem_option(mtts_opt)
     if mtts_opt & mod.MTTS_SCREEN_READER then
         auto_reader_mode = false
     end
+    if mtts_opt & COLOR_FLAGS ~= 0 then
+        auto_color = false
+    end
     Info(string.format("Updated MTTS 0x%X & ~0x%X = 0x%X", old_mtts, mtts_opt, mtts))
 end
 
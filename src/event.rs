@@ -1,13 +1,18 @@
-use crate::io::FSEvent;
-use crate::lua::ConnectionInfo;
+use crate::io::{
+    Direction, FSEvent, LogMatch, LogOptions, LogQuery, LogSettings, SaveData, ScrollbackSettings,
+};
+use crate::lua::{ConnectionInfo, LuaScript};
 use crate::net::spawn_connect_thread;
 use crate::{audio::SourceOptions, model::Regex};
 use crate::{
-    model::{Connection, Line, PromptMask, TagMask},
+    model::{
+        BellAction, BellSettings, ColorSettings, CompletionMenu, Connection, InputSettings, Line,
+        Palettes, PromptMask, TagMask, Theme, ToLine, VtSettings, Wrapping,
+    },
     net::{spawn_network_thread, WakingSender},
     session::Session,
     tts::TTSEvent,
-    ui::UserInterface,
+    ui::{NotifyMethod, ScrollMotion, UserInterface},
 };
 use libmudtelnet::{bytes::Bytes, events::TelnetEvents};
 use log::debug;
@@ -45,6 +50,8 @@ pub enum Event {
     FindForward(Regex),
     Info(String),
     LoadScript(String),
+    LogSearchMatch(LogMatch),
+    LogSearchResults(u32, Vec<LogMatch>),
     EvalScript(String),
     MudOutput(Line),
     Output(Line),
@@ -59,26 +66,37 @@ pub enum Event {
     QuitConfirmTimeout,
     Reconnect,
     Redraw,
+    Replay(String, f64, bool),
+    ReplayDone,
+    ReplayLine(Direction, Line),
     RemoveTag(String),
     RemoveTimer(u32),
     ResetScript,
     ScrollBottom,
+    ScrollCursor(ScrollMotion),
     ScrollDown,
     ScrollLock(bool),
+    ScrollMark(char),
+    ScrollMode(bool),
     ScrollTop,
     ScrollUp,
+    ScrollYank,
+    SearchLogs(LogQuery, Option<u32>),
+    SetFilter(Option<Regex>),
     ServerInput(Line),
     ServerSend(Bytes),
     SettingChanged(String, bool),
+    ShowCompletions(Option<CompletionMenu>),
     ShowHelp(String, bool),
     ShowTags(bool),
     Speak(String, bool),
     SpeakStop,
-    StartLogging(String, bool),
+    StartLogging(String, bool, LogOptions),
     StatusAreaHeight(u16),
     StatusLine(usize, String),
     StopLogging,
     StopMusic,
+    StopReplay,
     StopSFX,
     TTSEnabled(bool),
     TTSEvent(TTSEvent),
@@ -88,7 +106,22 @@ pub enum Event {
     SetPromptCursorPos(usize),
     SetPromptMask(PromptMask),
     ClearPromptMask,
+    /// Hides the input while the server echoes it itself
+    SetSecretInput(bool),
     SetTagMask(TagMask),
+    SetTheme(Theme),
+    SetWrapping(Wrapping),
+    SetScrollback(ScrollbackSettings),
+    SetLogSettings(LogSettings),
+    ExportHtml(String, Option<usize>),
+    SetTitle(String),
+    SetBell(BellSettings),
+    SetInputSettings(InputSettings),
+    SetColors(ColorSettings),
+    SetPalettes(Palettes),
+    SetVt(VtSettings),
+    Flash(bool),
+    Notify(String, String, NotifyMethod),
     UserInputBuffer(String, usize),
     UserInputCursor(usize),
     FSEvent(FSEvent),
@@ -98,6 +131,9 @@ pub enum Event {
 use anyhow::Result as AResult;
 type Result = AResult<()>;
 
+/// How long the screen flashes for a visual bell.
+const FLASH_DURATION: time::Duration = time::Duration::from_millis(150);
+
 pub struct EventHandler {
     session: Session,
 }
@@ -152,13 +188,19 @@ impl EventHandler {
                     let mut output_buffer = self.session.output_buffer.lock().unwrap();
                     output_buffer.input_sent();
                     script.on_mud_input(&mut line);
-                    if self.session.echo_input.load(Ordering::Relaxed) {
-                        screen.print_send(&line);
-                    }
-                    if let Ok(mut logger) = self.session.logger.lock() {
-                        logger.log_line("> ", &line)?;
+                    if !line.flags.secret {
+                        if self.session.echo_input.load(Ordering::Relaxed) {
+                            screen.print_send(&line);
+                        }
+                        if !line.flags.replay {
+                            self.log_line(Direction::In, &line)?;
+                        }
                     }
-                    if !line.flags.matched {
+                    if line.flags.replay && !line.flags.matched {
+                        if !line.flags.secret {
+                            screen.print_info(&format!("Not sent during replay: {}", line.line()));
+                        }
+                    } else if !line.flags.matched {
                         if let Ok(mut parser) = self.session.telnet_parser.lock() {
                             if let TelnetEvents::DataSend(buffer) = parser.send_text(line.line()) {
                                 self.session.main_writer.send(Event::ServerSend(buffer))?;
@@ -228,6 +270,12 @@ impl EventHandler {
 
                 debug!("Connected to {}:{}", host, port);
                 screen.set_host(&host, port)?;
+                let world = name.as_deref().unwrap_or(&host);
+                *self.session.palette.lock().unwrap() = Palettes::load().active(Some(world));
+                let vt = VtSettings::load().pane_for(Some(world));
+                *self.session.vt.lock().unwrap() = vt;
+                screen.set_vt_pane(vt)?;
+                self.restore_scrollback(world, screen);
                 if let Ok(mut script) = self.session.lua_script.lock() {
                     let info = ConnectionInfo {
                         host: host.clone(),
@@ -263,6 +311,9 @@ impl EventHandler {
                         });
                     }
                     transmit_writer.take();
+                    self.session
+                        .main_writer
+                        .send(Event::SetSecretInput(false))?;
                     screen.set_host("", 0)?;
                     screen.clear_tags()?;
                     screen.print_prompt(&Line::from(""));
@@ -287,9 +338,24 @@ impl EventHandler {
         }
     }
 
-    fn log_line(&self, prefix: &str, line: &Line) -> Result {
+    /// Prints the scrollback saved for a world followed by a separator.
+    fn restore_scrollback(&self, world: &str, screen: &mut Box<dyn UserInterface>) {
+        if let Ok(mut scrollback) = self.session.scrollback.lock() {
+            let lines = scrollback.open(world);
+            if let Some(first) = lines.first() {
+                let since = first.time().format("%Y-%m-%d %H:%M");
+                lines.iter().for_each(|line| screen.print_output(line));
+                screen.print_output(
+                    &format!("\x1b[2m─── Restored scrollback since {since} ───\x1b[0m")
+                        .to_internal_line(),
+                );
+            }
+        }
+    }
+
+    fn log_line(&self, direction: Direction, line: &Line) -> Result {
         if let Ok(mut logger) = self.session.logger.lock() {
-            logger.log_line(prefix, line)?;
+            logger.log_line(direction, line)?;
         }
         Ok(())
     }
@@ -301,15 +367,44 @@ impl EventHandler {
         Ok(())
     }
 
+    /// Runs the bell callbacks and the configured bell action for a line
+    /// that contained BEL characters.
+    fn ring_bell(
+        &self,
+        script: &LuaScript,
+        line: &Line,
+        screen: &mut Box<dyn UserInterface>,
+    ) -> Result {
+        script.on_bell(line);
+        let settings = self.session.bell.lock().unwrap().clone();
+        match settings.action {
+            BellAction::Bell => screen.ring_bell()?,
+            BellAction::Flash => {
+                screen.flash(true)?;
+                spawn_flash_timeout_thread(self.session.main_writer.clone(), FLASH_DURATION)?;
+            }
+            BellAction::Sound => match settings.sound {
+                Some(sound) => self
+                    .session
+                    .main_writer
+                    .send(Event::PlaySFX(sound, SourceOptions::default()))?,
+                None => screen.ring_bell()?,
+            },
+            BellAction::Speak => self
+                .session
+                .main_writer
+                .send(Event::Speak("Bell".to_string(), false))?,
+            BellAction::Off => {}
+        }
+        Ok(())
+    }
+
     fn handle_logging(&self, event: Event) -> Result {
         match event {
-            Event::MudOutput(line) | Event::Output(line) => self.log_line("", &line),
+            Event::MudOutput(line) | Event::Output(line) => self.log_line(Direction::Out, &line),
+            Event::Prompt(prompt) => self.log_line(Direction::Prompt, &prompt),
             Event::Error(line) => self.log_str("[!!] ", &line),
             Event::Info(line) => self.log_str("[**] ", &line),
-            Event::Prompt(prompt) => {
-                self.log_line("", &prompt)?;
-                Ok(())
-            }
             _ => Ok(()),
         }
     }
@@ -324,7 +419,22 @@ impl EventHandler {
             Event::MudOutput(mut line) => {
                 if let Ok(script) = self.session.lua_script.lock() {
                     script.on_mud_output(&mut line);
-                    screen.print_output(&line);
+                    if self.session.vt.lock().unwrap().is_some() && line.draws_screen() {
+                        if !line.flags.gag {
+                            screen.print_vt(&line);
+                        }
+                        if let Ok(mut scrollback) = self.session.scrollback.lock() {
+                            scrollback.record(&line.without_cursor_sequences());
+                        }
+                    } else {
+                        screen.print_output(&line);
+                        if let Ok(mut scrollback) = self.session.scrollback.lock() {
+                            scrollback.record(&line);
+                        }
+                    }
+                    if line.flags.bell {
+                        self.ring_bell(&script, &line, screen)?;
+                    }
                     script.get_output_lines().iter().for_each(|l| {
                         screen.print_output(l);
                     });
@@ -338,6 +448,9 @@ impl EventHandler {
             Event::Prompt(mut prompt) => {
                 if let Ok(script) = self.session.lua_script.lock() {
                     script.on_mud_output(&mut prompt);
+                    if prompt.flags.bell {
+                        self.ring_bell(&script, &prompt, screen)?;
+                    }
                     script.get_output_lines().iter().for_each(|l| {
                         screen.print_output(l);
                     });
@@ -368,6 +481,15 @@ impl EventHandler {
                 }
                 Ok(())
             }
+            Event::SetSecretInput(secret) => {
+                if let Ok(mut command_buffer) = self.session.command_buffer.lock() {
+                    command_buffer.set_secret(secret);
+                    let mut prompt_input = self.session.prompt_input.lock().unwrap();
+                    *prompt_input = command_buffer.get_masked_buffer();
+                    screen.print_prompt_input(&prompt_input, command_buffer.get_pos());
+                }
+                Ok(())
+            }
             Event::UserInputBuffer(input_buffer, pos) => {
                 if let Ok(script) = self.session.lua_script.lock() {
                     script.on_prompt_update(&input_buffer);
@@ -377,6 +499,40 @@ impl EventHandler {
                 screen.print_prompt_input(&prompt_input, pos);
                 Ok(())
             }
+            Event::ShowCompletions(menu) => {
+                screen.show_completions(menu)?;
+                Ok(())
+            }
+            Event::ReplayLine(direction, mut line) => {
+                let triggers = matches!(direction, Direction::Out | Direction::Prompt)
+                    && self.session.replay_triggers();
+                if !triggers {
+                    screen.print_replay(&line);
+                } else if let Ok(script) = self.session.lua_script.lock() {
+                    script.on_replay_output(&mut line);
+                    screen.print_replay(&line);
+                    script.get_output_lines().iter().for_each(|l| {
+                        screen.print_replay(l);
+                    });
+                }
+                Ok(())
+            }
+            Event::Flash(on) => {
+                screen.flash(on)?;
+                Ok(())
+            }
+            Event::LogSearchMatch(result) => {
+                screen.print_output(&result.to_line());
+                Ok(())
+            }
+            Event::ReplayDone => {
+                self.session.finish_replay();
+                screen.print_replay(
+                    &"\x1b[2m─── End of replay, /replay stop to return ───\x1b[0m"
+                        .to_internal_line(),
+                );
+                Ok(())
+            }
             Event::UserInputCursor(pos) => {
                 let prompt_input = self.session.prompt_input.lock().unwrap();
                 screen.print_prompt_input(&prompt_input, pos);
@@ -434,6 +590,26 @@ impl EventHandler {
                 screen.find_up(&pattern)?;
                 Ok(())
             }
+            Event::ScrollMode(enabled) => {
+                screen.set_scroll_mode(enabled)?;
+                Ok(())
+            }
+            Event::ScrollCursor(motion) => {
+                screen.move_scroll_cursor(motion)?;
+                Ok(())
+            }
+            Event::ScrollMark(name) => {
+                screen.set_scroll_mark(name)?;
+                Ok(())
+            }
+            Event::ScrollYank => {
+                screen.yank_scroll_line()?;
+                Ok(())
+            }
+            Event::SetFilter(filter) => {
+                screen.set_filter(filter)?;
+                Ok(())
+            }
             _ => Err(BadEventRoutingError.into()),
         }
     }
@@ -451,6 +627,18 @@ pub(crate) fn spawn_quit_confirm_timeout_thread(
         })
 }
 
+fn spawn_flash_timeout_thread(
+    writer: Sender<Event>,
+    timeout: time::Duration,
+) -> std::io::Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name("flash-timeout-thread".to_string())
+        .spawn(move || {
+            thread::sleep(timeout);
+            writer.send(Event::Flash(false)).ok();
+        })
+}
+
 #[cfg(test)]
 mod event_test {
 
@@ -493,12 +681,47 @@ mod event_test {
             .returning(|_| Ok(()));
         logger
             .expect_log_line()
-            .with(eq("prefix "), eq(Line::from("test line")))
+            .with(eq(Direction::Out), eq(Line::from("test line")))
             .returning(|_, _| Ok(()));
         session.logger = Arc::new(Mutex::new(logger));
         let handler = EventHandler::from(&session);
         let _ = handler.log_str("prefix ", "test line");
-        let _ = handler.log_line("prefix ", &Line::from("test line"));
+        let _ = handler.log_line(Direction::Out, &Line::from("test line"));
+    }
+
+    #[test]
+    fn test_bell() {
+        let (session, reader, _timer_reader) = build_session();
+        let mut screen = MockUserInterface::new();
+        screen.expect_print_output().times(2).return_const(());
+        screen.expect_print_prompt().times(1).return_const(());
+        screen
+            .expect_flash()
+            .with(eq(true))
+            .times(1)
+            .returning(|_| Ok(()));
+        *session.bell.lock().unwrap() = BellSettings {
+            action: BellAction::Flash,
+            sound: None,
+        };
+        let handler = EventHandler::from(&session);
+        let mut screen: Box<dyn UserInterface> = Box::new(screen);
+        handler
+            .handle_output_events(Event::MudOutput(Line::from("no bell")), &mut screen)
+            .unwrap();
+        handler
+            .handle_output_events(Event::MudOutput(Line::from("bell\x07")), &mut screen)
+            .unwrap();
+        assert_eq!(reader.recv(), Ok(Event::Flash(false)));
+
+        *session.bell.lock().unwrap() = BellSettings {
+            action: BellAction::Speak,
+            sound: None,
+        };
+        handler
+            .handle_output_events(Event::Prompt(Line::from("bell\x07")), &mut screen)
+            .unwrap();
+        assert_eq!(reader.recv(), Ok(Event::Speak("Bell".to_string(), false)));
     }
 
     #[test]
@@ -566,6 +789,45 @@ mod event_test {
             .is_ok());
     }
 
+    #[test]
+    fn test_scroll_mode() {
+        let (session, _reader, _timer_reader) = build_session();
+        let mut screen = MockUserInterface::new();
+        screen
+            .expect_set_scroll_mode()
+            .times(1)
+            .with(eq(true))
+            .returning(|_| Ok(()));
+        screen
+            .expect_move_scroll_cursor()
+            .times(1)
+            .with(eq(ScrollMotion::Mark('a')))
+            .returning(|_| Ok(()));
+        screen
+            .expect_set_scroll_mark()
+            .times(1)
+            .with(eq('a'))
+            .returning(|_| Ok(()));
+        screen
+            .expect_yank_scroll_line()
+            .times(1)
+            .returning(|| Ok(()));
+        let handler = EventHandler::from(&session);
+        let mut screen: Box<dyn UserInterface> = Box::new(screen);
+        assert!(handler
+            .handle_scroll_events(Event::ScrollMode(true), &mut screen)
+            .is_ok());
+        assert!(handler
+            .handle_scroll_events(Event::ScrollMark('a'), &mut screen)
+            .is_ok());
+        assert!(handler
+            .handle_scroll_events(Event::ScrollCursor(ScrollMotion::Mark('a')), &mut screen)
+            .is_ok());
+        assert!(handler
+            .handle_scroll_events(Event::ScrollYank, &mut screen)
+            .is_ok());
+    }
+
     #[test]
     fn test_output() {
         let (mut session, _reader, _timer_reader) = build_session();
@@ -611,6 +873,46 @@ mod event_test {
             .is_ok());
     }
 
+    #[test]
+    fn test_logs_raw_output() {
+        let (mut session, _reader, _timer_reader) = build_session();
+        let mut logger = MockLogWriter::new();
+        logger
+            .expect_log_line()
+            .with(eq(Direction::Out), eq(Line::from("a goblin")))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        logger
+            .expect_log_line()
+            .with(eq(Direction::Prompt), eq(Line::from("hp 10")))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        session.logger = Arc::new(Mutex::new(logger));
+        session
+            .lua_script
+            .lock()
+            .unwrap()
+            .eval(
+                r#"
+                trigger.add("goblin", {}, function (_, line) line:replace("a dead goblin") end)
+                trigger.add("hp", {prompt=true, gag=true}, function () end)
+                "#,
+            )
+            .unwrap();
+        let handler = EventHandler::from(&session);
+
+        let mut screen = MockUserInterface::new();
+        screen.expect_print_output().return_const(());
+        screen.expect_print_prompt().return_const(());
+        let mut screen: Box<dyn UserInterface> = Box::new(screen);
+        handler
+            .handle_output_events(Event::MudOutput(Line::from("a goblin")), &mut screen)
+            .unwrap();
+        handler
+            .handle_output_events(Event::Prompt(Line::from("hp 10")), &mut screen)
+            .unwrap();
+    }
+
     #[test]
     fn test_spawn_quit_confirm_timeout_thread() {
         let (session, reader, _) = build_session();
@@ -628,6 +930,41 @@ mod event_test {
         assert_eq!(event, Event::QuitConfirmTimeout);
     }
 
+    #[test]
+    fn test_secret_input() {
+        let (mut session, _reader, _) = build_session();
+
+        let mut logger = MockLogWriter::new();
+        logger.expect_log_line().times(0);
+        session.logger = Arc::new(Mutex::new(logger));
+        session.echo_input.store(true, Ordering::Relaxed);
+
+        let mut line = Line::from("hunter2");
+        line.flags.secret = true;
+        let mut screen = MockUserInterface::new();
+        screen.expect_print_send().times(0);
+        screen
+            .expect_print_prompt_input()
+            .with(eq("*******"), eq(7))
+            .times(1)
+            .return_const(());
+
+        let mut handler = EventHandler::from(&session);
+        let mut screen: Box<dyn UserInterface> = Box::new(screen);
+        assert!(handler
+            .handle_server_events(Event::ServerInput(line), &mut screen, &mut None)
+            .is_ok());
+
+        session
+            .command_buffer
+            .lock()
+            .unwrap()
+            .set("hunter2".to_string());
+        assert!(handler
+            .handle_output_events(Event::SetSecretInput(true), &mut screen)
+            .is_ok());
+    }
+
     #[test]
     fn test_no_echo() {
         let (mut session, _reader, _) = build_session();
@@ -0,0 +1,241 @@
+use anyhow::Result;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+use vte::{Params, Parser, Perform};
+
+use crate::model::{Color, Line, Style};
+
+type Rgb = (u8, u8, u8);
+
+const DEFAULT_FG: Rgb = (0xe5, 0xe5, 0xe5);
+const DEFAULT_BG: Rgb = (0x00, 0x00, 0x00);
+
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The CSS for text printed in `style`.
+fn css(style: &Style) -> String {
+    let fg = style.fg.map(|color| color.rgb());
+    let bg = style.bg.map(|color| color.rgb());
+    let (fg, bg) = if style.reverse {
+        (
+            Some(bg.unwrap_or(DEFAULT_BG)),
+            Some(fg.unwrap_or(DEFAULT_FG)),
+        )
+    } else {
+        (fg, bg)
+    };
+    let mut css = vec![];
+    if let Some(fg) = fg {
+        css.push(format!("color:{}", hex(fg)));
+    }
+    if let Some(bg) = bg {
+        css.push(format!("background-color:{}", hex(bg)));
+    }
+    if style.bold {
+        css.push("font-weight:bold".to_string());
+    }
+    if style.dim {
+        css.push("opacity:0.7".to_string());
+    }
+    if style.italic {
+        css.push("font-style:italic".to_string());
+    }
+    match (style.underline, style.strike) {
+        (true, true) => css.push("text-decoration:underline line-through".to_string()),
+        (true, false) => css.push("text-decoration:underline".to_string()),
+        (false, true) => css.push("text-decoration:line-through".to_string()),
+        (false, false) => {}
+    }
+    css.join(";")
+}
+
+struct Performer {
+    style: Style,
+    output: String,
+    open: bool,
+}
+
+impl Performer {
+    fn close(&mut self) {
+        if self.open {
+            self.output.push_str("</span>");
+            self.open = false;
+        }
+    }
+}
+
+impl Perform for Performer {
+    fn print(&mut self, c: char) {
+        if !self.open && self.style != Style::default() {
+            self.output
+                .push_str(&format!("<span style=\"{}\">", css(&self.style)));
+            self.open = true;
+        }
+        self.output.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if byte == b'\t' {
+            self.output.push('\t');
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, _: &[u8], _: bool, action: char) {
+        if action == 'm' {
+            self.close();
+            let params: Vec<u16> = params.iter().flatten().copied().collect();
+            self.style.apply(&params);
+        }
+    }
+}
+
+/// Turns lines of output into HTML, keeping their colors and attributes.
+/// Everything but SGR sequences is dropped. Colors carry over from one line
+/// to the next like they would in a terminal.
+#[derive(Default)]
+pub struct HtmlRenderer {
+    style: Style,
+}
+
+impl HtmlRenderer {
+    pub fn render(&mut self, line: &str) -> String {
+        let mut performer = Performer {
+            style: self.style,
+            output: String::new(),
+            open: false,
+        };
+        Parser::new().advance(&mut performer, line.as_bytes());
+        performer.close();
+        self.style = performer.style;
+        performer.output
+    }
+}
+
+/// The start of an HTML document, lines go right after it.
+pub fn html_header(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\nbody {{ background-color: {}; color: {}; }}\n\
+         pre {{ font-family: monospace; white-space: pre-wrap; }}\n\
+         .time {{ color: {}; }}\n</style>\n</head>\n<body>\n<pre>\n",
+        escape(title),
+        hex(DEFAULT_BG),
+        hex(DEFAULT_FG),
+        hex(Color::Indexed(8).rgb()),
+    )
+}
+
+pub const HTML_FOOTER: &str = "</pre>\n</body>\n</html>\n";
+
+/// Writes lines of output to an HTML file.
+pub fn export_html(path: &Path, lines: &[Line]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    writer.write_all(html_header(&title).as_bytes())?;
+    let mut renderer = HtmlRenderer::default();
+    for line in lines {
+        writeln!(writer, "{}", renderer.render(line.line()))?;
+    }
+    writer.write_all(HTML_FOOTER.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod html_test {
+    use super::{export_html, HtmlRenderer};
+    use crate::model::Line;
+
+    fn render(line: &str) -> String {
+        HtmlRenderer::default().render(line)
+    }
+
+    #[test]
+    fn test_plain_text_is_escaped() {
+        assert_eq!(render("<a & b>"), "&lt;a &amp; b&gt;");
+    }
+
+    #[test]
+    fn test_colors() {
+        assert_eq!(
+            render("\x1b[31mred\x1b[0m plain"),
+            "<span style=\"color:#cd0000\">red</span> plain"
+        );
+        assert_eq!(
+            render("\x1b[1;4;92;44mx"),
+            "<span style=\"color:#00ff00;background-color:#0000ee;font-weight:bold;text-decoration:underline\">x</span>"
+        );
+        assert_eq!(
+            render("\x1b[38;5;196mx\x1b[38;2;1;2;3mx\x1b[38:5:21mx"),
+            "<span style=\"color:#ff0000\">x</span>\
+             <span style=\"color:#010203\">x</span>\
+             <span style=\"color:#0000ff\">x</span>"
+        );
+        assert_eq!(
+            render("\x1b[7mx"),
+            "<span style=\"color:#000000;background-color:#e5e5e5\">x</span>"
+        );
+    }
+
+    #[test]
+    fn test_style_carries_over_lines() {
+        let mut renderer = HtmlRenderer::default();
+        assert_eq!(
+            renderer.render("\x1b[32mgreen"),
+            "<span style=\"color:#00cd00\">green</span>"
+        );
+        assert_eq!(
+            renderer.render("still green\x1b[m"),
+            "<span style=\"color:#00cd00\">still green</span>"
+        );
+        assert_eq!(renderer.render("plain"), "plain");
+    }
+
+    #[test]
+    fn test_other_escapes_are_dropped() {
+        assert_eq!(
+            render("\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\\x1b[2K\x07"),
+            "link"
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        std::fs::create_dir_all(&*crate::DATA_DIR).ok();
+        let path = crate::DATA_DIR.join("test_export.html");
+        export_html(
+            &path,
+            &[Line::from("\x1b[31mred\x1b[0m"), Line::from("<b>")],
+        )
+        .unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("<!DOCTYPE html>"));
+        assert!(content.contains("<title>test_export</title>"));
+        assert!(content.contains("<span style=\"color:#cd0000\">red</span>\n&lt;b&gt;\n"));
+        assert!(content.ends_with("</html>\n"));
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,299 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    thread,
+};
+
+use crate::event::Event;
+use crate::io::logger::log_dir;
+use crate::io::replay::read_numbered_log;
+use crate::model::{Line, Regex};
+
+/// The most matches a search returns.
+const MAX_MATCHES: usize = 1000;
+
+/// What to look for in the saved logs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogQuery {
+    pub pattern: Regex,
+    pub world: Option<String>,
+    /// Skip lines logged before this time
+    pub since: Option<DateTime<Local>>,
+}
+
+/// A line found in a log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogMatch {
+    /// Path of the log within the log directory
+    pub file: String,
+    /// Line number within the log, starting at 1
+    pub line_no: usize,
+    /// When the line was logged, or when the log was last written to if it
+    /// has no timestamps
+    pub time: DateTime<Local>,
+    pub line: Line,
+}
+
+impl LogMatch {
+    /// The match with its file and date in front, as printed by `/logsearch`.
+    pub fn to_line(&self) -> Line {
+        Line::from(format!(
+            "\x1b[2m{}:{} [{}]\x1b[0m {}",
+            self.file,
+            self.line_no,
+            self.time.format("%Y-%m-%d %H:%M"),
+            self.line.line()
+        ))
+    }
+}
+
+/// A saved log file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LogFile {
+    path: PathBuf,
+    /// Path within the log directory
+    name: String,
+    /// The top directory the log is in
+    world: String,
+    modified: DateTime<Local>,
+}
+
+/// The files in the log directory, newest first.
+struct LogListing {
+    logs: Vec<LogFile>,
+}
+
+impl LogListing {
+    fn read(root: &Path) -> Self {
+        let mut logs = vec![];
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    dirs.push(path);
+                } else if let Ok(modified) = metadata.modified() {
+                    let Ok(relative) = path.strip_prefix(root) else {
+                        continue;
+                    };
+                    let name = relative.to_string_lossy().to_string();
+                    let world = relative
+                        .components()
+                        .next()
+                        .filter(|_| relative.components().count() > 1)
+                        .map(|world| world.as_os_str().to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    logs.push(LogFile {
+                        path,
+                        name,
+                        world,
+                        modified: modified.into(),
+                    });
+                }
+            }
+        }
+        logs.sort_by_key(|log| std::cmp::Reverse(log.modified));
+        Self { logs }
+    }
+
+    /// The logs of `world`, skipping those last written to before `since`.
+    fn logs<'a>(
+        &'a self,
+        world: Option<&'a str>,
+        since: Option<DateTime<Local>>,
+    ) -> impl Iterator<Item = &'a LogFile> {
+        self.logs.iter().filter(move |log| {
+            world.is_none_or(|world| log.world == world)
+                && since.is_none_or(|since| log.modified >= since)
+        })
+    }
+}
+
+/// Searches the logs in `root`, calling `found` for each match. Logs that
+/// can't be read back, like HTML logs, are skipped. Returns the number of
+/// logs searched.
+fn search(root: &Path, query: &LogQuery, mut found: impl FnMut(LogMatch) -> bool) -> usize {
+    let listing = LogListing::read(root);
+    let mut searched = 0;
+    for log in listing.logs(query.world.as_deref(), query.since) {
+        let Ok(entries) = read_numbered_log(&log.path) else {
+            continue;
+        };
+        searched += 1;
+        for (line_no, entry) in entries {
+            let time = entry.time().unwrap_or(log.modified);
+            if query.since.is_some_and(|since| time < since)
+                || !query.pattern.is_match(entry.line.clean_line())
+            {
+                continue;
+            }
+            let result = LogMatch {
+                file: log.name.clone(),
+                line_no,
+                time,
+                line: entry.line,
+            };
+            if !found(result) {
+                return searched;
+            }
+        }
+    }
+    searched
+}
+
+/// Searches all saved logs, newest log first.
+fn search_logs(query: &LogQuery) -> Vec<LogMatch> {
+    let mut matches = vec![];
+    search(&log_dir(), query, |result| {
+        matches.push(result);
+        matches.len() < MAX_MATCHES
+    });
+    matches
+}
+
+/// Searches all saved logs in the background. The matches are sent together
+/// as a `Event::LogSearchResults` for the Lua callback `callback`, or one by
+/// one as `Event::LogSearchMatch` to be printed when there's no callback.
+pub fn spawn_log_search(query: LogQuery, callback: Option<u32>, writer: Sender<Event>) {
+    thread::Builder::new()
+        .name("log-search-thread".to_string())
+        .spawn(move || {
+            if let Some(id) = callback {
+                writer
+                    .send(Event::LogSearchResults(id, search_logs(&query)))
+                    .ok();
+                return;
+            }
+            let mut count = 0;
+            let searched = search(&log_dir(), &query, |result| {
+                count += 1;
+                writer.send(Event::LogSearchMatch(result)).is_ok() && count < MAX_MATCHES
+            });
+            let limit = if count == MAX_MATCHES {
+                " (limit reached)"
+            } else {
+                ""
+            };
+            writer
+                .send(Event::Info(format!(
+                    "Found {count} matches in {searched} logs{limit}"
+                )))
+                .ok();
+        })
+        .ok();
+}
+
+/// Parses a date like `2024-03-01` or a time ago like `12h`, `3d` or `2w`.
+pub fn parse_since(since: &str) -> Result<DateTime<Local>> {
+    if let Ok(date) = NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+        if let Some(time) = Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+        {
+            return Ok(time);
+        }
+    }
+    let mut chars = since.chars();
+    let unit = chars.next_back();
+    if let Ok(count) = chars.as_str().parse::<i64>() {
+        let ago = match unit {
+            Some('h') => Some(Duration::hours(count)),
+            Some('d') => Some(Duration::days(count)),
+            Some('w') => Some(Duration::weeks(count)),
+            _ => None,
+        };
+        if let Some(ago) = ago {
+            return Ok(Local::now() - ago);
+        }
+    }
+    bail!("Invalid time: {since}, use eg. 2024-03-01, 12h, 3d or 2w")
+}
+
+#[cfg(test)]
+mod log_search_test {
+    use super::*;
+
+    fn build_logs(name: &str) -> PathBuf {
+        let root = crate::DATA_DIR.join(name);
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(root.join("arctic")).unwrap();
+        fs::create_dir_all(root.join("other")).unwrap();
+        fs::write(
+            root.join("arctic/20240301.log"),
+            "[2024-03-01 14:05:09] Bob gives you a sword.\n\
+             [2024-03-02 10:00:00] Alice gives you a sword.\n",
+        )
+        .unwrap();
+        fs::write(root.join("other/20240301.log"), "Carl gives you a sword.\n").unwrap();
+        fs::write(root.join("other/20240301.html"), "<pre>a sword</pre>\n").unwrap();
+        root
+    }
+
+    fn query(world: Option<&str>, since: Option<DateTime<Local>>) -> LogQuery {
+        LogQuery {
+            pattern: Regex::new("gives you a sword", None).unwrap(),
+            world: world.map(|world| world.to_string()),
+            since,
+        }
+    }
+
+    fn run(root: &Path, query: &LogQuery) -> (Vec<LogMatch>, usize) {
+        let mut matches = vec![];
+        let searched = search(root, query, |result| {
+            matches.push(result);
+            true
+        });
+        (matches, searched)
+    }
+
+    #[test]
+    fn test_search() {
+        let root = build_logs("test_log_search");
+        let (matches, searched) = run(&root, &query(None, None));
+        assert_eq!(searched, 2);
+        assert_eq!(matches.len(), 3);
+
+        let (matches, _) = run(&root, &query(Some("arctic"), None));
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[1].file, "arctic/20240301.log");
+        assert_eq!(matches[1].line_no, 2);
+        assert_eq!(
+            matches[1].to_line().clean_line(),
+            "arctic/20240301.log:2 [2024-03-02 10:00] Alice gives you a sword."
+        );
+
+        let since = Local.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+        let (matches, _) = run(&root, &query(Some("arctic"), Some(since)));
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].line.line().starts_with("Alice"));
+
+        let (matches, _) = run(&root, &query(None, Some(Local::now())));
+        assert!(matches.is_empty());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(
+            parse_since("2024-03-01").unwrap(),
+            Local.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
+        );
+        let ago = Local::now() - parse_since("3w").unwrap();
+        assert!((ago - Duration::weeks(3)).num_seconds().abs() < 5);
+        assert!(parse_since("12h").is_ok());
+        assert!(parse_since("3x").is_err());
+        assert!(parse_since("yesterday").is_err());
+        assert!(parse_since("").is_err());
+        assert!(parse_since("3é").is_err());
+        assert!(parse_since("é").is_err());
+    }
+}
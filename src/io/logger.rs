@@ -1,23 +1,188 @@
-use anyhow::Result;
-use chrono::{self, Local};
+use anyhow::{bail, Result};
+use chrono::{self, DateTime, Duration, Local, SecondsFormat};
+use flate2::{write::GzEncoder, Compression};
+use log::error;
+use serde::{Deserialize, Serialize};
 use std::{
-    fs::File,
+    collections::BTreeMap,
+    fs::{self, File},
     io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 use strip_ansi_escapes::Writer as StripWriter;
 
 #[cfg(test)]
 use mockall::automock;
 
-use crate::model::Line;
+use crate::io::html::{html_header, HtmlRenderer, HTML_FOOTER};
+use crate::io::SaveData;
+use crate::model::{Line, Settings, LOG_TIMESTAMPS};
+
+const LOG_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The kind of file logs are written to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogFormat {
+    /// Plain text without colors
+    #[default]
+    Text,
+    /// An HTML page keeping the colors
+    Html,
+    /// One JSON record per line
+    Json,
+}
+
+impl LogFormat {
+    pub fn named(name: &str) -> Result<Self> {
+        match name {
+            "text" => Ok(Self::Text),
+            "html" => Ok(Self::Html),
+            "json" => Ok(Self::Json),
+            _ => bail!("Unknown log format: {name}"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Html => "html",
+            Self::Json => "json",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Text => "log",
+            Self::Html => "html",
+            Self::Json => "jsonl",
+        }
+    }
+}
+
+/// Logging options that aren't on/off switches, which is all `Settings`
+/// holds, so they're saved in their own file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// The format new logs are written in
+    pub format: LogFormat,
+    /// Formats used instead of `format` for specific worlds
+    pub worlds: BTreeMap<String, LogFormat>,
+    /// Path of new log files within the log directory, without extension
+    pub template: String,
+    /// Start a new file once the current one grows past this many bytes
+    pub max_size: Option<u64>,
+    /// Start a new file when the day changes
+    pub daily: bool,
+    /// Compress files with gzip once a new file is started
+    pub compress: bool,
+    /// Delete logs older than this many days
+    pub retention: Option<u64>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            worlds: BTreeMap::new(),
+            template: "{world}/{date}.{time}".to_string(),
+            max_size: None,
+            daily: false,
+            compress: false,
+            retention: None,
+        }
+    }
+}
+
+impl LogSettings {
+    /// The format logs for `world` are written in.
+    pub fn format_for(&self, world: &str) -> LogFormat {
+        self.worlds.get(world).copied().unwrap_or(self.format)
+    }
+}
+
+impl SaveData for LogSettings {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("logging.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+/// Options for a single log.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogOptions {
+    /// The character name used in file names
+    pub character: Option<String>,
+    /// Used instead of the saved settings
+    pub settings: Option<LogSettings>,
+}
+
+/// Where a logged line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Sent to the server
+    In,
+    /// Received from the server or printed by a script
+    Out,
+    Prompt,
+    /// Messages from Blightmud itself
+    Info,
+}
+
+/// A record in a JSON log.
+#[derive(Serialize)]
+struct LogRecord<'a> {
+    time: String,
+    direction: Direction,
+    connection: &'a str,
+    raw: &'a str,
+    clean: &'a str,
+    matched: bool,
+}
+
+/// A log file counting the bytes written to it, to rotate by size without
+/// asking the file system after every line.
+struct CountingFile {
+    file: File,
+    written: Arc<AtomicU64>,
+}
+
+impl Write for CountingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let count = self.file.write(buf)?;
+        self.written.fetch_add(count as u64, Ordering::Relaxed);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+enum LogFile {
+    Text(Box<BufWriter<StripWriter<CountingFile>>>),
+    Html(BufWriter<CountingFile>, HtmlRenderer),
+    Json(BufWriter<CountingFile>),
+}
 
 #[cfg_attr(test, automock)]
 pub trait LogWriter {
-    fn start_logging(&mut self, host: &str) -> Result<()>;
+    fn start_logging(&mut self, host: &str, options: &LogOptions) -> Result<()>;
 
     fn log_str(&mut self, line: &str) -> Result<()>;
 
-    fn log_line(&mut self, prefix: &str, line: &Line) -> Result<()>;
+    fn log_line(&mut self, direction: Direction, line: &Line) -> Result<()>;
+
+    /// Prefix logged lines with the time they were received.
+    fn set_timestamps(&mut self, enabled: bool);
 
     fn stop_logging(&mut self) -> Result<()>;
 
@@ -25,53 +190,313 @@ pub trait LogWriter {
     fn is_logging(&self) -> bool;
 }
 
-#[derive(Default)]
 pub struct Logger {
-    file: Option<BufWriter<StripWriter<File>>>,
+    file: Option<LogFile>,
+    /// The file currently written to
+    path: PathBuf,
+    /// When the current file was started
+    opened: DateTime<Local>,
+    /// Bytes written to the current file
+    written: Arc<AtomicU64>,
+    world: String,
+    character: Option<String>,
+    settings: LogSettings,
+    timestamps: bool,
 }
 
-fn get_and_ensure_log_dir(host: &str) -> std::path::PathBuf {
-    let path = crate::DATA_DIR.clone().join("logs").join(host);
-    std::fs::create_dir_all(&path).ok();
-    path
+impl Default for Logger {
+    fn default() -> Self {
+        let timestamps = Settings::load().get(LOG_TIMESTAMPS).unwrap_or_default();
+        Self {
+            file: None,
+            path: PathBuf::new(),
+            opened: Local::now(),
+            written: Arc::new(AtomicU64::new(0)),
+            world: String::new(),
+            character: None,
+            settings: LogSettings::default(),
+            timestamps,
+        }
+    }
 }
 
-impl LogWriter for Logger {
-    fn start_logging(&mut self, host: &str) -> Result<()> {
+impl Logger {
+    /// Starts a new file named after the settings template.
+    fn open(&mut self) -> Result<()> {
+        let now = Local::now();
+        let format = self.settings.format_for(&self.world);
+        let name = fill_template(
+            &self.settings.template,
+            &self.world,
+            self.character.as_deref(),
+            &now,
+        );
+        let path = free_path(&log_dir(), &name, format.extension());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.written.store(0, Ordering::Relaxed);
+        let file = CountingFile {
+            file: File::create(&path)?,
+            written: self.written.clone(),
+        };
+        self.file = Some(match format {
+            LogFormat::Text => LogFile::Text(Box::new(BufWriter::new(StripWriter::new(file)))),
+            LogFormat::Html => {
+                let mut writer = BufWriter::new(file);
+                let title = format!("{} {}", self.world, now.format(LOG_TIME_FORMAT));
+                writer.write_all(html_header(&title).as_bytes())?;
+                LogFile::Html(writer, HtmlRenderer::default())
+            }
+            LogFormat::Json => LogFile::Json(BufWriter::new(file)),
+        });
+        self.path = path;
+        self.opened = now;
+        if let Some(days) = self.settings.retention {
+            let template = &self.settings.template;
+            let character = self.character.as_deref();
+            let root = template_root(template, &self.world, character);
+            let pattern = template_pattern(template, &self.world, character);
+            remove_old_logs(&root, &self.path, &pattern, days);
+        }
+        Ok(())
+    }
+
+    /// Finishes the current file.
+    fn close(&mut self) -> Result<()> {
+        match self.file.take() {
+            Some(LogFile::Text(mut writer)) => writer.flush()?,
+            Some(LogFile::Html(mut writer, _)) => {
+                writer.write_all(HTML_FOOTER.as_bytes())?;
+                writer.flush()?;
+            }
+            Some(LogFile::Json(mut writer)) => writer.flush()?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn should_rotate(&self) -> bool {
         if self.file.is_none() {
-            let path = get_and_ensure_log_dir(host);
+            return false;
+        }
+        let new_day = self.settings.daily && Local::now().date_naive() != self.opened.date_naive();
+        let too_big = self
+            .settings
+            .max_size
+            .is_some_and(|max| self.written.load(Ordering::Relaxed) >= max);
+        new_day || too_big
+    }
 
-            let logfile = path.join(format!("{}.log", Local::now().format("%Y%m%d.%H:%M:%S")));
-            self.file = Some(BufWriter::new(StripWriter::new(File::create(logfile)?)));
+    /// Moves on to a new file when the current one is full or a day old.
+    fn rotate(&mut self) -> Result<()> {
+        if self.should_rotate() {
+            self.close()?;
+            if self.settings.compress {
+                if let Err(err) = compress(&self.path) {
+                    error!("Failed to compress {:?}: {err}", self.path);
+                }
+            }
+            self.open()?;
         }
         Ok(())
     }
 
-    fn log_str(&mut self, line: &str) -> Result<()> {
-        if let Some(mut writer) = self.file.take() {
-            writer.write_all(line.as_bytes())?;
-            if !line.ends_with('\n') {
-                writer.write_all(b"\n")?;
+    fn write_line(&mut self, time: &DateTime<Local>, line: &str) -> Result<()> {
+        let timestamp = if self.timestamps {
+            format!("[{}] ", time.format(LOG_TIME_FORMAT))
+        } else {
+            String::new()
+        };
+        match &mut self.file {
+            Some(LogFile::Text(writer)) => {
+                writer.write_all(timestamp.as_bytes())?;
+                writer.write_all(line.as_bytes())?;
+                if !line.ends_with('\n') {
+                    writer.write_all(b"\n")?;
+                }
+                writer.flush()?;
             }
+            Some(LogFile::Html(writer, renderer)) => {
+                if !timestamp.is_empty() {
+                    write!(writer, "<span class=\"time\">{timestamp}</span>")?;
+                }
+                for segment in line.trim_end_matches('\n').split('\n') {
+                    writeln!(writer, "{}", renderer.render(segment))?;
+                }
+                writer.flush()?;
+            }
+            // Records are written by `write_record`
+            Some(LogFile::Json(_)) | None => {}
+        }
+        Ok(())
+    }
+
+    fn write_record(&mut self, direction: Direction, line: &Line) -> Result<()> {
+        if let Some(LogFile::Json(writer)) = &mut self.file {
+            let record = LogRecord {
+                time: line.time().to_rfc3339_opts(SecondsFormat::Millis, false),
+                direction,
+                connection: &self.world,
+                raw: line.line(),
+                clean: line.clean_line(),
+                matched: line.flags.matched,
+            };
+            serde_json::to_writer(&mut *writer, &record)?;
+            writer.write_all(b"\n")?;
             writer.flush()?;
-            self.file = Some(writer);
         }
         Ok(())
     }
+}
+
+pub fn log_dir() -> PathBuf {
+    crate::DATA_DIR.join("logs")
+}
+
+/// Fills in the `{world}`, `{character}`, `{date}` and `{time}` placeholders
+/// of a file name template.
+fn fill_template(
+    template: &str,
+    world: &str,
+    character: Option<&str>,
+    time: &DateTime<Local>,
+) -> String {
+    template
+        .replace("{world}", &clean_name(world))
+        .replace("{character}", &clean_name(character.unwrap_or("unknown")))
+        .replace("{date}", &time.format("%Y%m%d").to_string())
+        .replace("{time}", &time.format("%H:%M:%S").to_string())
+}
+
+/// Names may not add directories of their own.
+fn clean_name(name: &str) -> String {
+    name.replace(['/', '\\'], "_")
+}
 
-    fn log_line(&mut self, prefix: &str, line: &Line) -> Result<()> {
-        if let Some(line) = line.log_line() {
-            self.log_str(&format!("{}{}", prefix, &line))
+/// Matches the paths within the log directory of the files `fill_template`
+/// names for `world` and `character` at any time, see `free_path`.
+fn template_pattern(template: &str, world: &str, character: Option<&str>) -> regex::Regex {
+    let name = regex::escape(template)
+        .replace(r"\{world\}", &regex::escape(&clean_name(world)))
+        .replace(
+            r"\{character\}",
+            &regex::escape(&clean_name(character.unwrap_or("unknown"))),
+        )
+        .replace(r"\{date\}", r"\d{8}")
+        .replace(r"\{time\}", r"\d{2}:\d{2}:\d{2}");
+    regex::Regex::new(&format!(r"^{name}(-\d+)?\.(log|html|jsonl)(\.gz)?$"))
+        .expect("escaped template is a valid regex")
+}
+
+/// The deepest directory holding all the files `fill_template` names for
+/// `world` and `character`, made of the directories of the template before
+/// the first one changing with the date or time.
+fn template_root(template: &str, world: &str, character: Option<&str>) -> PathBuf {
+    let mut root = log_dir();
+    let mut dirs: Vec<&str> = template.split('/').collect();
+    dirs.pop();
+    for dir in dirs {
+        if dir.contains("{date}") || dir.contains("{time}") {
+            break;
+        }
+        root.push(fill_template(dir, world, character, &Local::now()));
+    }
+    root
+}
+
+/// A path for `name` in `dir` that isn't taken yet.
+fn free_path(dir: &Path, name: &str, extension: &str) -> PathBuf {
+    let mut path = dir.join(format!("{name}.{extension}"));
+    let mut count = 1;
+    while path.exists() || path.with_extension(format!("{extension}.gz")).exists() {
+        path = dir.join(format!("{name}-{count}.{extension}"));
+        count += 1;
+    }
+    path
+}
+
+/// Replaces a log file with a gzip compressed copy.
+fn compress(path: &Path) -> Result<()> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let mut encoder = GzEncoder::new(File::create(gz_path)?, Compression::default());
+    std::io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Deletes logs anywhere below `root` other than `current` that haven't been
+/// written to in `days` days. Only files whose path within the log directory
+/// matches `pattern` are deleted, leaving the logs of other worlds and
+/// characters alone.
+fn remove_old_logs(root: &Path, current: &Path, pattern: &regex::Regex, days: u64) {
+    let Ok(entries) = fs::read_dir(root) else {
+        return;
+    };
+    let limit = Local::now() - Duration::days(days as i64);
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            remove_old_logs(&path, current, pattern, days);
+            continue;
+        }
+        let is_log = path
+            .strip_prefix(log_dir())
+            .is_ok_and(|name| pattern.is_match(&name.to_string_lossy()));
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map(DateTime::<Local>::from);
+        if is_log && path != current && modified.is_ok_and(|modified| modified < limit) {
+            if let Err(err) = fs::remove_file(&path) {
+                error!("Failed to remove old log {path:?}: {err}");
+            }
+        }
+    }
+}
+
+impl LogWriter for Logger {
+    fn start_logging(&mut self, host: &str, options: &LogOptions) -> Result<()> {
+        if self.file.is_none() {
+            self.world = host.to_string();
+            self.character = options.character.clone();
+            self.settings = options.settings.clone().unwrap_or_else(LogSettings::load);
+            self.open()?;
+        }
+        Ok(())
+    }
+
+    fn log_str(&mut self, line: &str) -> Result<()> {
+        self.rotate()?;
+        if let Some(LogFile::Json(_)) = self.file {
+            return self.write_record(Direction::Info, &Line::from(line));
+        }
+        self.write_line(&Local::now(), line)
+    }
+
+    fn log_line(&mut self, direction: Direction, line: &Line) -> Result<()> {
+        if let Some(content) = line.log_line() {
+            self.rotate()?;
+            // HTML logs keep the colors the clean content has lost
+            let content = match self.file {
+                Some(LogFile::Json(_)) => return self.write_record(direction, line),
+                Some(LogFile::Html(..)) => line.line(),
+                _ => content,
+            };
+            let prefix = if direction == Direction::In { "> " } else { "" };
+            self.write_line(&line.time(), &format!("{prefix}{content}"))
         } else {
             Ok(())
         }
     }
 
+    fn set_timestamps(&mut self, enabled: bool) {
+        self.timestamps = enabled;
+    }
+
     fn stop_logging(&mut self) -> Result<()> {
-        if let Some(mut writer) = self.file.take() {
-            writer.flush()?;
-        }
-        Ok(())
+        self.close()
     }
 
     #[cfg(test)]
@@ -84,12 +509,25 @@ impl LogWriter for Logger {
 mod logger_tests {
 
     use super::*;
+    use chrono::TimeZone;
+
+    fn with_format(format: LogFormat) -> LogOptions {
+        LogOptions {
+            character: None,
+            settings: Some(LogSettings {
+                format,
+                ..LogSettings::default()
+            }),
+        }
+    }
 
     #[test]
     fn test_logger() {
         let mut logger = Logger::default();
         assert!(!logger.is_logging());
-        logger.start_logging("hostname").unwrap();
+        logger
+            .start_logging("hostname", &LogOptions::default())
+            .unwrap();
         assert!(logger.is_logging());
         logger.stop_logging().unwrap();
         assert!(!logger.is_logging());
@@ -104,9 +542,13 @@ mod logger_tests {
     #[test]
     fn test_logger_start_twice() {
         let mut logger = Logger::default();
-        logger.start_logging("test_host").unwrap();
+        logger
+            .start_logging("test_host", &LogOptions::default())
+            .unwrap();
         // Starting again should be a no-op (file already exists)
-        logger.start_logging("test_host").unwrap();
+        logger
+            .start_logging("test_host", &LogOptions::default())
+            .unwrap();
         assert!(logger.is_logging());
         logger.stop_logging().unwrap();
     }
@@ -114,7 +556,9 @@ mod logger_tests {
     #[test]
     fn test_logger_log_str() {
         let mut logger = Logger::default();
-        logger.start_logging("test_host_log").unwrap();
+        logger
+            .start_logging("test_host_log", &LogOptions::default())
+            .unwrap();
         assert!(logger.log_str("test line").is_ok());
         assert!(logger.log_str("test line with newline\n").is_ok());
         logger.stop_logging().unwrap();
@@ -130,10 +574,116 @@ mod logger_tests {
     #[test]
     fn test_logger_log_line() {
         let mut logger = Logger::default();
-        logger.start_logging("test_host_line").unwrap();
+        logger
+            .start_logging("test_host_line", &LogOptions::default())
+            .unwrap();
         let line = Line::from("test output line");
-        assert!(logger.log_line("[<<] ", &line).is_ok());
+        assert!(logger.log_line(Direction::Out, &line).is_ok());
+        logger.stop_logging().unwrap();
+    }
+
+    #[test]
+    fn test_logger_timestamps() {
+        let dir = log_dir().join("test_host_timestamps");
+        std::fs::remove_dir_all(&dir).ok();
+        let mut logger = Logger::default();
+        logger.set_timestamps(true);
+        logger
+            .start_logging("test_host_timestamps", &with_format(LogFormat::Text))
+            .unwrap();
+        let mut line = Line::from("stamped line");
+        line.set_time(Local.with_ymd_and_hms(2024, 3, 1, 14, 5, 9).unwrap());
+        logger.log_line(Direction::Out, &line).unwrap();
+        logger.set_timestamps(false);
+        logger.log_line(Direction::Out, &line).unwrap();
         logger.stop_logging().unwrap();
+
+        let file = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(
+            content,
+            "[2024-03-01 14:05:09] stamped line\nstamped line\n"
+        );
+    }
+
+    #[test]
+    fn test_logger_html() {
+        let dir = log_dir().join("test_host_html");
+        std::fs::remove_dir_all(&dir).ok();
+        let mut logger = Logger::default();
+        logger.set_timestamps(true);
+        logger
+            .start_logging("test_host_html", &with_format(LogFormat::Html))
+            .unwrap();
+        let mut line = Line::from("\x1b[31m<red>\x1b[0m");
+        line.set_time(Local.with_ymd_and_hms(2024, 3, 1, 14, 5, 9).unwrap());
+        logger.log_line(Direction::Out, &line).unwrap();
+        logger.stop_logging().unwrap();
+
+        let file = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        assert_eq!(file.path().extension().unwrap(), "html");
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        assert!(content.starts_with("<!DOCTYPE html>"));
+        assert!(content.contains(
+            "<span class=\"time\">[2024-03-01 14:05:09] </span>\
+             <span style=\"color:#cd0000\">&lt;red&gt;</span>\n"
+        ));
+        assert!(content.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_logger_json() {
+        let dir = log_dir().join("test_host_json");
+        std::fs::remove_dir_all(&dir).ok();
+        let mut logger = Logger::default();
+        logger
+            .start_logging("test_host_json", &with_format(LogFormat::Json))
+            .unwrap();
+        let time = Local.with_ymd_and_hms(2024, 3, 1, 14, 5, 9).unwrap();
+        let mut line = Line::from("\x1b[31mA goblin\x1b[0m");
+        line.set_time(time);
+        logger.log_line(Direction::Out, &line).unwrap();
+        let mut input = Line::from("kill goblin");
+        input.set_time(time);
+        input.flags.matched = true;
+        logger.log_line(Direction::In, &input).unwrap();
+        logger.log_str("[**] Connected").unwrap();
+        logger.stop_logging().unwrap();
+
+        let file = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        assert_eq!(file.path().extension().unwrap(), "jsonl");
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        let records: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[0],
+            serde_json::json!({
+                "time": time.to_rfc3339_opts(SecondsFormat::Millis, false),
+                "direction": "out",
+                "connection": "test_host_json",
+                "raw": "\x1b[31mA goblin\x1b[0m",
+                "clean": "A goblin",
+                "matched": false,
+            })
+        );
+        assert_eq!(records[1]["direction"], "in");
+        assert_eq!(records[1]["clean"], "kill goblin");
+        assert_eq!(records[1]["matched"], true);
+        assert_eq!(records[2]["direction"], "info");
+        assert_eq!(records[2]["clean"], "[**] Connected");
+    }
+
+    #[test]
+    fn test_format_for_world() {
+        let mut settings = LogSettings::default();
+        settings
+            .worlds
+            .insert("arctic".to_string(), LogFormat::Json);
+        assert_eq!(settings.format_for("arctic"), LogFormat::Json);
+        assert_eq!(settings.format_for("other"), LogFormat::Text);
     }
 
     #[test]
@@ -144,8 +694,166 @@ mod logger_tests {
     }
 
     #[test]
-    fn test_get_and_ensure_log_dir() {
-        let path = get_and_ensure_log_dir("test_create_dir");
-        assert!(path.to_string_lossy().contains("test_create_dir"));
+    fn test_fill_template() {
+        let time = Local.with_ymd_and_hms(2024, 3, 1, 14, 5, 9).unwrap();
+        assert_eq!(
+            fill_template("{world}/{date}.{time}", "arctic", None, &time),
+            "arctic/20240301.14:05:09"
+        );
+        assert_eq!(
+            fill_template("{world}/{character}-{date}", "arc/tic", Some("Bob"), &time),
+            "arc_tic/Bob-20240301"
+        );
+        assert_eq!(
+            fill_template("{character}", "arctic", None, &time),
+            "unknown"
+        );
+    }
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = log_dir().join("test_host_rotate");
+        std::fs::remove_dir_all(&dir).ok();
+        let mut logger = Logger::default();
+        logger.set_timestamps(false);
+        let options = LogOptions {
+            character: Some("Bob".to_string()),
+            settings: Some(LogSettings {
+                template: "{world}/{character}".to_string(),
+                max_size: Some(10),
+                compress: true,
+                ..LogSettings::default()
+            }),
+        };
+        logger.start_logging("test_host_rotate", &options).unwrap();
+        logger.log_str("first line").unwrap();
+        logger.log_str("second line").unwrap();
+        logger.stop_logging().unwrap();
+
+        let mut files: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["Bob-1.log", "Bob.log.gz"]);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("Bob-1.log")).unwrap(),
+            "second line\n"
+        );
+        let mut content = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(File::open(dir.join("Bob.log.gz")).unwrap()),
+            &mut content,
+        )
+        .unwrap();
+        assert_eq!(content, "first line\n");
+    }
+
+    #[test]
+    fn test_rotate_daily() {
+        let dir = log_dir().join("test_host_daily");
+        std::fs::remove_dir_all(&dir).ok();
+        let mut logger = Logger::default();
+        let options = LogOptions {
+            character: None,
+            settings: Some(LogSettings {
+                daily: true,
+                ..LogSettings::default()
+            }),
+        };
+        logger.start_logging("test_host_daily", &options).unwrap();
+        logger.log_str("today").unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        logger.opened = Local::now() - Duration::days(1);
+        logger.log_str("tomorrow").unwrap();
+        logger.stop_logging().unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_remove_old_logs() {
+        let dir = log_dir().join("test_host_retention");
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("Bob-20240301.log.gz");
+        let other = dir.join("notes.txt");
+        let alice = dir.join("Alice-20240301.log");
+        let current = dir.join("Bob-20240304.log");
+        for path in [&old, &other, &alice, &current] {
+            File::create(path).unwrap();
+        }
+        let past = std::time::SystemTime::now() - std::time::Duration::from_secs(3 * 86400);
+        for path in [&old, &other, &alice] {
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(past)
+                .unwrap();
+        }
+        let pattern = template_pattern(
+            "{world}/{character}-{date}",
+            "test_host_retention",
+            Some("Bob"),
+        );
+        remove_old_logs(&dir, &current, &pattern, 5);
+        assert!(old.exists());
+        remove_old_logs(&dir, &current, &pattern, 2);
+        assert!(!old.exists());
+        assert!(other.exists());
+        assert!(alice.exists());
+        assert!(current.exists());
+    }
+
+    #[test]
+    fn test_remove_old_logs_in_dated_dirs() {
+        let template = "{world}/{date}/{character}-{time}";
+        let root = template_root(template, "test_host_dated", Some("Bob"));
+        assert_eq!(root, log_dir().join("test_host_dated"));
+        std::fs::remove_dir_all(&root).ok();
+        let old = root.join("20240301/Bob-10:00:00.log");
+        let alice = root.join("20240301/Alice-10:00:00.log");
+        let current = root.join("20240304/Bob-09:00:00.log");
+        for path in [&old, &alice, &current] {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(path).unwrap();
+        }
+        let past = std::time::SystemTime::now() - std::time::Duration::from_secs(3 * 86400);
+        for path in [&old, &alice] {
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(past)
+                .unwrap();
+        }
+        let pattern = template_pattern(template, "test_host_dated", Some("Bob"));
+        remove_old_logs(&root, &current, &pattern, 2);
+        assert!(!old.exists());
+        assert!(alice.exists());
+        assert!(current.exists());
+    }
+
+    #[test]
+    fn test_template_root() {
+        assert_eq!(
+            template_root("{world}/{character}/{date}", "arc/tic", Some("Bob")),
+            log_dir().join("arc_tic").join("Bob")
+        );
+        assert_eq!(template_root("{date}/{world}", "arctic", None), log_dir());
+        assert_eq!(template_root("{world}", "arctic", None), log_dir());
+    }
+
+    #[test]
+    fn test_template_pattern() {
+        let pattern = template_pattern("{world}/{date}.{time}", "arc.tic", None);
+        assert!(pattern.is_match("arc.tic/20240301.14:05:09.log"));
+        assert!(pattern.is_match("arc.tic/20240301.14:05:09-2.jsonl.gz"));
+        assert!(!pattern.is_match("arcxtic/20240301.14:05:09.log"));
+        assert!(!pattern.is_match("arc.tic/notes.log"));
+        assert!(!pattern.is_match("arc.tic/20240301.14:05:09.txt"));
+        let pattern = template_pattern("{world}/{character}", "arc/tic", Some("Bob"));
+        assert!(pattern.is_match("arc_tic/Bob.html"));
+        assert!(!pattern.is_match("arc_tic/Alice.html"));
     }
 }
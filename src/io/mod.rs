@@ -1,12 +1,20 @@
 mod exec;
 mod fs_monitor;
+mod html;
+mod log_search;
 pub mod logger;
+mod replay;
 mod save;
+mod scrollback;
 
 pub use exec::{exec, exec_args};
 pub use fs_monitor::{FSEvent, FSMonitor};
-pub use logger::{LogWriter, Logger};
+pub use html::export_html;
+pub use log_search::{parse_since, spawn_log_search, LogMatch, LogQuery};
+pub use logger::{Direction, LogFormat, LogOptions, LogSettings, LogWriter, Logger};
+pub use replay::{find_log, list_logs, read_log, Replay, ReplayEntry};
 pub use save::SaveData;
+pub use scrollback::{Scrollback, ScrollbackSettings};
 
 #[cfg(test)]
 pub use logger::MockLogWriter;
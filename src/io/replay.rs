@@ -0,0 +1,333 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::debug;
+
+use crate::event::Event;
+use crate::io::logger::{log_dir, Direction};
+use crate::model::Line;
+use crate::tools::util::expand_tilde;
+
+/// The longest pause between two replayed lines, before applying the speed.
+const MAX_GAP: Duration = Duration::from_secs(2);
+/// The pause between lines of logs without timestamps.
+const LINE_DELAY: Duration = Duration::from_millis(30);
+
+/// A line read back from a log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayEntry {
+    pub direction: Direction,
+    pub line: Line,
+    /// When the line was logged, if the log has timestamps
+    time: Option<DateTime<Local>>,
+}
+
+impl ReplayEntry {
+    fn new(direction: Direction, content: &str, time: Option<DateTime<Local>>) -> Self {
+        let mut line = Line::from(content);
+        if let Some(time) = time {
+            line.set_time(time);
+        }
+        Self {
+            direction,
+            line,
+            time,
+        }
+    }
+
+    /// When the line was logged, if the log has timestamps.
+    pub fn time(&self) -> Option<DateTime<Local>> {
+        self.time
+    }
+}
+
+#[derive(Deserialize)]
+struct Record {
+    time: String,
+    direction: Direction,
+    raw: String,
+}
+
+/// Parses a line of a text log, see `Logger`.
+fn parse_text_line(line: &str) -> ReplayEntry {
+    let stamp = line
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("] "))
+        .and_then(|(stamp, rest)| {
+            NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .and_then(|time| Local.from_local_datetime(&time).single())
+                .map(|time| (time, rest))
+        });
+    let (time, content) = match stamp {
+        Some((time, rest)) => (Some(time), rest),
+        None => (None, line),
+    };
+    let direction = if content.starts_with("> ") {
+        Direction::In
+    } else if content.starts_with("[**] ") || content.starts_with("[!!] ") {
+        Direction::Info
+    } else {
+        Direction::Out
+    };
+    ReplayEntry::new(direction, content, time)
+}
+
+fn parse_json_line(line: &str) -> Result<ReplayEntry> {
+    let record: Record = serde_json::from_str(line)?;
+    let time = DateTime::parse_from_rfc3339(&record.time)?.with_timezone(&Local);
+    let content = if record.direction == Direction::In {
+        format!("> {}", record.raw)
+    } else {
+        record.raw
+    };
+    Ok(ReplayEntry::new(record.direction, &content, Some(time)))
+}
+
+/// Reads back a text or JSON log, gzip compressed or not.
+pub fn read_log(path: &Path) -> Result<Vec<ReplayEntry>> {
+    Ok(read_numbered_log(path)?
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .collect())
+}
+
+/// Reads a log like `read_log` along with the line number of each entry,
+/// starting at 1. Malformed lines of JSON logs are skipped.
+pub fn read_numbered_log(path: &Path) -> Result<Vec<(usize, ReplayEntry)>> {
+    let file = File::open(path)?;
+    let (reader, name): (Box<dyn Read>, &Path) = match path.extension() {
+        Some(ext) if ext == "gz" => (
+            Box::new(GzDecoder::new(file)),
+            path.file_stem().unwrap().as_ref(),
+        ),
+        _ => (Box::new(file), path),
+    };
+    let json = match name.extension().and_then(|ext| ext.to_str()) {
+        Some("log") | None => false,
+        Some("jsonl") => true,
+        Some(ext) => bail!("Can't replay .{ext} logs"),
+    };
+    let mut entries = vec![];
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if json {
+            match parse_json_line(&line) {
+                Ok(entry) => entries.push((index + 1, entry)),
+                Err(err) => debug!("Skipping line {} of {:?}: {err}", index + 1, path),
+            }
+        } else {
+            entries.push((index + 1, parse_text_line(&line)));
+        }
+    }
+    Ok(entries)
+}
+
+/// Finds a log by its path in the log directory, eg. `arctic/20240301.log`,
+/// or anywhere else by its full path.
+pub fn find_log(name: &str) -> PathBuf {
+    let path = log_dir().join(name);
+    if path.is_file() {
+        path
+    } else {
+        PathBuf::from(expand_tilde(name).as_ref())
+    }
+}
+
+/// The worlds with logs, or the logs of a world newest first as paths in the
+/// log directory.
+pub fn list_logs(world: Option<&str>) -> Vec<String> {
+    let dir = match world {
+        Some(world) => log_dir().join(world),
+        None => log_dir(),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut entries: Vec<(String, bool)> = entries
+        .flatten()
+        .map(|entry| {
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            (entry.file_name().to_string_lossy().to_string(), is_dir)
+        })
+        .collect();
+    match world {
+        Some(world) => {
+            let mut logs: Vec<String> = entries
+                .into_iter()
+                .filter(|(_, is_dir)| !is_dir)
+                .map(|(name, _)| format!("{world}/{name}"))
+                .collect();
+            // Log names start with their date
+            logs.sort_by(|a, b| b.cmp(a));
+            logs
+        }
+        None => {
+            entries.retain(|(_, is_dir)| *is_dir);
+            let mut worlds: Vec<String> = entries.into_iter().map(|(name, _)| name).collect();
+            worlds.sort();
+            worlds
+        }
+    }
+}
+
+/// A log being played back. Lines are sent as `Event::ReplayLine` keeping
+/// the pauses between them, `speed` times faster.
+pub struct Replay {
+    stop: Arc<AtomicBool>,
+    /// Replayed lines are run through the triggers, what they send is
+    /// dropped
+    pub triggers: bool,
+}
+
+impl Replay {
+    pub fn start(
+        entries: Vec<ReplayEntry>,
+        speed: f64,
+        triggers: bool,
+        writer: Sender<Event>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        thread::Builder::new()
+            .name("replay-thread".to_string())
+            .spawn(move || {
+                let mut last: Option<DateTime<Local>> = None;
+                for entry in entries {
+                    let delay = match (last, entry.time) {
+                        (Some(last), Some(time)) => {
+                            (time - last).to_std().unwrap_or_default().min(MAX_GAP)
+                        }
+                        (_, None) => LINE_DELAY,
+                        (None, Some(_)) => Duration::ZERO,
+                    };
+                    thread::sleep(delay.div_f64(speed));
+                    if stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    last = entry.time;
+                    if writer
+                        .send(Event::ReplayLine(entry.direction, entry.line))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                writer.send(Event::ReplayDone).ok();
+            })
+            .ok();
+        Self { stop, triggers }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod replay_test {
+    use super::*;
+    use std::io::Write;
+    use std::sync::mpsc::{channel, Receiver};
+
+    #[test]
+    fn test_parse_text_line() {
+        let entry = parse_text_line("[2024-03-01 14:05:09] > kill goblin");
+        assert_eq!(entry.direction, Direction::In);
+        assert_eq!(entry.line.line(), "> kill goblin");
+        assert_eq!(
+            entry.time,
+            Some(Local.with_ymd_and_hms(2024, 3, 1, 14, 5, 9).unwrap())
+        );
+        let entry = parse_text_line("[**] Connected");
+        assert_eq!(entry.direction, Direction::Info);
+        assert_eq!(entry.time, None);
+        let entry = parse_text_line("[not a time] A goblin");
+        assert_eq!(entry.direction, Direction::Out);
+        assert_eq!(entry.line.line(), "[not a time] A goblin");
+    }
+
+    #[test]
+    fn test_parse_json_line() {
+        let entry = parse_json_line(
+            r#"{"time":"2024-03-01T14:05:09.120+00:00","direction":"in","connection":"arctic","raw":"kill goblin","clean":"kill goblin","matched":false}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.direction, Direction::In);
+        assert_eq!(entry.line.line(), "> kill goblin");
+        assert!(entry.time.is_some());
+        assert!(parse_json_line("not json").is_err());
+    }
+
+    #[test]
+    fn test_read_compressed_log() {
+        let dir = log_dir().join("test_replay");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log.log.gz");
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&path).unwrap(), Default::default());
+        encoder
+            .write_all(b"first\n\x1b[31msecond\x1b[0m\n")
+            .unwrap();
+        encoder.finish().unwrap();
+        let entries = read_log(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].line.line(), "\x1b[31msecond\x1b[0m");
+        assert!(read_log(&dir.join("missing.log")).is_err());
+        File::create(dir.join("log.html")).unwrap();
+        assert!(read_log(&dir.join("log.html")).is_err());
+
+        assert!(list_logs(None).contains(&"test_replay".to_string()));
+        let logs = list_logs(Some("test_replay"));
+        assert!(logs.contains(&"test_replay/log.log.gz".to_string()));
+        assert_eq!(find_log("test_replay/log.log.gz"), path);
+    }
+
+    #[test]
+    fn test_skips_malformed_json_lines() {
+        let dir = log_dir().join("test_replay_json");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log.jsonl");
+        let record = r#"{"time":"2024-03-01T14:05:09.120+00:00","direction":"out","connection":"arctic","raw":"a goblin","clean":"a goblin","matched":false}"#;
+        fs::write(&path, format!("{record}\n{{\"truncated\n{record}\n")).unwrap();
+        let entries = read_numbered_log(&path).unwrap();
+        let numbers: Vec<usize> = entries.iter().map(|(line_no, _)| *line_no).collect();
+        assert_eq!(numbers, vec![1, 3]);
+        assert_eq!(read_log(&path).unwrap().len(), 2);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_replay() {
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+        let entries = vec![
+            parse_text_line("[2024-03-01 14:05:09] one"),
+            parse_text_line("[2024-03-01 14:05:10] two"),
+        ];
+        let replay = Replay::start(entries, 100.0, false, writer);
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::ReplayLine(Direction::Out, Line::from("one")))
+        );
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::ReplayLine(Direction::Out, Line::from("two")))
+        );
+        assert_eq!(reader.recv(), Ok(Event::ReplayDone));
+        replay.stop();
+    }
+}
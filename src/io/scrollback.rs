@@ -0,0 +1,265 @@
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::Write,
+    path::PathBuf,
+};
+
+use crate::io::SaveData;
+use crate::model::{Line, Settings, SAVE_SCROLLBACK};
+
+/// How often, in milliseconds, new scrollback is written to disk.
+const SAVE_INTERVAL: u128 = 60_000;
+
+/// Settings for the scrollback kept across restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrollbackSettings {
+    /// Number of lines kept for each world
+    pub retention: usize,
+    /// Number of lines kept in memory to scroll back through
+    pub capacity: usize,
+}
+
+impl Default for ScrollbackSettings {
+    fn default() -> Self {
+        Self {
+            retention: 2000,
+            capacity: 32 * 1024,
+        }
+    }
+}
+
+impl SaveData for ScrollbackSettings {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("scrollback.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SavedLine {
+    /// Receive time in milliseconds since the epoch
+    time: i64,
+    content: String,
+}
+
+/// The output of the current world, saved to disk on disconnect, on quit and
+/// periodically so it can be restored on the next connection to that world.
+pub struct Scrollback {
+    enabled: bool,
+    world: Option<String>,
+    lines: VecDeque<SavedLine>,
+    retention: usize,
+    dirty: bool,
+    last_save: u128,
+}
+
+impl Default for Scrollback {
+    fn default() -> Self {
+        let enabled = Settings::load().get(SAVE_SCROLLBACK).unwrap_or_default();
+        Self {
+            enabled,
+            world: None,
+            lines: VecDeque::new(),
+            retention: ScrollbackSettings::load().retention,
+            dirty: false,
+            last_save: 0,
+        }
+    }
+}
+
+impl Scrollback {
+    /// Starts keeping scrollback for a world and returns the lines saved for
+    /// it by a previous session.
+    pub fn open(&mut self, world: &str) -> Vec<Line> {
+        self.close();
+        self.world = Some(world.to_string());
+        self.retention = ScrollbackSettings::load().retention;
+        if !self.enabled {
+            return vec![];
+        }
+        match self.read() {
+            Ok(lines) => self.lines = lines,
+            Err(err) => error!("Failed to read scrollback for {world}: {err}"),
+        }
+        self.trim();
+        self.lines
+            .iter()
+            .map(|saved| {
+                let mut line = Line::from(saved.content.as_str());
+                if let Some(time) = Local.timestamp_millis_opt(saved.time).single() {
+                    line.set_time(time);
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Saves and stops keeping scrollback for the current world.
+    pub fn close(&mut self) {
+        self.save();
+        self.world = None;
+        self.lines.clear();
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.lines.clear();
+            self.dirty = false;
+        }
+    }
+
+    /// Keeps a line of output unless it's gagged or kept out of the logs.
+    pub fn record(&mut self, line: &Line) {
+        if !self.enabled || self.world.is_none() || line.flags.skip_log {
+            return;
+        }
+        if let Some(content) = line.print_line() {
+            self.lines.push_back(SavedLine {
+                time: line.time().timestamp_millis(),
+                content: content.to_string(),
+            });
+            self.trim();
+            self.dirty = true;
+        }
+    }
+
+    /// Saves new scrollback once in a while, `millis` is the time elapsed
+    /// since startup.
+    pub fn tick(&mut self, millis: u128) {
+        if millis.saturating_sub(self.last_save) >= SAVE_INTERVAL {
+            self.last_save = millis;
+            self.save();
+        }
+    }
+
+    pub fn save(&mut self) {
+        if self.dirty && self.world.is_some() {
+            if let Err(err) = self.write() {
+                error!("Failed to save scrollback: {err}");
+            }
+            self.dirty = false;
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.lines.len() > self.retention {
+            self.lines.pop_front();
+        }
+    }
+
+    fn path(&self) -> Option<PathBuf> {
+        let world: String = self
+            .world
+            .as_ref()?
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || "-_.".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Some(
+            crate::DATA_DIR
+                .join("scrollback")
+                .join(format!("{world}.ron.gz")),
+        )
+    }
+
+    fn read(&self) -> Result<VecDeque<SavedLine>> {
+        match self.path() {
+            Some(path) if path.exists() => {
+                let decoder = GzDecoder::new(File::open(path)?);
+                Ok(ron::de::from_reader(decoder)?)
+            }
+            _ => Ok(VecDeque::new()),
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(path) = self.path() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut encoder = GzEncoder::new(File::create(path)?, Compression::default());
+            encoder.write_all(ron::ser::to_string(&self.lines)?.as_bytes())?;
+            encoder.finish()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod scrollback_test {
+    use super::Scrollback;
+    use crate::model::Line;
+
+    fn scrollback(world: &str) -> Scrollback {
+        let mut scrollback = Scrollback {
+            enabled: true,
+            ..Scrollback::default()
+        };
+        scrollback.open(world);
+        scrollback.lines.clear();
+        scrollback
+    }
+
+    #[test]
+    fn test_skips_gagged_lines() {
+        let mut scrollback = scrollback("test_skips_gagged_lines");
+        scrollback.record(&Line::from("kept"));
+        let mut gagged = Line::from("gagged");
+        gagged.flags.gag = true;
+        scrollback.record(&gagged);
+        let mut skipped = Line::from("skipped");
+        skipped.flags.skip_log = true;
+        scrollback.record(&skipped);
+        assert_eq!(scrollback.lines.len(), 1);
+        assert_eq!(scrollback.lines[0].content, "kept");
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut scrollback = scrollback("test_retention");
+        scrollback.retention = 3;
+        for i in 0..5 {
+            scrollback.record(&Line::from(format!("line {i}")));
+        }
+        assert_eq!(scrollback.lines.len(), 3);
+        assert_eq!(scrollback.lines[0].content, "line 2");
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut scrollback = scrollback("test/restore");
+        let line = Line::from("\x1b[31mred line\x1b[0m");
+        scrollback.record(&line);
+        scrollback.close();
+        assert!(scrollback.lines.is_empty());
+
+        let restored = scrollback.open("test/restore");
+        assert_eq!(restored, vec![line.clone()]);
+        assert_eq!(
+            restored[0].time().timestamp_millis(),
+            line.time().timestamp_millis()
+        );
+
+        let mut disabled = Scrollback {
+            enabled: false,
+            ..Scrollback::default()
+        };
+        assert!(disabled.open("test/restore").is_empty());
+    }
+}
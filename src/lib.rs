@@ -23,8 +23,11 @@ mod tts;
 mod ui;
 
 use crate::event::{spawn_quit_confirm_timeout_thread, Event, QuitMethod};
-use crate::io::{FSMonitor, SaveData};
-use crate::model::{Servers, ECHO_INPUT, HIDE_TOPBAR, LAST_COMMAND, READER_MODE, SCROLL_SPLIT};
+use crate::io::{export_html, find_log, read_log, spawn_log_search, FSMonitor, SaveData};
+use crate::model::{
+    Servers, ECHO_INPUT, HIDE_TOPBAR, LAST_COMMAND, LOG_TIMESTAMPS, READER_MODE, SAVE_SCROLLBACK,
+    SCROLL_SPLIT, TIMESTAMPS,
+};
 use crate::session::{Session, SessionBuilder};
 use crate::timer::{spawn_timer_thread, TimerEvent};
 use crate::tools::patch::migrate_v2_settings_and_servers;
@@ -333,7 +336,7 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                     session
                         .main_writer
                         .send(Event::UserInputBuffer(
-                            buffer.get_buffer(),
+                            buffer.get_shown_buffer(),
                             buffer.get_pos(),
                         ))
                         .unwrap();
@@ -343,7 +346,7 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                 if let Ok(mut buffer) = session.command_buffer.lock() {
                     buffer.set_pos(pos);
                     if let Ok(mut script) = session.lua_script.lock() {
-                        script.set_prompt_content(buffer.get_buffer(), buffer.get_pos());
+                        script.set_prompt_content(buffer.get_shown_buffer(), buffer.get_pos());
                     }
                     session
                         .main_writer
@@ -373,8 +376,14 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
             | Event::ClearTags
             | Event::UserInputBuffer(_, _)
             | Event::UserInputCursor(_)
+            | Event::ShowCompletions(_)
+            | Event::ReplayLine(_, _)
+            | Event::ReplayDone
+            | Event::LogSearchMatch(_)
+            | Event::Flash(_)
             | Event::SetPromptMask(_)
-            | Event::ClearPromptMask => {
+            | Event::ClearPromptMask
+            | Event::SetSecretInput(_) => {
                 //tts_ctrl.handle_events(event.clone());
                 event_handler.handle_output_events(event, &mut screen)?;
             }
@@ -403,10 +412,21 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                         lua.set_reader_mode(value);
                     }
                     screen = Box::new(UiWrapper::new_from(screen, &session, value)?);
+                    screen.set_vt_pane(*session.vt.lock().unwrap())?;
                 }
-                HIDE_TOPBAR | SCROLL_SPLIT => {
+                HIDE_TOPBAR | SCROLL_SPLIT | TIMESTAMPS => {
                     screen.setup()?;
                 }
+                LOG_TIMESTAMPS => {
+                    if let Ok(mut logger) = session.logger.lock() {
+                        logger.set_timestamps(value);
+                    }
+                }
+                SAVE_SCROLLBACK => {
+                    if let Ok(mut scrollback) = session.scrollback.lock() {
+                        scrollback.set_enabled(value);
+                    }
+                }
                 ECHO_INPUT => session.echo_input.store(value, Ordering::Relaxed),
                 LAST_COMMAND => {
                     if let Ok(mut buffer) = session.command_buffer.lock() {
@@ -415,9 +435,9 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                 }
                 _ => {}
             },
-            Event::StartLogging(world, force) => {
+            Event::StartLogging(world, force, options) => {
                 if Settings::load().get(LOGGING_ENABLED)? || force {
-                    session.start_logging(&world)
+                    session.start_logging(&world, &options)
                 }
             }
             Event::StopLogging => {
@@ -483,12 +503,95 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
             | Event::ScrollTop
             | Event::ScrollBottom
             | Event::FindForward(_)
-            | Event::FindBackward(_) => {
+            | Event::FindBackward(_)
+            | Event::ScrollMode(_)
+            | Event::ScrollCursor(_)
+            | Event::ScrollMark(_)
+            | Event::ScrollYank
+            | Event::SetFilter(_) => {
                 event_handler.handle_scroll_events(event, &mut screen)?;
             }
             Event::StatusAreaHeight(height) => screen.set_status_area_height(height)?,
             Event::ShowTags(show) => screen.set_show_tags(show)?,
             Event::SetTagMask(mask) => screen.set_tag_mask(mask),
+            Event::SetTheme(theme) => {
+                theme.save();
+                if let Ok(mut buffer) = session.command_buffer.lock() {
+                    buffer.set_theme(&theme);
+                }
+                screen.set_theme(theme)?;
+            }
+            Event::SetWrapping(wrapping) => {
+                wrapping.save();
+                screen.set_wrapping(wrapping)?;
+            }
+            Event::SetScrollback(settings) => {
+                settings.save();
+                screen.set_history_capacity(settings.capacity)?;
+            }
+            Event::SetLogSettings(settings) => settings.save(),
+            Event::ExportHtml(path, count) => {
+                let lines = screen.output_lines(count);
+                let file = PathBuf::from(expand_tilde(&path).as_ref());
+                match export_html(&file, &lines) {
+                    Ok(_) => screen.print_info(&format!(
+                        "Exported {} lines to {}",
+                        lines.len(),
+                        file.display()
+                    )),
+                    Err(err) => screen.print_error(&format!("Failed to export to `{path}`: {err}")),
+                }
+            }
+            Event::Replay(name, speed, triggers) => match read_log(&find_log(&name)) {
+                Ok(entries) => {
+                    screen.set_replay(Some(name))?;
+                    session.start_replay(entries, speed, triggers);
+                }
+                Err(err) => screen.print_error(&format!("Failed to replay `{name}`: {err}")),
+            },
+            Event::SetTitle(title) => screen.set_title(&title)?,
+            Event::SetBell(settings) => {
+                settings.save();
+                *session.bell.lock().unwrap() = settings;
+            }
+            Event::SetInputSettings(settings) => {
+                settings.save();
+                if let Ok(mut buffer) = session.command_buffer.lock() {
+                    buffer.set_send_delay(settings.send_delay());
+                }
+            }
+            Event::SetColors(settings) => {
+                settings.save();
+                *session.color_depth.lock().unwrap() = settings.depth();
+                screen.setup()?;
+            }
+            Event::SetPalettes(palettes) => {
+                palettes.save();
+                *session.palette.lock().unwrap() = palettes.active(session.world().as_deref());
+                screen.setup()?;
+            }
+            Event::SetVt(settings) => {
+                settings.save();
+                let vt = settings.pane_for(session.world().as_deref());
+                *session.vt.lock().unwrap() = vt;
+                screen.set_vt_pane(vt)?;
+            }
+            Event::Notify(title, body, method) => screen.notify(&title, &body, method)?,
+            Event::SearchLogs(query, callback) => {
+                spawn_log_search(query, callback, session.main_writer.clone());
+            }
+            Event::LogSearchResults(id, matches) => {
+                if let Ok(script) = session.lua_script.lock() {
+                    script.on_log_search_results(id, &matches);
+                    script.get_output_lines().iter().for_each(|l| {
+                        screen.print_output(l);
+                    });
+                }
+            }
+            Event::StopReplay => {
+                session.stop_replay();
+                screen.set_replay(None)?;
+            }
             Event::StatusLine(index, info) => screen.set_status_line(index, info)?,
             Event::LoadScript(path) => {
                 info!("Loading script: {}", path);
@@ -551,6 +654,9 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                 }
             }
             Event::TimerTick(millis) => {
+                if let Ok(mut scrollback) = session.scrollback.lock() {
+                    scrollback.tick(millis);
+                }
                 if let Ok(mut script) = session.lua_script.lock() {
                     script.tick(millis);
                     script.get_output_lines().iter().for_each(|l| {
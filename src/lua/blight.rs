@@ -1,8 +1,13 @@
 use super::{constants::*, regex::Regex, ui_event::UiEvent};
 use crate::event::{Event, QuitMethod};
 use crate::{
-    model::{Line, TagMask},
+    io::{SaveData, ScrollbackSettings},
+    model::{
+        BellAction, BellSettings, ColorDepth, ColorSettings, InputSettings, Line, Palettes,
+        TagMask, Theme, VtSettings, Wrapping,
+    },
     tools::printable_chars::PrintableCharsIterator,
+    ui::NotifyMethod,
     PROJECT_NAME, VERSION,
 };
 use log::debug;
@@ -240,6 +245,236 @@ impl UserData for Blight {
                 .unwrap();
             Ok(())
         });
+        methods.add_function("theme", |ctx, name: Option<String>| {
+            if let Some(name) = name {
+                let theme = Theme::named(&name)
+                    .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+                let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer.send(Event::SetTheme(theme)).unwrap();
+                Ok(name)
+            } else {
+                Ok(Theme::load().name)
+            }
+        });
+        methods.add_function("themes", |_, ()| Ok(Theme::available()));
+        methods.add_function("scrollback_retention", |ctx, lines: Option<usize>| {
+            let mut settings = ScrollbackSettings::load();
+            if let Some(lines) = lines {
+                settings.retention = lines;
+                let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer
+                    .send(Event::SetScrollback(settings.clone()))
+                    .unwrap();
+            }
+            Ok(settings.retention)
+        });
+        methods.add_function(
+            "export_html",
+            |ctx, (path, lines): (String, Option<usize>)| {
+                let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer
+                    .send(Event::ExportHtml(path, lines))
+                    .unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function(
+            "bell",
+            |ctx, (action, sound): (Option<String>, Option<String>)| {
+                let mut settings = BellSettings::load();
+                if let Some(action) = action {
+                    settings.action = BellAction::named(&action)
+                        .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+                    if sound.is_some() {
+                        settings.sound = sound;
+                    }
+                    let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                    let this = this_aux.borrow::<Blight>()?;
+                    this.main_writer
+                        .send(Event::SetBell(settings.clone()))
+                        .unwrap();
+                }
+                Ok((settings.action.name(), settings.sound))
+            },
+        );
+        methods.add_function("color_depth", |ctx, depth: Option<String>| {
+            let mut settings = ColorSettings::load();
+            if let Some(depth) = depth {
+                settings.depth = match depth.as_str() {
+                    "auto" => None,
+                    depth => Some(
+                        ColorDepth::named(depth)
+                            .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?,
+                    ),
+                };
+                let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer
+                    .send(Event::SetColors(settings.clone()))
+                    .unwrap();
+            }
+            Ok(settings.depth().name())
+        });
+        methods.add_function("palette", |ctx, world: Option<String>| {
+            let mut palettes = Palettes::load();
+            let palette = palettes.get_mut(world.as_deref());
+            let table = ctx.create_table()?;
+            table.set("fg", palette.fg.clone())?;
+            table.set("bg", palette.bg.clone())?;
+            Ok(table)
+        });
+        methods.add_function(
+            "remap_color",
+            |ctx, (color, to, options): (String, Option<String>, Option<Table>)| {
+                let (background, world) = match options {
+                    Some(options) => (
+                        options.get::<Option<bool>>("bg")?.unwrap_or_default(),
+                        options.get::<Option<String>>("world")?,
+                    ),
+                    None => (false, None),
+                };
+                let mut palettes = Palettes::load();
+                palettes
+                    .get_mut(world.as_deref())
+                    .remap(&color, to.as_deref(), background)
+                    .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+                palettes.prune();
+                let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer.send(Event::SetPalettes(palettes)).unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("clear_palette", |ctx, world: Option<String>| {
+            let mut palettes = Palettes::load();
+            *palettes.get_mut(world.as_deref()) = Default::default();
+            palettes.prune();
+            let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+            let this = this_aux.borrow::<Blight>()?;
+            this.main_writer.send(Event::SetPalettes(palettes)).unwrap();
+            Ok(())
+        });
+        methods.add_function(
+            "vt_mode",
+            |ctx, (world, enabled): (String, Option<bool>)| {
+                let mut settings = VtSettings::load();
+                if let Some(enabled) = enabled {
+                    if enabled {
+                        settings.worlds.insert(world.clone());
+                    } else {
+                        settings.worlds.remove(&world);
+                    }
+                    let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                    let this = this_aux.borrow::<Blight>()?;
+                    this.main_writer
+                        .send(Event::SetVt(settings.clone()))
+                        .unwrap();
+                }
+                Ok(settings.worlds.contains(&world))
+            },
+        );
+        methods.add_function(
+            "vt_size",
+            |ctx, (cols, rows): (Option<u16>, Option<u16>)| {
+                let mut settings = VtSettings::load();
+                if cols.is_some() || rows.is_some() {
+                    if cols == Some(0) || rows == Some(0) {
+                        return Err(mlua::Error::RuntimeError(
+                            "The VT pane needs at least one column and row".to_string(),
+                        ));
+                    }
+                    settings.cols = cols.unwrap_or(settings.cols);
+                    settings.rows = rows.unwrap_or(settings.rows);
+                    let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                    let this = this_aux.borrow::<Blight>()?;
+                    this.main_writer
+                        .send(Event::SetVt(settings.clone()))
+                        .unwrap();
+                }
+                Ok((settings.cols, settings.rows))
+            },
+        );
+        methods.add_function("set_title", |ctx, title: String| {
+            let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+            let this = this_aux.borrow::<Blight>()?;
+            this.main_writer.send(Event::SetTitle(title)).unwrap();
+            Ok(())
+        });
+        methods.add_function(
+            "notify",
+            |ctx, (title, body, method): (String, String, Option<String>)| {
+                let method = match method {
+                    Some(method) => NotifyMethod::named(&method)
+                        .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?,
+                    None => NotifyMethod::default(),
+                };
+                let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer
+                    .send(Event::Notify(title, body, method))
+                    .unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("scrollback_capacity", |ctx, lines: Option<usize>| {
+            let mut settings = ScrollbackSettings::load();
+            if let Some(lines) = lines {
+                if lines == 0 {
+                    return Err(mlua::Error::RuntimeError(
+                        "Scrollback capacity must be at least 1 line".to_string(),
+                    ));
+                }
+                settings.capacity = lines;
+                let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer
+                    .send(Event::SetScrollback(settings.clone()))
+                    .unwrap();
+            }
+            Ok(settings.capacity)
+        });
+        methods.add_function("send_delay", |ctx, millis: Option<u64>| {
+            let mut settings = InputSettings::load();
+            if let Some(millis) = millis {
+                settings.send_delay = millis;
+                let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer
+                    .send(Event::SetInputSettings(settings))
+                    .unwrap();
+            }
+            Ok(settings.send_delay)
+        });
+        methods.add_function(
+            "wrap",
+            |ctx, (window, opts): (String, Option<Table>)| -> LuaResult<Table> {
+                let mut wrapping = Wrapping::load();
+                let mut options = wrapping
+                    .get(&window)
+                    .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+                if let Some(opts) = opts {
+                    if let Some(words) = opts.get::<Option<bool>>("words")? {
+                        options.words = words;
+                    }
+                    if let Some(indent) = opts.get::<Option<usize>>("indent")? {
+                        options.indent = indent;
+                    }
+                    wrapping
+                        .set(&window, options)
+                        .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+                    let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+                    let this = this_aux.borrow::<Blight>()?;
+                    this.main_writer.send(Event::SetWrapping(wrapping)).unwrap();
+                }
+                let table = ctx.create_table()?;
+                table.set("words", options.words)?;
+                table.set("indent", options.indent)?;
+                Ok(table)
+            },
+        );
         methods.add_function("find_backward", |ctx, re: Regex| {
             let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
             let this = this_aux.borrow::<Blight>()?;
@@ -254,6 +489,14 @@ impl UserData for Blight {
             this.main_writer.send(Event::FindForward(re.regex)).unwrap();
             Ok(())
         });
+        methods.add_function("grep", |ctx, re: Option<Regex>| {
+            let this_aux = ctx.globals().get::<AnyUserData>("blight")?;
+            let this = this_aux.borrow::<Blight>()?;
+            this.main_writer
+                .send(Event::SetFilter(re.map(|re| re.regex)))
+                .unwrap();
+            Ok(())
+        });
     }
 }
 
@@ -264,7 +507,12 @@ mod test_blight {
     use mlua::{AnyUserData, Lua};
 
     use crate::event::{Event, QuitMethod};
+    use crate::io::SaveData;
     use crate::lua::UiEvent;
+    use crate::model::{
+        BellAction, BellSettings, ColorDepth, ColorSettings, InputSettings, Theme, Wrapping,
+    };
+    use crate::ui::NotifyMethod;
 
     use super::Blight;
     use crate::lua::constants::{
@@ -395,6 +643,18 @@ mod test_blight {
         assert_eq!(reader.recv(), Ok(Event::FindBackward(re)));
     }
 
+    #[test]
+    fn grep() {
+        let (lua, reader) = get_lua_state();
+        let re = crate::model::Regex::new("test", None).unwrap();
+        lua.load(r#"blight.grep(regex.new("test"))"#)
+            .exec()
+            .unwrap();
+        assert_eq!(reader.recv(), Ok(Event::SetFilter(Some(re))));
+        lua.load("blight.grep()").exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::SetFilter(None)));
+    }
+
     #[test]
     fn show_help() {
         let (lua, reader) = get_lua_state();
@@ -564,4 +824,226 @@ mod test_blight {
             .unwrap();
         assert_eq!(height, 1);
     }
+
+    #[test]
+    fn test_theme() {
+        let (lua, reader) = get_lua_state();
+        let name = lua
+            .load("return blight.theme(\"monochrome\")")
+            .call::<String>(())
+            .unwrap();
+        assert_eq!(name, "monochrome");
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::SetTheme(Theme::named("monochrome").unwrap()))
+        );
+        assert!(lua.load("blight.theme(\"no-such-theme\")").exec().is_err());
+
+        let themes = lua
+            .load("return blight.themes()")
+            .call::<Vec<String>>(())
+            .unwrap();
+        assert!(themes.contains(&"high-contrast".to_string()));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let (lua, reader) = get_lua_state();
+        let mut wrapping = Wrapping::load();
+        let indent = lua
+            .load("return blight.wrap(\"reader\", { indent = 4 }).indent")
+            .call::<usize>(())
+            .unwrap();
+        assert_eq!(indent, 4);
+        wrapping.reader.indent = 4;
+        assert_eq!(reader.recv(), Ok(Event::SetWrapping(wrapping)));
+        assert!(lua.load("blight.wrap(\"nowhere\")").exec().is_err());
+    }
+
+    #[test]
+    fn test_export_html() {
+        let (lua, reader) = get_lua_state();
+        lua.load("blight.export_html(\"fight.html\", 100)")
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::ExportHtml("fight.html".to_string(), Some(100)))
+        );
+        lua.load("blight.export_html(\"all.html\")").exec().unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::ExportHtml("all.html".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn test_bell() {
+        let (lua, reader) = get_lua_state();
+        lua.load("blight.bell(\"sound\", \"~/sounds/ding.wav\")")
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::SetBell(BellSettings {
+                action: BellAction::Sound,
+                sound: Some("~/sounds/ding.wav".to_string()),
+            }))
+        );
+        assert!(lua.load("blight.bell(\"siren\")").exec().is_err());
+    }
+
+    #[test]
+    fn test_color_depth() {
+        let (lua, reader) = get_lua_state();
+        assert_eq!(
+            lua.load("return blight.color_depth(\"256\")")
+                .call::<String>(())
+                .unwrap(),
+            "256"
+        );
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::SetColors(ColorSettings {
+                depth: Some(ColorDepth::Indexed),
+            }))
+        );
+        lua.load("blight.color_depth(\"auto\")").exec().unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::SetColors(ColorSettings { depth: None }))
+        );
+        assert!(lua.load("blight.color_depth(\"8\")").exec().is_err());
+    }
+
+    #[test]
+    fn test_remap_color() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r##"blight.remap_color("blue", "#6060ff")"##)
+            .exec()
+            .unwrap();
+        let Ok(Event::SetPalettes(palettes)) = reader.recv() else {
+            panic!("Expected a SetPalettes event");
+        };
+        assert_eq!(
+            palettes.palette.fg.get("blue"),
+            Some(&"#6060ff".to_string())
+        );
+
+        lua.load(r#"blight.remap_color("default", "black", { bg = true, world = "arctic" })"#)
+            .exec()
+            .unwrap();
+        let Ok(Event::SetPalettes(palettes)) = reader.recv() else {
+            panic!("Expected a SetPalettes event");
+        };
+        assert_eq!(
+            palettes.worlds["arctic"].bg.get("default"),
+            Some(&"black".to_string())
+        );
+        assert!(lua
+            .load(r#"blight.remap_color("blue", "pink")"#)
+            .exec()
+            .is_err());
+
+        lua.load(r#"blight.clear_palette("arctic")"#)
+            .exec()
+            .unwrap();
+        let Ok(Event::SetPalettes(palettes)) = reader.recv() else {
+            panic!("Expected a SetPalettes event");
+        };
+        assert!(!palettes.worlds.contains_key("arctic"));
+    }
+
+    #[test]
+    fn test_vt_mode() {
+        let (lua, reader) = get_lua_state();
+        assert!(lua
+            .load(r#"return blight.vt_mode("arctic", true)"#)
+            .call::<bool>(())
+            .unwrap());
+        let Ok(Event::SetVt(settings)) = reader.recv() else {
+            panic!("Expected a SetVt event");
+        };
+        assert!(settings.worlds.contains("arctic"));
+        assert_eq!(settings.pane_for(Some("arctic")), Some((80, 24)));
+
+        assert_eq!(
+            lua.load("return blight.vt_size(100, 30)")
+                .call::<(u16, u16)>(())
+                .unwrap(),
+            (100, 30)
+        );
+        let Ok(Event::SetVt(settings)) = reader.recv() else {
+            panic!("Expected a SetVt event");
+        };
+        assert_eq!((settings.cols, settings.rows), (100, 30));
+        assert!(lua.load("blight.vt_size(0)").exec().is_err());
+    }
+
+    #[test]
+    fn test_set_title() {
+        let (lua, reader) = get_lua_state();
+        lua.load("blight.set_title(\"Arctic\")").exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::SetTitle("Arctic".to_string())));
+    }
+
+    #[test]
+    fn test_notify() {
+        let (lua, reader) = get_lua_state();
+        lua.load("blight.notify(\"Tell\", \"Bob: hi\")")
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Notify(
+                "Tell".to_string(),
+                "Bob: hi".to_string(),
+                NotifyMethod::Osc777
+            ))
+        );
+        lua.load("blight.notify(\"Tell\", \"Bob: hi\", \"bell\")")
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Notify(
+                "Tell".to_string(),
+                "Bob: hi".to_string(),
+                NotifyMethod::Bell
+            ))
+        );
+        assert!(lua
+            .load("blight.notify(\"Tell\", \"hi\", \"popup\")")
+            .exec()
+            .is_err());
+    }
+
+    #[test]
+    fn test_scrollback_capacity() {
+        let (lua, reader) = get_lua_state();
+        let capacity = lua
+            .load("return blight.scrollback_capacity(5000)")
+            .call::<usize>(())
+            .unwrap();
+        assert_eq!(capacity, 5000);
+        assert!(matches!(
+            reader.recv(),
+            Ok(Event::SetScrollback(settings)) if settings.capacity == 5000
+        ));
+        assert!(lua.load("blight.scrollback_capacity(0)").exec().is_err());
+    }
+
+    #[test]
+    fn test_send_delay() {
+        let (lua, reader) = get_lua_state();
+        let delay = lua
+            .load("return blight.send_delay(250)")
+            .call::<u64>(())
+            .unwrap();
+        assert_eq!(delay, 250);
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::SetInputSettings(InputSettings { send_delay: 250 }))
+        );
+    }
 }
@@ -1,5 +1,6 @@
 pub const ON_CONNECTION_CALLBACK_TABLE: &str = "__connection_callback_table";
 pub const ON_DISCONNECT_CALLBACK_TABLE: &str = "__disconnect_callback_table";
+pub const ON_BELL_CALLBACK_TABLE: &str = "__bell_callback_table";
 pub const IS_CONNECTED: &str = "__is_connected_bool";
 pub const TIMED_CALLBACK_TABLE: &str = "__timed_callback_table";
 pub const TIMED_CALLBACK_TABLE_CORE: &str = "__timed_callback_table_core";
@@ -19,9 +20,12 @@ pub const PROMPT_CURSOR_INDEX: &str = "__prompt_cursor_index";
 pub const PROMPT_MASK_CONTENT: &str = "__prompt_mask_content";
 pub const PROMPT_INPUT_LISTENER_TABLE: &str = "__prompt_listeners";
 pub const FS_LISTENERS: &str = "__fs_listeners";
+pub const LOG_SEARCH_CALLBACK_TABLE: &str = "__log_search_callback_table";
+pub const LOG_SEARCH_NEXT_ID: &str = "__log_search_next_id";
 pub const SCRIPT_RESET_LISTENERS: &str = "__script_reset_listeners";
 pub const STATUS_AREA_HEIGHT: &str = "__status_area_height";
 pub const SHOW_TAGS: &str = "__show_tags";
+pub const REPLAYING: &str = "__replaying";
 
 // Core tables
 pub const PROTO_ENABLED_LISTENERS_TABLE: &str = "__protocol_enabled_listeners";
@@ -1,7 +1,10 @@
 use log::warn;
-use mlua::{FromLua, UserData, UserDataMethods};
+use mlua::{FromLua, Lua, Table, UserData, UserDataMethods, Value};
 
-use crate::model::Line as mLine;
+use super::regex::Regex;
+use crate::model::{
+    self, highlight, parse_spans, render_spans, substitute, Color, Line as mLine, Span, Style,
+};
 use crate::tools::printable_chars::PrintableCharsIterator;
 
 #[derive(Clone, FromLua)]
@@ -19,6 +22,94 @@ impl From<mLine> for Line {
     }
 }
 
+impl Line {
+    /// The raw content, including any replacement made by earlier scripts.
+    fn content(&self) -> &str {
+        self.replacement
+            .as_deref()
+            .unwrap_or_else(|| self.inner.line())
+    }
+}
+
+/// Reads a color given as a name, palette index or `#rrggbb` hex color.
+fn lua_color(value: Value) -> mlua::Result<Option<Color>> {
+    let name = match value {
+        Value::Nil => return Ok(None),
+        Value::Integer(index) => index.to_string(),
+        Value::String(name) => name.to_str()?.to_string(),
+        _ => {
+            return Err(mlua::Error::RuntimeError(
+                "Expected a color name, palette index or #rrggbb".to_string(),
+            ))
+        }
+    };
+    Color::named(&name)
+        .map(Some)
+        .map_err(|err| mlua::Error::RuntimeError(err.to_string()))
+}
+
+/// Reads the colors for `line:highlight`, a color or `fg:bg` where either
+/// may be left out.
+fn lua_colors(value: Value) -> mlua::Result<(Option<Color>, Option<Color>)> {
+    let Value::String(colors) = &value else {
+        return Ok((lua_color(value)?, None));
+    };
+    let colors = colors.to_str()?.to_string();
+    let (fg, bg) = colors.split_once(':').unwrap_or((&colors, ""));
+    let color = |name: &str| {
+        (!name.is_empty())
+            .then(|| Color::named(name))
+            .transpose()
+            .map_err(|err| mlua::Error::RuntimeError(err.to_string()))
+    };
+    Ok((color(fg)?, color(bg)?))
+}
+
+/// Reads a regex given as a pattern or a regex object.
+pub(super) fn lua_regex(value: Value) -> mlua::Result<model::Regex> {
+    match value {
+        Value::String(pattern) => model::Regex::new(&pattern.to_str()?, None)
+            .map_err(|err| mlua::Error::RuntimeError(err.to_string())),
+        Value::UserData(ud) => Ok(ud.borrow::<Regex>()?.regex.clone()),
+        _ => Err(mlua::Error::RuntimeError(
+            "Expected a string or a regex to match".to_string(),
+        )),
+    }
+}
+
+fn span_table(lua: &Lua, span: &Span) -> mlua::Result<Table> {
+    let table = lua.create_table()?;
+    table.set("text", span.text.as_str())?;
+    table.set("fg", span.style.fg.map(|color| color.name()))?;
+    table.set("bg", span.style.bg.map(|color| color.name()))?;
+    table.set("bold", span.style.bold)?;
+    table.set("dim", span.style.dim)?;
+    table.set("italic", span.style.italic)?;
+    table.set("underline", span.style.underline)?;
+    table.set("reverse", span.style.reverse)?;
+    table.set("strike", span.style.strike)?;
+    Ok(table)
+}
+
+fn table_span(table: Table) -> mlua::Result<Span> {
+    let flag = |key: &str| -> mlua::Result<bool> {
+        Ok(table.get::<Option<bool>>(key)?.unwrap_or_default())
+    };
+    Ok(Span {
+        text: table.get("text")?,
+        style: Style {
+            fg: lua_color(table.get("fg")?)?,
+            bg: lua_color(table.get("bg")?)?,
+            bold: flag("bold")?,
+            dim: flag("dim")?,
+            italic: flag("italic")?,
+            underline: flag("underline")?,
+            reverse: flag("reverse")?,
+            strike: flag("strike")?,
+        },
+    })
+}
+
 impl UserData for Line {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("line", |_, this, _: ()| -> mlua::Result<String> {
@@ -94,6 +185,9 @@ impl UserData for Line {
         methods.add_method("prompt", |_, this, _: ()| -> mlua::Result<bool> {
             Ok(this.inner.flags.prompt)
         });
+        methods.add_method("secret", |_, this, _: ()| -> mlua::Result<bool> {
+            Ok(this.inner.flags.secret)
+        });
         methods.add_method_mut(
             "matched",
             |_, this, val: Option<bool>| -> mlua::Result<bool> {
@@ -107,7 +201,43 @@ impl UserData for Line {
             this.replacement = Some(line);
             Ok(())
         });
+        methods.add_method("spans", |ctx, this, ()| {
+            let spans = ctx.create_table()?;
+            for span in parse_spans(this.content()) {
+                spans.push(span_table(ctx, &span)?)?;
+            }
+            Ok(spans)
+        });
+        methods.add_method_mut("set_spans", |_, this, spans: Vec<Table>| {
+            let spans = spans
+                .into_iter()
+                .map(table_span)
+                .collect::<mlua::Result<Vec<Span>>>()?;
+            this.replacement = Some(render_spans(&spans));
+            Ok(())
+        });
+        methods.add_method_mut("highlight", |_, this, (regex, colors): (Value, Value)| {
+            let regex = lua_regex(regex)?;
+            let (fg, bg) = lua_colors(colors)?;
+            let overlay = Style {
+                fg,
+                bg,
+                ..Style::default()
+            };
+            let (line, count) = highlight(this.content(), &regex, &overlay);
+            this.replacement = Some(line);
+            Ok(count)
+        });
+        methods.add_method_mut("sub", |_, this, (regex, repl): (Value, String)| {
+            let regex = lua_regex(regex)?;
+            let (line, count) = substitute(this.content(), &regex, &repl);
+            this.replacement = Some(line);
+            Ok(count)
+        });
         methods.add_method("source", |_, this, ()| Ok(this.inner.flags.source.clone()));
+        methods.add_method("time", |_, this, ()| {
+            Ok(this.inner.time().timestamp_millis())
+        });
         methods.add_method(
             "replacement",
             |_, this, _: ()| -> mlua::Result<Option<String>> { Ok(this.replacement.clone()) },
@@ -118,6 +248,7 @@ impl UserData for Line {
 #[cfg(test)]
 mod test_lua_line {
     use super::Line;
+    use crate::lua::regex::RegexLib;
     use crate::model::Line as mLine;
 
     fn test_line() -> Line {
@@ -186,6 +317,16 @@ mod test_lua_line {
         assert!(line.inner.flags.skip_log);
     }
 
+    #[test]
+    fn test_secret() {
+        let mut secret_line = test_line();
+        secret_line.inner.flags.secret = true;
+        test_lua!("test_line" => test_line(), "secret_line" => secret_line);
+
+        assert_lua_bool!("test_line:secret()", false);
+        assert_lua_bool!("secret_line:secret()", true);
+    }
+
     #[test]
     fn test_matched() {
         test_lua!("test_line" => test_line());
@@ -221,6 +362,88 @@ mod test_lua_line {
         assert_eq!(line.replacement, Some("test test".to_string()));
     }
 
+    #[test]
+    fn test_spans() {
+        test_lua!("test_line" => Line::from(mLine::from("\x1b[1;31mred\x1b[0m and \x1b[38;5;208morange")));
+        assert_lua!(usize, "#test_line:spans()", 3);
+        assert_lua_string!("test_line:spans()[1].text", "red");
+        assert_lua_string!("test_line:spans()[1].fg", "red");
+        assert_lua_bool!("test_line:spans()[1].bold", true);
+        assert_lua_bool!("test_line:spans()[2].fg == nil", true);
+        assert_lua_string!("test_line:spans()[3].fg", "208");
+
+        run_lua!(
+            r##"
+            local spans = test_line:spans()
+            spans[1].fg = "bgreen"
+            spans[1].bold = false
+            spans[2].text = " or "
+            spans[3].fg = 214
+            spans[3].bg = "#000080"
+            test_line:set_spans(spans)
+            "##
+        );
+        assert_lua!(
+            Option<String>,
+            "test_line:replacement()",
+            Some("\x1b[0;92mred\x1b[0m or \x1b[0;38;5;214;48;2;0;0;128morange\x1b[0m".to_string())
+        );
+        assert_lua_string!("test_line:spans()[2].text", " or ");
+    }
+
+    #[test]
+    fn test_set_spans_invalid_color() {
+        let lua = mlua::Lua::new();
+        lua.globals().set("test_line", test_line()).unwrap();
+        assert!(lua
+            .load(r#"test_line:set_spans({ { text = "x", fg = "pink" } })"#)
+            .exec()
+            .is_err());
+    }
+
+    #[test]
+    fn test_sub() {
+        test_lua!(
+            "test_line" => Line::from(mLine::from("\x1b[32mBob\x1b[0m says: \x1b[33mhello\x1b[0m")),
+            "regex" => RegexLib,
+        );
+        assert_lua!(usize, r#"test_line:sub("(\\w+) says", "$1 yells")"#, 1);
+        assert_lua!(usize, r#"test_line:sub(regex.new("hel+o"), "bye")"#, 1);
+        assert_lua!(usize, r#"test_line:sub("nothing", "bye")"#, 0);
+        assert_lua!(
+            Option<String>,
+            "test_line:replacement()",
+            Some("\x1b[32mBob yells\x1b[0m: \x1b[33mbye\x1b[0m".to_string())
+        );
+        assert_lua_string!("test_line:line()", "Bob says: hello");
+    }
+
+    #[test]
+    fn test_highlight() {
+        test_lua!("test_line" => Line::from(mLine::from("You hit the \x1b[1morc\x1b[0m")));
+        assert_lua!(usize, r#"test_line:highlight("orc", "red")"#, 1);
+        assert_lua!(
+            Option<String>,
+            "test_line:replacement()",
+            Some("You hit the \x1b[1m\x1b[0;1;31morc\x1b[0;1m\x1b[0m".to_string())
+        );
+        assert_lua!(usize, r#"test_line:highlight("hit", ":#000080")"#, 1);
+        assert_lua_bool!("test_line:spans()[2].fg == nil", true);
+        assert_lua_string!("test_line:spans()[2].bg", "#000080");
+        assert_lua!(usize, r#"test_line:highlight("You", 46)"#, 1);
+        assert_lua_string!("test_line:spans()[1].fg", "46");
+    }
+
+    #[test]
+    fn test_highlight_invalid_color() {
+        let lua = mlua::Lua::new();
+        lua.globals().set("test_line", test_line()).unwrap();
+        assert!(lua
+            .load(r#"test_line:highlight("test", "pink:blue")"#)
+            .exec()
+            .is_err());
+    }
+
     #[test]
     fn test_source() {
         test_lua!("test_line" => test_line());
@@ -345,4 +568,14 @@ mod test_lua_line {
         let line: Line = global!("test_line");
         assert_eq!(line.replacement, Some("new content".to_string()));
     }
+
+    #[test]
+    fn test_time() {
+        let mut inner = mLine::from("A timed line");
+        let time = chrono::Local::now() - chrono::Duration::seconds(30);
+        inner.set_time(time);
+        test_lua!("test_line" => Line::from(inner));
+
+        assert_lua!(i64, "test_line:time()", time.timestamp_millis());
+    }
 }
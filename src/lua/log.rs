@@ -1,7 +1,76 @@
-use mlua::{UserData, UserDataMethods};
+use mlua::{Lua, Result as LuaResult, Table, UserData, UserDataMethods, Value, Variadic};
 
-use super::{backend::Backend, constants::BACKEND};
-use crate::event::Event;
+use super::{
+    backend::Backend,
+    constants::{BACKEND, LOG_SEARCH_CALLBACK_TABLE, LOG_SEARCH_NEXT_ID},
+    line::lua_regex,
+};
+use crate::{
+    event::Event,
+    io::{
+        list_logs, parse_since, LogFormat, LogMatch, LogOptions, LogQuery, LogSettings, SaveData,
+    },
+};
+
+/// Applies the rotation and naming options in `opts` to `settings`.
+fn apply_options(settings: &mut LogSettings, opts: &Table) -> LuaResult<()> {
+    if let Some(template) = opts.get::<Option<String>>("template")? {
+        settings.template = template;
+    }
+    if let Some(max_size) = opts.get::<Option<u64>>("max_size")? {
+        settings.max_size = Some(max_size).filter(|size| *size > 0);
+    }
+    if let Some(daily) = opts.get::<Option<bool>>("daily")? {
+        settings.daily = daily;
+    }
+    if let Some(compress) = opts.get::<Option<bool>>("compress")? {
+        settings.compress = compress;
+    }
+    if let Some(retention) = opts.get::<Option<u64>>("retention")? {
+        settings.retention = Some(retention).filter(|days| *days > 0);
+    }
+    Ok(())
+}
+
+/// Builds a search from a pattern, a string or a `regex.new()`, and the
+/// optional world and since arguments.
+fn log_query(pattern: Value, world: Option<String>, since: Option<String>) -> LuaResult<LogQuery> {
+    let pattern = lua_regex(pattern)?;
+    let since = since
+        .map(|since| parse_since(&since))
+        .transpose()
+        .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+    Ok(LogQuery {
+        pattern,
+        world: world.filter(|world| !world.is_empty()),
+        since,
+    })
+}
+
+/// The matches of a `log.search()` as passed to its callback.
+pub fn log_matches_table(lua: &Lua, matches: &[LogMatch]) -> LuaResult<Table> {
+    let results = lua.create_table()?;
+    for result in matches {
+        let entry = lua.create_table()?;
+        entry.set("file", result.file.as_str())?;
+        entry.set("line", result.line_no)?;
+        entry.set("time", result.time.timestamp())?;
+        entry.set("text", result.line.clean_line())?;
+        entry.set("raw", result.line.line())?;
+        results.push(entry)?;
+    }
+    Ok(results)
+}
+
+fn options_table(lua: &Lua, settings: &LogSettings) -> LuaResult<Table> {
+    let table = lua.create_table()?;
+    table.set("template", settings.template.as_str())?;
+    table.set("max_size", settings.max_size.unwrap_or_default())?;
+    table.set("daily", settings.daily)?;
+    table.set("compress", settings.compress)?;
+    table.set("retention", settings.retention.unwrap_or_default())?;
+    Ok(table)
+}
 
 pub struct Log {}
 
@@ -13,11 +82,18 @@ impl Log {
 
 impl UserData for Log {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_function("start", |ctx, name: String| {
+        methods.add_function("start", |ctx, (name, opts): (String, Option<Table>)| {
+            let mut options = LogOptions::default();
+            if let Some(opts) = opts {
+                options.character = opts.get("character")?;
+                let mut settings = LogSettings::load();
+                apply_options(&mut settings, &opts)?;
+                options.settings = Some(settings);
+            }
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             backend
                 .writer
-                .send(Event::StartLogging(name, true))
+                .send(Event::StartLogging(name, true, options))
                 .unwrap();
             Ok(())
         });
@@ -26,6 +102,107 @@ impl UserData for Log {
             backend.writer.send(Event::StopLogging).unwrap();
             Ok(())
         });
+        methods.add_function(
+            "replay",
+            |ctx, (name, speed, triggers): (String, Option<f64>, Option<bool>)| {
+                let speed = speed.unwrap_or(1.0);
+                if !speed.is_finite() || speed <= 0.0 {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "Invalid replay speed: {speed}"
+                    )));
+                }
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend
+                    .writer
+                    .send(Event::Replay(name, speed, triggers.unwrap_or_default()))
+                    .unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("stop_replay", |ctx, _: ()| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::StopReplay).unwrap();
+            Ok(())
+        });
+        methods.add_function("list", |_, world: Option<String>| {
+            Ok(list_logs(world.as_deref()))
+        });
+        methods.add_function(
+            "search",
+            |ctx, (pattern, mut args): (Value, Variadic<Value>)| {
+                let Some(Value::Function(callback)) = args.pop() else {
+                    return Err(mlua::Error::RuntimeError(
+                        "Expected a function to call with the matches".to_string(),
+                    ));
+                };
+                let mut args = args.into_iter();
+                let world: Option<String> = ctx.unpack(args.next().unwrap_or(Value::Nil))?;
+                let since: Option<String> = ctx.unpack(args.next().unwrap_or(Value::Nil))?;
+                let query = log_query(pattern, world, since)?;
+                let callbacks: Table = ctx.named_registry_value(LOG_SEARCH_CALLBACK_TABLE)?;
+                let id: u32 = ctx.named_registry_value(LOG_SEARCH_NEXT_ID)?;
+                callbacks.raw_set(id, callback)?;
+                ctx.set_named_registry_value(LOG_SEARCH_NEXT_ID, id + 1)?;
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend
+                    .writer
+                    .send(Event::SearchLogs(query, Some(id)))
+                    .unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function(
+            "show_search",
+            |ctx, (pattern, world, since): (Value, Option<String>, Option<String>)| {
+                let query = log_query(pattern, world, since)?;
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend.writer.send(Event::SearchLogs(query, None)).unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("settings", |ctx, opts: Option<Table>| {
+            let mut settings = LogSettings::load();
+            if let Some(opts) = opts {
+                apply_options(&mut settings, &opts)?;
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend
+                    .writer
+                    .send(Event::SetLogSettings(settings.clone()))
+                    .unwrap();
+            }
+            options_table(ctx, &settings)
+        });
+        methods.add_function(
+            "format",
+            |ctx, (format, world): (Option<String>, Option<String>)| {
+                let mut settings = LogSettings::load();
+                if let Some(format) = format {
+                    match &world {
+                        Some(world) if format == "default" => {
+                            settings.worlds.remove(world);
+                        }
+                        _ => {
+                            let format = LogFormat::named(&format)
+                                .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+                            if let Some(world) = &world {
+                                settings.worlds.insert(world.clone(), format);
+                            } else {
+                                settings.format = format;
+                            }
+                        }
+                    }
+                    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                    backend
+                        .writer
+                        .send(Event::SetLogSettings(settings.clone()))
+                        .unwrap();
+                }
+                Ok(match &world {
+                    Some(world) => settings.format_for(world).name(),
+                    None => settings.format.name(),
+                })
+            },
+        );
     }
 }
 
@@ -37,18 +214,32 @@ mod test_log {
 
     use crate::{
         event::Event,
-        lua::{backend::Backend, constants::BACKEND},
+        io::{LogFormat, LogOptions},
+        lua::{
+            backend::Backend,
+            constants::{BACKEND, LOG_SEARCH_CALLBACK_TABLE, LOG_SEARCH_NEXT_ID},
+            regex::RegexLib,
+        },
+        model::Regex,
     };
 
     use super::Log;
 
-    fn assert_event(lua_code: &str, event: Event) {
+    fn get_lua_state() -> (Lua, Receiver<Event>) {
         let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
         let backend = Backend::new(writer);
         let log = Log::new();
         let lua = Lua::new();
         lua.set_named_registry_value(BACKEND, backend).unwrap();
+        lua.set_named_registry_value(LOG_SEARCH_CALLBACK_TABLE, lua.create_table().unwrap())
+            .unwrap();
+        lua.set_named_registry_value(LOG_SEARCH_NEXT_ID, 1).unwrap();
         lua.globals().set("log", log).unwrap();
+        (lua, reader)
+    }
+
+    fn assert_event(lua_code: &str, event: Event) {
+        let (lua, reader) = get_lua_state();
         lua.load(lua_code).exec().unwrap();
 
         assert_eq!(reader.recv(), Ok(event));
@@ -58,12 +249,152 @@ mod test_log {
     fn test_start() {
         assert_event(
             "log.start(\"some_name\")",
-            Event::StartLogging("some_name".to_string(), true),
+            Event::StartLogging("some_name".to_string(), true, LogOptions::default()),
         );
     }
 
+    #[test]
+    fn test_start_with_options() {
+        let (lua, reader) = get_lua_state();
+        lua.load(
+            r#"log.start("some_name", {
+                character = "Bob",
+                template = "{world}/{character}",
+                max_size = 1024,
+                daily = true,
+                retention = 0,
+            })"#,
+        )
+        .exec()
+        .unwrap();
+        let Ok(Event::StartLogging(name, true, options)) = reader.recv() else {
+            panic!("Expected a StartLogging event");
+        };
+        assert_eq!(name, "some_name");
+        assert_eq!(options.character, Some("Bob".to_string()));
+        let settings = options.settings.unwrap();
+        assert_eq!(settings.template, "{world}/{character}");
+        assert_eq!(settings.max_size, Some(1024));
+        assert!(settings.daily);
+        assert_eq!(settings.retention, None);
+    }
+
     #[test]
     fn test_stop() {
         assert_event("log.stop()", Event::StopLogging);
     }
+
+    #[test]
+    fn test_replay() {
+        assert_event(
+            "log.replay(\"arctic/20240301.log\")",
+            Event::Replay("arctic/20240301.log".to_string(), 1.0, false),
+        );
+        assert_event(
+            "log.replay(\"raid.jsonl\", 4, true)",
+            Event::Replay("raid.jsonl".to_string(), 4.0, true),
+        );
+        assert_event("log.stop_replay()", Event::StopReplay);
+        let (lua, _reader) = get_lua_state();
+        assert!(lua.load("log.replay(\"raid.log\", 0)").exec().is_err());
+    }
+
+    #[test]
+    fn test_show_search() {
+        let (lua, reader) = get_lua_state();
+        lua.globals().set("regex", RegexLib).unwrap();
+        lua.load(r#"log.show_search(regex.new("sword"), "arctic", "2024-03-01")"#)
+            .exec()
+            .unwrap();
+        let Ok(Event::SearchLogs(query, None)) = reader.recv() else {
+            panic!("Expected a SearchLogs event");
+        };
+        assert_eq!(query.pattern, Regex::new("sword", None).unwrap());
+        assert_eq!(query.world, Some("arctic".to_string()));
+        assert!(query.since.is_some());
+        lua.load(r#"log.show_search("sword")"#).exec().unwrap();
+        assert!(
+            matches!(reader.recv(), Ok(Event::SearchLogs(query, None)) if query.world.is_none())
+        );
+        assert!(lua.load(r#"log.show_search("(")"#).exec().is_err());
+        assert!(lua
+            .load(r#"log.show_search("a", nil, "soon")"#)
+            .exec()
+            .is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"log.search("sword", "arctic", "3d", function (matches) end)"#)
+            .exec()
+            .unwrap();
+        let Ok(Event::SearchLogs(query, Some(1))) = reader.recv() else {
+            panic!("Expected a SearchLogs event with a callback");
+        };
+        assert_eq!(query.world, Some("arctic".to_string()));
+        assert!(query.since.is_some());
+        lua.load(r#"log.search("sword", function (matches) end)"#)
+            .exec()
+            .unwrap();
+        assert!(matches!(
+            reader.recv(),
+            Ok(Event::SearchLogs(query, Some(2))) if query.world.is_none() && query.since.is_none()
+        ));
+        let callbacks: mlua::Table = lua.named_registry_value(LOG_SEARCH_CALLBACK_TABLE).unwrap();
+        assert_eq!(callbacks.len().unwrap(), 2);
+        assert!(lua.load(r#"log.search("sword")"#).exec().is_err());
+        assert!(lua.load("log.search(5, function () end)").exec().is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let (lua, reader) = get_lua_state();
+        let format: String = lua.load("return log.format()").call(()).unwrap();
+        assert!(["text", "html", "json"].contains(&format.as_str()));
+        let format: String = lua.load("return log.format(\"html\")").call(()).unwrap();
+        assert_eq!(format, "html");
+        assert!(matches!(
+            reader.recv(),
+            Ok(Event::SetLogSettings(settings)) if settings.format == LogFormat::Html
+        ));
+        assert!(lua.load("log.format(\"pdf\")").exec().is_err());
+    }
+
+    #[test]
+    fn test_settings() {
+        let (lua, reader) = get_lua_state();
+        let daily: bool = lua
+            .load("return log.settings({ daily = true, max_size = 0 }).daily")
+            .call(())
+            .unwrap();
+        assert!(daily);
+        assert!(matches!(
+            reader.recv(),
+            Ok(Event::SetLogSettings(settings)) if settings.daily && settings.max_size.is_none()
+        ));
+    }
+
+    #[test]
+    fn test_world_format() {
+        let (lua, reader) = get_lua_state();
+        let format: String = lua
+            .load("return log.format(\"json\", \"test_world\")")
+            .call(())
+            .unwrap();
+        assert_eq!(format, "json");
+        let Ok(Event::SetLogSettings(settings)) = reader.recv() else {
+            panic!("Expected a SetLogSettings event");
+        };
+        assert_eq!(settings.worlds.get("test_world"), Some(&LogFormat::Json));
+        let format: String = lua
+            .load("return log.format(\"default\", \"test_world\")")
+            .call(())
+            .unwrap();
+        let Ok(Event::SetLogSettings(settings)) = reader.recv() else {
+            panic!("Expected a SetLogSettings event");
+        };
+        assert!(!settings.worlds.contains_key("test_world"));
+        assert_eq!(format, settings.format.name());
+    }
 }
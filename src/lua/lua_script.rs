@@ -5,7 +5,13 @@ use super::{
 };
 use super::{constants::*, core::Core, ui_event::UiEvent};
 use super::{
-    log::Log, mud::Mud, regex::RegexLib, settings::Settings, store::Store, timer::Timer, util::*,
+    log::{log_matches_table, Log},
+    mud::Mud,
+    regex::RegexLib,
+    settings::Settings,
+    store::Store,
+    timer::Timer,
+    util::*,
 };
 use crate::lua::fs::Fs;
 use crate::lua::prompt::Prompt;
@@ -14,10 +20,10 @@ use crate::lua::prompt_mask::PromptMask;
 use crate::lua::spellcheck::{self, Spellchecker};
 use crate::model::Completions;
 use crate::tools::util::expand_tilde;
-use crate::{event::Event, lua::servers::Servers, model, model::Line};
+use crate::{event::Event, io::LogMatch, lua::servers::Servers, model, model::Line};
 use anyhow::Result;
 use log::{debug, info};
-use mlua::{AnyUserData, FromLua, Lua, Result as LuaResult, UserData, UserDataMethods, Value};
+use mlua::{AnyUserData, Lua, Result as LuaResult, UserData, UserDataMethods, Value};
 use std::io::prelude::*;
 use std::path::Path;
 use std::{fs::File, sync::mpsc::Sender};
@@ -170,13 +176,17 @@ fn create_default_lua_state(builder: LuaScriptBuilder, store: Option<Store>) ->
         state.set_named_registry_value(PROTO_SUBNEG_LISTENERS_TABLE, state.create_table()?)?;
         state.set_named_registry_value(ON_CONNECTION_CALLBACK_TABLE, state.create_table()?)?;
         state.set_named_registry_value(ON_DISCONNECT_CALLBACK_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(ON_BELL_CALLBACK_TABLE, state.create_table()?)?;
         state.set_named_registry_value(COMPLETION_CALLBACK_TABLE, state.create_table()?)?;
         state.set_named_registry_value(FS_LISTENERS, state.create_table()?)?;
+        state.set_named_registry_value(LOG_SEARCH_CALLBACK_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(LOG_SEARCH_NEXT_ID, 1)?;
         state.set_named_registry_value(SCRIPT_RESET_LISTENERS, state.create_table()?)?;
         state.set_named_registry_value(PROMPT_CONTENT, String::new())?;
         state.set_named_registry_value(PROMPT_CURSOR_INDEX, 0)?;
         state.set_named_registry_value(PROMPT_INPUT_LISTENER_TABLE, state.create_table()?)?;
         state.set_named_registry_value(STATUS_AREA_HEIGHT, 1)?;
+        state.set_named_registry_value(REPLAYING, false)?;
 
         globals.set("blight", blight)?;
         globals.set("core", Core::new(writer.clone()))?;
@@ -243,6 +253,28 @@ fn create_default_lua_state(builder: LuaScriptBuilder, store: Option<Store>) ->
     state
 }
 
+/// Reads a completion returned from a lua completion callback. Completions are
+/// either plain strings or `{word, description}` pairs.
+fn parse_completion(entry: Value) -> Option<(String, Option<String>)> {
+    match entry {
+        Value::String(word) => Some((word.to_str().ok()?.to_string(), None)),
+        Value::Table(pair) => {
+            let word = pair
+                .get::<Option<String>>(1)
+                .ok()
+                .flatten()
+                .or_else(|| pair.get::<Option<String>>("word").ok().flatten())?;
+            let description = pair
+                .get::<Option<String>>(2)
+                .ok()
+                .flatten()
+                .or_else(|| pair.get::<Option<String>>("description").ok().flatten());
+            Some((word, description))
+        }
+        _ => None,
+    }
+}
+
 impl LuaScript {
     pub fn on_reset(&mut self) {
         self.exec_lua(&mut || -> LuaResult<()> {
@@ -331,6 +363,14 @@ impl LuaScript {
         });
     }
 
+    /// Runs the triggers on a line of a replayed log. Input they send is
+    /// marked as `replay` so it never reaches the server.
+    pub fn on_replay_output(&self, line: &mut Line) {
+        self.state.set_named_registry_value(REPLAYING, true).ok();
+        self.on_mud_output(line);
+        self.state.set_named_registry_value(REPLAYING, false).ok();
+    }
+
     pub fn on_mud_output(&self, line: &mut Line) {
         if !line.flags.bypass_script {
             let mut lline = LuaLine::from(line.clone());
@@ -403,6 +443,17 @@ impl LuaScript {
         });
     }
 
+    pub fn on_log_search_results(&self, id: u32, matches: &[LogMatch]) {
+        self.exec_lua(&mut || -> LuaResult<()> {
+            let table: mlua::Table = self.state.named_registry_value(LOG_SEARCH_CALLBACK_TABLE)?;
+            if let mlua::Value::Function(func) = table.get(id)? {
+                table.set(id, mlua::Nil)?;
+                func.call::<()>(log_matches_table(&self.state, matches)?)?;
+            }
+            Ok(())
+        });
+    }
+
     pub fn tick(&mut self, millis: u128) {
         self.exec_lua(&mut || -> LuaResult<()> {
             let core_tick_table: mlua::Table = self
@@ -488,6 +539,17 @@ impl LuaScript {
         });
     }
 
+    pub fn on_bell(&self, line: &Line) {
+        self.exec_lua(&mut || -> LuaResult<()> {
+            let table: mlua::Table = self.state.named_registry_value(ON_BELL_CALLBACK_TABLE)?;
+            for pair in table.pairs::<mlua::Value, mlua::Function>() {
+                let (_, cb) = pair?;
+                cb.call::<()>(line.clean_line())?;
+            }
+            Ok(())
+        });
+    }
+
     pub fn set_dimensions(&mut self, dim: (u16, u16)) {
         self.exec_lua(&mut || -> LuaResult<()> {
             let blight_aud: AnyUserData = self.state.globals().get("blight")?;
@@ -578,11 +640,13 @@ impl LuaScript {
                 if !result.is_empty() {
                     let mut it = result.into_iter();
                     if let Some(Value::Table(table)) = it.next() {
-                        if let Ok(mut comps) =
-                            Vec::<String>::from_lua(Value::Table(table), &self.state)
-                        {
-                            comps.sort();
-                            completions.add_all(&mut comps);
+                        let mut comps = table
+                            .sequence_values::<Value>()
+                            .filter_map(|entry| parse_completion(entry.ok()?))
+                            .collect::<Vec<(String, Option<String>)>>();
+                        comps.sort();
+                        for (word, description) in comps {
+                            completions.add_described(word, description);
                         }
                     }
                     if let Some(Value::Boolean(lock)) = it.next() {
@@ -633,9 +697,10 @@ mod lua_script_tests {
     use super::LuaScriptBuilder;
     use super::CONNECTION_ID;
     use crate::event::QuitMethod;
+    use crate::io::{LogMatch, LogOptions};
     use crate::lua::constants::TIMED_CALLBACK_TABLE;
     use crate::model::Completions;
-    use crate::model::{Connection, PromptMask, Regex};
+    use crate::model::{ColorDepth, ColorSettings, Connection, PromptMask, Regex};
     use crate::{event::Event, lua::regex::Regex as LReg, model::Line, PROJECT_NAME, VERSION};
     use libmudtelnet::bytes::Bytes;
     use mlua::Table;
@@ -709,6 +774,28 @@ mod lua_script_tests {
         assert!(!test_prompt_trigger("test test", &lua));
     }
 
+    #[test]
+    fn test_replay_trigger_sends() {
+        let (lua, reader) = get_lua();
+        lua.state
+            .load(r#"trigger.add("^goblin$", {}, function () mud.send("kill goblin") end)"#)
+            .exec()
+            .unwrap();
+
+        lua.on_replay_output(&mut Line::from("goblin"));
+        let Ok(Event::ServerInput(line)) = reader.recv() else {
+            panic!("Expected a ServerInput event");
+        };
+        assert_eq!(line.line(), "kill goblin");
+        assert!(line.flags.replay);
+
+        lua.on_mud_output(&mut Line::from("goblin"));
+        let Ok(Event::ServerInput(line)) = reader.recv() else {
+            panic!("Expected a ServerInput event");
+        };
+        assert!(!line.flags.replay);
+    }
+
     #[test]
     fn test_lua_trigger_id_increment() {
         let lua = get_lua().0;
@@ -1165,6 +1252,23 @@ mod lua_script_tests {
         assert_eq!(lua.get_output_lines(), [Line::from("ctrl-up")]);
     }
 
+    #[test]
+    fn test_on_bell() {
+        let lua_code = r#"
+        mud.on_bell(function (line)
+            blight.output("bell: " .. line)
+        end)
+        "#;
+
+        let (lua, _reader) = get_lua();
+        lua.state.load(lua_code).exec().unwrap();
+        lua.on_bell(&Line::from("\x1b[31mBob tells you: hi\x07\x1b[0m"));
+        assert_eq!(
+            lua.get_output_lines(),
+            [Line::from("bell: Bob tells you: hi")]
+        );
+    }
+
     #[test]
     fn test_on_connect_test() {
         let lua_code = r#"
@@ -1407,12 +1511,115 @@ mod lua_script_tests {
         lua.on_mud_input(&mut Line::from("/start_log test"));
         assert_eq!(
             reader.recv().unwrap(),
-            Event::StartLogging("test".to_string(), true)
+            Event::StartLogging("test".to_string(), true, LogOptions::default())
         );
         lua.on_mud_input(&mut Line::from("/stop_log"));
         assert_eq!(reader.recv().unwrap(), Event::StopLogging);
     }
 
+    #[test]
+    fn confirm_replay_macros() {
+        let (lua, reader) = get_lua();
+        lua.on_mud_input(&mut Line::from("/replay arctic/raid.log"));
+        assert_eq!(
+            reader.recv().unwrap(),
+            Event::Replay("arctic/raid.log".to_string(), 1.0, false)
+        );
+        lua.on_mud_input(&mut Line::from("/replay raid.jsonl 2.5 triggers"));
+        assert_eq!(
+            reader.recv().unwrap(),
+            Event::Replay("raid.jsonl".to_string(), 2.5, true)
+        );
+        lua.on_mud_input(&mut Line::from("/replay stop"));
+        assert_eq!(reader.recv().unwrap(), Event::StopReplay);
+    }
+
+    #[test]
+    fn confirm_logsearch_macro() {
+        let (lua, reader) = get_lua();
+        lua.on_mud_input(&mut Line::from("/logsearch sword arctic"));
+        let Ok(Event::SearchLogs(query, None)) = reader.recv() else {
+            panic!("Expected a SearchLogs event");
+        };
+        assert_eq!(query.pattern.as_str(), "sword");
+        assert_eq!(query.world, Some("arctic".to_string()));
+        assert_eq!(query.since, None);
+        lua.on_mud_input(&mut Line::from("/logsearch sword 3d"));
+        let Ok(Event::SearchLogs(query, None)) = reader.recv() else {
+            panic!("Expected a SearchLogs event");
+        };
+        assert_eq!(query.world, None);
+        assert!(query.since.is_some());
+    }
+
+    #[test]
+    fn test_log_search_results() {
+        let (lua, reader) = get_lua();
+        lua.state
+            .load(r#"log.search("sword", function (matches) found = matches[1].text end)"#)
+            .exec()
+            .unwrap();
+        let Ok(Event::SearchLogs(_, Some(id))) = reader.recv() else {
+            panic!("Expected a SearchLogs event with a callback");
+        };
+        let result = LogMatch {
+            file: "arctic/20240301.log".to_string(),
+            line_no: 3,
+            time: chrono::Local::now(),
+            line: Line::from("\x1b[31mBob gives you a sword.\x1b[0m"),
+        };
+        lua.on_log_search_results(id, &[result]);
+        let found: String = lua.state.globals().get("found").unwrap();
+        assert_eq!(found, "Bob gives you a sword.");
+        let callbacks: Table = lua
+            .state
+            .named_registry_value(crate::lua::constants::LOG_SEARCH_CALLBACK_TABLE)
+            .unwrap();
+        assert_eq!(callbacks.len().unwrap(), 0);
+    }
+
+    #[test]
+    fn confirm_colordepth_macro() {
+        let (lua, reader) = get_lua();
+        lua.on_mud_input(&mut Line::from("/colordepth 16"));
+        assert_eq!(
+            reader.recv().unwrap(),
+            Event::SetColors(ColorSettings {
+                depth: Some(ColorDepth::Basic)
+            })
+        );
+    }
+
+    #[test]
+    fn confirm_palette_macro() {
+        let (lua, reader) = get_lua();
+        lua.on_mud_input(&mut Line::from("/palette blue bblue bg arctic"));
+        let Ok(Event::SetPalettes(palettes)) = reader.recv() else {
+            panic!("Expected a SetPalettes event");
+        };
+        assert_eq!(
+            palettes.worlds["arctic"].bg.get("blue"),
+            Some(&"bblue".to_string())
+        );
+        lua.on_mud_input(&mut Line::from("/palette clear"));
+        assert!(matches!(reader.recv(), Ok(Event::SetPalettes(_))));
+    }
+
+    #[test]
+    fn confirm_vt_macro() {
+        let (lua, reader) = get_lua();
+        lua.on_mud_input(&mut Line::from("/vt arctic on"));
+        let Ok(Event::SetVt(settings)) = reader.recv() else {
+            panic!("Expected a SetVt event");
+        };
+        assert!(settings.worlds.contains("arctic"));
+        lua.on_mud_input(&mut Line::from("/vt size 60 20"));
+        let Ok(Event::SetVt(settings)) = reader.recv() else {
+            panic!("Expected a SetVt event");
+        };
+        assert_eq!((settings.cols, settings.rows), (60, 20));
+    }
+
     #[test]
     fn confirm_load_macro() {
         let (lua, reader) = get_lua();
@@ -1606,6 +1813,40 @@ mod lua_script_tests {
         assert_eq!(lua.tab_complete(&"rob".to_string()), Completions::default());
     }
 
+    #[test]
+    fn confirm_completion_descriptions() {
+        let (mut lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+                blight.on_complete(function (input)
+                    return {
+                        {"kill", "Attack something"},
+                        "kick",
+                        { word = "kiss", description = "Show affection" },
+                    }
+                end)
+                "#,
+            )
+            .exec()
+            .unwrap();
+
+        let completions = lua.tab_complete("k");
+        assert_eq!(
+            completions.iter().collect::<Vec<&String>>(),
+            vec!["kick", "kill", "kiss"]
+        );
+        assert_eq!(completions.description("kick"), None);
+        assert_eq!(
+            completions.description("kill"),
+            Some(&"Attack something".to_string())
+        );
+        assert_eq!(
+            completions.description("kiss"),
+            Some(&"Show affection".to_string())
+        );
+    }
+
     #[test]
     fn confirm_completion_lock() {
         let (mut lua, _reader) = get_lua();
@@ -10,7 +10,8 @@ use super::{
     backend::Backend,
     constants::{
         BACKEND, IS_CONNECTED, MUD_INPUT_LISTENER_TABLE, MUD_OUTPUT_LISTENER_TABLE,
-        ON_CONNECTION_CALLBACK_TABLE, ON_DISCONNECT_CALLBACK_TABLE,
+        ON_BELL_CALLBACK_TABLE, ON_CONNECTION_CALLBACK_TABLE, ON_DISCONNECT_CALLBACK_TABLE,
+        REPLAYING,
     },
 };
 
@@ -82,6 +83,7 @@ impl UserData for Mud {
                 let mut line = Line::from(msg);
                 line.flags.bypass_script = true;
                 line.flags.source = Some("script".to_string());
+                line.flags.replay = ctx.named_registry_value(REPLAYING)?;
 
                 if let Some(table) = options {
                     line.flags.gag = table.get("gag")?;
@@ -94,6 +96,9 @@ impl UserData for Mud {
             },
         );
         methods.add_function("send_bytes", |ctx, bytes: Vec<u8>| {
+            if ctx.named_registry_value(REPLAYING)? {
+                return Ok(());
+            }
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             backend
                 .writer
@@ -104,6 +109,7 @@ impl UserData for Mud {
         methods.add_function("input", |ctx, line: String| {
             let mut line = Line::from(line);
             line.flags.source = Some("script".to_string());
+            line.flags.replay = ctx.named_registry_value(REPLAYING)?;
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             backend.writer.send(Event::ServerInput(line)).unwrap();
             Ok(())
@@ -118,6 +124,11 @@ impl UserData for Mud {
             table.set(table.raw_len() + 1, callback)?;
             Ok(())
         });
+        methods.add_function("on_bell", |ctx, callback: mlua::Function| {
+            let table: mlua::Table = ctx.named_registry_value(ON_BELL_CALLBACK_TABLE)?;
+            table.raw_set(table.raw_len() + 1, callback)?;
+            Ok(())
+        });
         methods.add_function("is_connected", |ctx, ()| {
             let value: bool = ctx.named_registry_value(IS_CONNECTED)?;
             Ok(value)
@@ -16,6 +16,22 @@ pub enum UiEvent {
     ScrollDown,
     ScrollTop,
     ScrollBottom,
+    ScrollMode,
+    ScrollModeExit,
+    ScrollLineUp,
+    ScrollLineDown,
+    ScrollHalfPageUp,
+    ScrollHalfPageDown,
+    ScrollYank,
+    ScrollSetMark,
+    ScrollJumpMark,
+    HistorySearch,
+    Undo,
+    Redo,
+    Yank,
+    YankPop,
+    Newline,
+    Multiline,
     Complete,
     Unknown(String),
 }
@@ -39,6 +55,22 @@ impl From<&str> for UiEvent {
             "scroll_down" => UiEvent::ScrollDown,
             "scroll_top" => UiEvent::ScrollTop,
             "scroll_bottom" => UiEvent::ScrollBottom,
+            "scroll_mode" => UiEvent::ScrollMode,
+            "scroll_mode_exit" => UiEvent::ScrollModeExit,
+            "scroll_line_up" => UiEvent::ScrollLineUp,
+            "scroll_line_down" => UiEvent::ScrollLineDown,
+            "scroll_half_page_up" => UiEvent::ScrollHalfPageUp,
+            "scroll_half_page_down" => UiEvent::ScrollHalfPageDown,
+            "scroll_yank" => UiEvent::ScrollYank,
+            "scroll_set_mark" => UiEvent::ScrollSetMark,
+            "scroll_jump_mark" => UiEvent::ScrollJumpMark,
+            "history_search" => UiEvent::HistorySearch,
+            "undo" => UiEvent::Undo,
+            "redo" => UiEvent::Redo,
+            "yank" => UiEvent::Yank,
+            "yank_pop" => UiEvent::YankPop,
+            "newline" => UiEvent::Newline,
+            "multiline" => UiEvent::Multiline,
             "complete" => UiEvent::Complete,
             _ => UiEvent::Unknown(s.to_string()),
         }
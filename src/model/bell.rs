@@ -0,0 +1,82 @@
+use crate::io::SaveData;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// What to do when the mud sends a BEL character.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BellAction {
+    /// Ring the terminal bell
+    #[default]
+    Bell,
+    /// Flash the top bar, or the screen in reader mode
+    Flash,
+    /// Play the configured sound
+    Sound,
+    /// Announce it with text to speech
+    Speak,
+    /// Do nothing
+    Off,
+}
+
+impl BellAction {
+    pub fn named(name: &str) -> Result<Self> {
+        match name {
+            "bell" => Ok(Self::Bell),
+            "flash" => Ok(Self::Flash),
+            "sound" => Ok(Self::Sound),
+            "speak" => Ok(Self::Speak),
+            "off" => Ok(Self::Off),
+            _ => bail!("Unknown bell action: {name}, use bell, flash, sound, speak or off"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bell => "bell",
+            Self::Flash => "flash",
+            Self::Sound => "sound",
+            Self::Speak => "speak",
+            Self::Off => "off",
+        }
+    }
+}
+
+/// How BEL characters from the mud are handled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BellSettings {
+    pub action: BellAction,
+    /// The sound file played by `BellAction::Sound`
+    pub sound: Option<String>,
+}
+
+impl SaveData for BellSettings {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("bell.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod bell_test {
+    use super::BellAction;
+
+    #[test]
+    fn test_action_names() {
+        for action in [
+            BellAction::Bell,
+            BellAction::Flash,
+            BellAction::Sound,
+            BellAction::Speak,
+            BellAction::Off,
+        ] {
+            assert_eq!(BellAction::named(action.name()).unwrap(), action);
+        }
+        assert!(BellAction::named("siren").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::io::SaveData;
+use anyhow::{bail, Result};
+use lazy_static::lazy_static;
+use regex::bytes::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, env, path::PathBuf};
+
+use super::{Color, Palette};
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ColorDepth {
+    /// The 16 basic colors, eg. the Linux console
+    #[serde(rename = "16")]
+    Basic,
+    /// The 256 color palette
+    #[serde(rename = "256")]
+    Indexed,
+    /// 24-bit colors
+    #[serde(rename = "truecolor")]
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub fn named(name: &str) -> Result<Self> {
+        match name {
+            "16" => Ok(Self::Basic),
+            "256" => Ok(Self::Indexed),
+            "truecolor" | "24bit" => Ok(Self::TrueColor),
+            _ => bail!("Unknown color depth: {name}, use 16, 256 or truecolor"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Basic => "16",
+            Self::Indexed => "256",
+            Self::TrueColor => "truecolor",
+        }
+    }
+
+    /// Guesses the color depth of the terminal from `COLORTERM` and `TERM`.
+    /// Most terminals show 256 colors, so that's assumed unless they say
+    /// otherwise.
+    pub fn detect() -> Self {
+        Self::from_env(
+            env::var("COLORTERM").ok().as_deref(),
+            env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        let colorterm = colorterm.unwrap_or_default().to_lowercase();
+        let term = term.unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" || term.contains("direct") {
+            Self::TrueColor
+        } else if term == "linux"
+            || term == "dumb"
+            || term == "ansi"
+            || term.starts_with("vt")
+            || term.ends_with("-16color")
+            || term.ends_with("-8color")
+        {
+            Self::Basic
+        } else {
+            Self::Indexed
+        }
+    }
+}
+
+/// The color depth chosen by the user, detected from the terminal when unset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorSettings {
+    pub depth: Option<ColorDepth>,
+}
+
+impl ColorSettings {
+    /// The color depth output is rendered in.
+    pub fn depth(&self) -> ColorDepth {
+        self.depth.unwrap_or_else(ColorDepth::detect)
+    }
+}
+
+impl SaveData for ColorSettings {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("colors.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+lazy_static! {
+    static ref SGR: Regex = Regex::new(r"\x1b\[([0-9;:]*)m").unwrap();
+}
+
+/// Reads the color following a 38 or 48 SGR parameter given with `;`.
+fn extended_color<'a>(params: &mut impl Iterator<Item = &'a [u8]>) -> Option<Color> {
+    let mut number = || params.next().map(|param| param_value(param).min(255) as u8);
+    match number()? {
+        5 => Some(Color::Indexed(number()?)),
+        2 => Some(Color::Rgb(number()?, number()?, number()?)),
+        _ => None,
+    }
+}
+
+/// Reads a 38 or 48 SGR parameter given with `:`, eg. `38:2::255:0:0`.
+fn colon_color(param: &[u8]) -> Option<(bool, Color)> {
+    let parts: Vec<u16> = param.split(|b| *b == b':').map(param_value).collect();
+    let background = match parts.first()? {
+        38 => false,
+        48 => true,
+        _ => return None,
+    };
+    let value = |index: usize| parts.get(index).map(|value| (*value).min(255) as u8);
+    let color = match parts.get(1)? {
+        5 => Color::Indexed(value(2)?),
+        // The color space id before the channels is optional
+        2 => {
+            let rgb = parts.len().checked_sub(3).filter(|start| *start >= 2)?;
+            Color::Rgb(value(rgb)?, value(rgb + 1)?, value(rgb + 2)?)
+        }
+        _ => return None,
+    };
+    Some((background, color))
+}
+
+fn param_value(param: &[u8]) -> u16 {
+    std::str::from_utf8(param)
+        .ok()
+        .and_then(|param| param.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Rewrites the parameters of an SGR sequence, drawing its colors as
+/// `palette` says and converting them to ones that fit `depth`.
+fn recolor_params(params: &[u8], palette: &Palette, depth: ColorDepth) -> Vec<u8> {
+    let mut output: Vec<String> = vec![];
+    let push_color = |output: &mut Vec<String>, color: Option<Color>, background: bool| {
+        output.push(match palette.color(color, background) {
+            Some(color) => color.downsample(depth).sgr(background),
+            None if background => "49".to_string(),
+            None => "39".to_string(),
+        });
+    };
+    let mut params = params.split(|b| *b == b';');
+    while let Some(param) = params.next() {
+        if param.contains(&b':') {
+            match colon_color(param) {
+                Some((background, color)) => push_color(&mut output, Some(color), background),
+                None => output.push(String::from_utf8_lossy(param).to_string()),
+            }
+            continue;
+        }
+        let value = param_value(param);
+        match value {
+            0 => {
+                output.push(String::from_utf8_lossy(param).to_string());
+                // The palette may replace the default colors a reset goes back to
+                for background in [false, true] {
+                    if palette.color(None, background).is_some() {
+                        push_color(&mut output, None, background);
+                    }
+                }
+            }
+            30..=37 => push_color(&mut output, Some(Color::Indexed(value as u8 - 30)), false),
+            90..=97 => push_color(&mut output, Some(Color::Indexed(value as u8 - 82)), false),
+            40..=47 => push_color(&mut output, Some(Color::Indexed(value as u8 - 40)), true),
+            100..=107 => push_color(&mut output, Some(Color::Indexed(value as u8 - 92)), true),
+            39 => push_color(&mut output, None, false),
+            49 => push_color(&mut output, None, true),
+            38 | 48 => {
+                if let Some(color) = extended_color(&mut params) {
+                    push_color(&mut output, Some(color), value == 48);
+                }
+            }
+            _ => output.push(String::from_utf8_lossy(param).to_string()),
+        }
+    }
+    output.join(";").into_bytes()
+}
+
+/// Rewrites the colors of the SGR sequences in `text`, drawing them as
+/// `palette` says in colors the terminal can show. Other escape sequences are
+/// left alone.
+pub fn recolor<'a>(text: &'a [u8], palette: &Palette, depth: ColorDepth) -> Cow<'a, [u8]> {
+    if depth == ColorDepth::TrueColor && palette.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    SGR.replace_all(text, |captures: &Captures| {
+        let mut sgr = b"\x1b[".to_vec();
+        sgr.extend(recolor_params(&captures[1], palette, depth));
+        sgr.push(b'm');
+        sgr
+    })
+}
+
+#[cfg(test)]
+mod color_depth_test {
+    use super::{recolor, ColorDepth};
+    use crate::model::Palette;
+
+    fn downsampled(text: &str, depth: ColorDepth) -> String {
+        String::from_utf8(recolor(text.as_bytes(), &Palette::default(), depth).to_vec()).unwrap()
+    }
+
+    fn remapped(text: &str, palette: &Palette) -> String {
+        String::from_utf8(recolor(text.as_bytes(), palette, ColorDepth::TrueColor).to_vec())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        let detect = ColorDepth::from_env;
+        assert_eq!(
+            detect(Some("truecolor"), Some("xterm-256color")),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(detect(Some("24bit"), None), ColorDepth::TrueColor);
+        assert_eq!(detect(None, Some("xterm-direct")), ColorDepth::TrueColor);
+        assert_eq!(detect(None, Some("screen-256color")), ColorDepth::Indexed);
+        assert_eq!(detect(None, Some("linux")), ColorDepth::Basic);
+        assert_eq!(detect(None, Some("vt100")), ColorDepth::Basic);
+        assert_eq!(detect(None, Some("xterm-16color")), ColorDepth::Basic);
+        assert_eq!(detect(None, Some("xterm")), ColorDepth::Indexed);
+        assert_eq!(detect(None, Some("screen")), ColorDepth::Indexed);
+        assert_eq!(detect(None, None), ColorDepth::Indexed);
+    }
+
+    #[test]
+    fn test_names() {
+        for depth in [
+            ColorDepth::Basic,
+            ColorDepth::Indexed,
+            ColorDepth::TrueColor,
+        ] {
+            assert_eq!(ColorDepth::named(depth.name()).unwrap(), depth);
+        }
+        assert!(ColorDepth::named("8").is_err());
+    }
+
+    #[test]
+    fn test_downsample_truecolor() {
+        let text = "\x1b[38;2;255;0;0mred\x1b[0m";
+        assert_eq!(downsampled(text, ColorDepth::TrueColor), text);
+        assert_eq!(
+            downsampled(text, ColorDepth::Indexed),
+            "\x1b[38;5;196mred\x1b[0m"
+        );
+        assert_eq!(downsampled(text, ColorDepth::Basic), "\x1b[91mred\x1b[0m");
+        assert_eq!(
+            downsampled("\x1b[1;48;2;0;0;120;4mx", ColorDepth::Basic),
+            "\x1b[1;44;4mx"
+        );
+        assert_eq!(
+            downsampled("\x1b[38;2;128;128;128mgray", ColorDepth::Indexed),
+            "\x1b[38;5;244mgray"
+        );
+    }
+
+    #[test]
+    fn test_downsample_256() {
+        let text = "\x1b[38;5;208morange \x1b[48;5;4mblue";
+        assert_eq!(
+            downsampled(text, ColorDepth::Indexed),
+            "\x1b[38;5;208morange \x1b[44mblue"
+        );
+        assert_eq!(
+            downsampled(text, ColorDepth::Basic),
+            "\x1b[33morange \x1b[44mblue"
+        );
+        assert_eq!(
+            downsampled("\x1b[38;5;12mx", ColorDepth::Basic),
+            "\x1b[94mx"
+        );
+    }
+
+    #[test]
+    fn test_downsample_colon_params() {
+        assert_eq!(
+            downsampled("\x1b[38:2::0:255:0mx", ColorDepth::Indexed),
+            "\x1b[38;5;46mx"
+        );
+        assert_eq!(
+            downsampled("\x1b[38:2:0:255:0mx", ColorDepth::Basic),
+            "\x1b[92mx"
+        );
+        assert_eq!(
+            downsampled("\x1b[4:3;38:5:196mx", ColorDepth::Basic),
+            "\x1b[4:3;91mx"
+        );
+    }
+
+    #[test]
+    fn test_downsample_keeps_other_sequences() {
+        let text = "\x1b[2J\x1b[1;31mhi\x1b[10;5H\x1b]0;title\x07";
+        assert_eq!(downsampled(text, ColorDepth::Basic), text);
+    }
+
+    #[test]
+    fn test_palette() {
+        let mut palette = Palette::default();
+        palette.remap("blue", Some("#6060ff"), false).unwrap();
+        palette.remap("bblack", Some("white"), false).unwrap();
+        palette.remap("red", Some("default"), true).unwrap();
+        assert_eq!(
+            remapped("\x1b[1;34mblue\x1b[90mgray\x1b[41mred", &palette),
+            "\x1b[1;38;2;96;96;255mblue\x1b[37mgray\x1b[49mred"
+        );
+        assert_eq!(
+            remapped("\x1b[38;5;4mblue\x1b[38:5:4mblue", &palette),
+            "\x1b[38;2;96;96;255mblue\x1b[38;2;96;96;255mblue"
+        );
+        assert_eq!(remapped("\x1b[31mred", &palette), "\x1b[31mred");
+        assert_eq!(
+            recolor(b"\x1b[34mblue", &palette, ColorDepth::Basic).as_ref(),
+            b"\x1b[94mblue"
+        );
+    }
+
+    #[test]
+    fn test_palette_default_colors() {
+        let mut palette = Palette::default();
+        palette.remap("default", Some("#101010"), true).unwrap();
+        assert_eq!(
+            remapped("\x1b[44mblue\x1b[0mplain\x1b[mplain\x1b[49mplain", &palette),
+            "\x1b[44mblue\x1b[0;48;2;16;16;16mplain\x1b[;48;2;16;16;16mplain\x1b[48;2;16;16;16mplain"
+        );
+    }
+}
@@ -1,9 +1,20 @@
+use std::collections::HashMap;
+
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Completions {
     inner: Vec<String>,
+    descriptions: HashMap<String, String>,
     lock: bool,
 }
 
+/// The candidates shown in the completion menu and the one currently
+/// inserted in the prompt, if any.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionMenu {
+    pub options: Vec<(String, Option<String>)>,
+    pub selected: Option<usize>,
+}
+
 impl Completions {
     pub fn lock(&mut self, lock: bool) {
         self.lock = lock;
@@ -16,11 +27,27 @@ impl Completions {
 
     pub fn merge(&mut self, mut other: Self) {
         if !self.lock {
-            self.inner.append(&mut other.inner)
+            self.inner.append(&mut other.inner);
+            for (word, description) in other.descriptions {
+                self.descriptions.entry(word).or_insert(description);
+            }
         }
         self.lock = other.lock;
     }
 
+    pub fn add_described(&mut self, word: String, description: Option<String>) {
+        if !self.lock {
+            if let Some(description) = description {
+                self.descriptions.entry(word.clone()).or_insert(description);
+            }
+            self.inner.push(word);
+        }
+    }
+
+    pub fn description(&self, word: &str) -> Option<&String> {
+        self.descriptions.get(word)
+    }
+
     pub fn add_all(&mut self, other: &mut Vec<String>) {
         if !self.lock {
             self.inner.append(other)
@@ -43,6 +70,7 @@ impl From<Vec<String>> for Completions {
     fn from(v: Vec<String>) -> Self {
         Self {
             inner: v,
+            descriptions: HashMap::new(),
             lock: false,
         }
     }
@@ -77,6 +105,23 @@ mod tests {
         assert_eq!(it.next(), None);
     }
 
+    #[test]
+    fn confirm_descriptions() {
+        let mut completions = Completions::default();
+        completions.add_described("kill".to_string(), Some("Attack".to_string()));
+        completions.add_described("kick".to_string(), None);
+
+        let mut other = Completions::default();
+        other.add_described("kill".to_string(), Some("Ignored".to_string()));
+        other.add_described("kiss".to_string(), Some("Smooch".to_string()));
+        completions.merge(other);
+
+        assert_eq!(completions.iter().count(), 4);
+        assert_eq!(completions.description("kill"), Some(&"Attack".to_string()));
+        assert_eq!(completions.description("kick"), None);
+        assert_eq!(completions.description("kiss"), Some(&"Smooch".to_string()));
+    }
+
     #[test]
     fn confirm_merge() {
         let mut completions = Completions::default();
@@ -0,0 +1,27 @@
+use crate::io::SaveData;
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+
+/// How input from the prompt is sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    /// Milliseconds to wait between sending the lines of a multi-line input
+    pub send_delay: u64,
+}
+
+impl InputSettings {
+    pub fn send_delay(&self) -> Duration {
+        Duration::from_millis(self.send_delay)
+    }
+}
+
+impl SaveData for InputSettings {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("input.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
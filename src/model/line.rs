@@ -1,3 +1,4 @@
+use chrono::{DateTime, Local};
 use log::error;
 use std::fmt;
 use strip_ansi_escapes::strip as strip_ansi;
@@ -27,6 +28,9 @@ pub struct Tag {
     pub color: String,
 }
 
+/// Tags using this symbol are drawn with the symbol of the active theme.
+pub const DEFAULT_TAG_SYMBOL: char = '┃';
+
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct TagMask {
     pub symbol: Option<char>,
@@ -38,7 +42,7 @@ pub struct TagMask {
 impl Default for Tag {
     fn default() -> Self {
         Self {
-            symbol: '┃',
+            symbol: DEFAULT_TAG_SYMBOL,
             key: String::new(),
             color: String::new(),
         }
@@ -60,6 +64,14 @@ pub struct Flags {
     /// Set when the line contained screen-clearing escape sequences (ED sequences)
     /// that were filtered out. The UI should clear its output area when this is set.
     pub screen_clear: bool,
+    /// Set when the line contained BEL characters, which were filtered out.
+    pub bell: bool,
+    /// Set on input typed while the server hides it, eg. a password. It's kept
+    /// out of the echo, the log and the command history.
+    pub secret: bool,
+    /// Set on input sent by triggers running on a replayed line. It's never
+    /// sent to the server.
+    pub replay: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -69,6 +81,8 @@ pub struct Line {
     clean_utf8: bool,
     pub tag: Tag,
     pub flags: Flags,
+    /// When the line was received or created
+    time: DateTime<Local>,
 }
 
 impl Eq for Line {}
@@ -170,12 +184,79 @@ fn filter_screen_clear_sequences(s: &str) -> (String, bool) {
     (result, had_screen_clear)
 }
 
-fn get_content_from(line: &str) -> (String, String, bool, bool) {
+/// Filters out BEL characters, except those ending OSC sequences.
+/// Returns (filtered_content, had_bell).
+fn filter_bell(s: &str) -> (String, bool) {
+    if !s.contains('\x07') {
+        return (s.to_string(), false);
+    }
+    let mut result = String::with_capacity(s.len());
+    let mut had_bell = false;
+    let mut in_osc = false;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' if chars.peek() == Some(&']') => in_osc = true,
+            '\x1b' if chars.peek() == Some(&'\\') => in_osc = false,
+            '\x07' if in_osc => in_osc = false,
+            '\x07' => {
+                had_bell = true;
+                continue;
+            }
+            _ => {}
+        }
+        result.push(c);
+    }
+    (result, had_bell)
+}
+
+/// The length of an escape sequence at the start of `s` moving the cursor or
+/// erasing part of a line, which only draw correctly on a screen.
+fn cursor_sequence_len(s: &str) -> Option<usize> {
+    let rest = s.strip_prefix('\x1b')?;
+    if rest.starts_with(['7', '8']) {
+        return Some(2);
+    }
+    let csi = rest.strip_prefix('[')?;
+    let params = csi.trim_start_matches(|c: char| c.is_ascii_digit() || c == ';');
+    if params.starts_with([
+        'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'K', 'd', 'f', 's', 'u',
+    ]) {
+        Some(s.len() - params.len() + 1)
+    } else {
+        None
+    }
+}
+
+fn has_cursor_sequences(s: &str) -> bool {
+    s.match_indices('\x1b')
+        .any(|(start, _)| cursor_sequence_len(&s[start..]).is_some())
+}
+
+/// Filters out the sequences found by `cursor_sequence_len`.
+fn strip_cursor_sequences(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('\x1b') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let len = cursor_sequence_len(rest).unwrap_or_else(|| {
+            result.push('\x1b');
+            1
+        });
+        rest = &rest[len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn get_content_from(line: &str) -> (String, String, bool, bool, bool) {
     let mut clean_utf8 = true;
     let trimmed = line.trim_end().to_string();
 
     // Filter out screen-clearing sequences before storing content
     let (content, screen_clear) = filter_screen_clear_sequences(&trimmed);
+    let (content, bell) = filter_bell(&content);
 
     let bytes = strip_ansi(&content);
     let clean_content = if let Ok(clean) = String::from_utf8(bytes.clone()) {
@@ -186,7 +267,7 @@ fn get_content_from(line: &str) -> (String, String, bool, bool) {
         String::from_utf8_lossy(&bytes).to_mut().clone()
     };
     let clean_content = clean_content.replace('\r', "");
-    (content, clean_content, clean_utf8, screen_clear)
+    (content, clean_content, clean_utf8, screen_clear, bell)
 }
 
 impl fmt::Display for Line {
@@ -203,13 +284,14 @@ impl From<&Line> for Line {
             clean_utf8: line.clean_utf8,
             tag: line.tag.clone(),
             flags: line.flags.clone(),
+            time: line.time,
         }
     }
 }
 
 impl From<&str> for Line {
     fn from(line: &str) -> Self {
-        let (content, clean_content, clean_utf8, screen_clear) = get_content_from(line);
+        let (content, clean_content, clean_utf8, screen_clear, bell) = get_content_from(line);
         Self {
             content,
             clean_content,
@@ -217,15 +299,17 @@ impl From<&str> for Line {
             tag: Tag::default(),
             flags: Flags {
                 screen_clear,
+                bell,
                 ..Flags::default()
             },
+            time: Local::now(),
         }
     }
 }
 
 impl From<String> for Line {
     fn from(line: String) -> Self {
-        let (content, clean_content, clean_utf8, screen_clear) = get_content_from(&line);
+        let (content, clean_content, clean_utf8, screen_clear, bell) = get_content_from(&line);
         Self {
             content,
             clean_content,
@@ -233,15 +317,17 @@ impl From<String> for Line {
             tag: Tag::default(),
             flags: Flags {
                 screen_clear,
+                bell,
                 ..Flags::default()
             },
+            time: Local::now(),
         }
     }
 }
 
 impl From<&String> for Line {
     fn from(line: &String) -> Self {
-        let (content, clean_content, clean_utf8, screen_clear) = get_content_from(line);
+        let (content, clean_content, clean_utf8, screen_clear, bell) = get_content_from(line);
         Self {
             content,
             clean_content,
@@ -249,8 +335,10 @@ impl From<&String> for Line {
             tag: Tag::default(),
             flags: Flags {
                 screen_clear,
+                bell,
                 ..Flags::default()
             },
+            time: Local::now(),
         }
     }
 }
@@ -264,7 +352,7 @@ impl From<&[u8]> for Line {
             String::from_utf8_lossy(line).to_mut().clone()
         };
 
-        let (content, clean_content, clean_utf8, screen_clear) = get_content_from(&line);
+        let (content, clean_content, clean_utf8, screen_clear, bell) = get_content_from(&line);
         Self {
             content,
             clean_content,
@@ -272,8 +360,10 @@ impl From<&[u8]> for Line {
             tag: Tag::default(),
             flags: Flags {
                 screen_clear,
+                bell,
                 ..Flags::default()
             },
+            time: Local::now(),
         }
     }
 }
@@ -288,7 +378,7 @@ impl From<&Vec<u8>> for Line {
             String::from_utf8_lossy(line).to_mut().clone()
         };
 
-        let (content, clean_content, _, screen_clear) = get_content_from(&line);
+        let (content, clean_content, _, screen_clear, bell) = get_content_from(&line);
         Self {
             content,
             clean_content,
@@ -296,8 +386,10 @@ impl From<&Vec<u8>> for Line {
             tag: Tag::default(),
             flags: Flags {
                 screen_clear,
+                bell,
                 ..Flags::default()
             },
+            time: Local::now(),
         }
     }
 }
@@ -305,11 +397,27 @@ impl From<&Vec<u8>> for Line {
 #[allow(dead_code)]
 impl Line {
     pub fn set_content(&mut self, line: &str) {
-        let (content, clean_content, clean_utf8, screen_clear) = get_content_from(line);
+        let (content, clean_content, clean_utf8, screen_clear, bell) = get_content_from(line);
         self.content = content;
         self.clean_content = clean_content;
         self.clean_utf8 = clean_utf8;
         self.flags.screen_clear = screen_clear;
+        self.flags.bell = bell;
+    }
+
+    /// Whether the line draws on the screen with cursor movement or erase
+    /// sequences, rather than being a line of text.
+    pub fn draws_screen(&self) -> bool {
+        self.flags.screen_clear || has_cursor_sequences(&self.content)
+    }
+
+    /// A copy of a line drawing on the screen with only its text and colors,
+    /// to keep in the scrollback.
+    pub fn without_cursor_sequences(&self) -> Line {
+        let mut line = self.clone();
+        line.set_content(&strip_cursor_sequences(&self.content));
+        line.flags.screen_clear = false;
+        line
     }
 
     pub fn print_line(&self) -> Option<&str> {
@@ -320,18 +428,31 @@ impl Line {
         }
     }
 
-    pub fn tagged_line(&self) -> Option<String> {
+    pub fn tagged_line(&self, default_symbol: char) -> Option<String> {
+        let symbol = if self.tag.symbol == DEFAULT_TAG_SYMBOL {
+            default_symbol
+        } else {
+            self.tag.symbol
+        };
         self.print_line().map(|content| {
             if self.tag.color.is_empty() {
                 format!("  {}", content)
-            } else if self.tag.symbol.width() == Some(2) {
-                format!("{}{}\x1b[0m{}", self.tag.color, self.tag.symbol, content)
+            } else if symbol.width() == Some(2) {
+                format!("{}{}\x1b[0m{}", self.tag.color, symbol, content)
             } else {
-                format!("{}{} \x1b[0m{}", self.tag.color, self.tag.symbol, content)
+                format!("{}{} \x1b[0m{}", self.tag.color, symbol, content)
             }
         })
     }
 
+    pub fn time(&self) -> DateTime<Local> {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: DateTime<Local>) {
+        self.time = time;
+    }
+
     pub fn is_utf8(&self) -> bool {
         self.clean_utf8
     }
@@ -374,7 +495,7 @@ impl Line {
 #[cfg(test)]
 mod test_line {
 
-    use super::Line;
+    use super::{Line, DEFAULT_TAG_SYMBOL};
 
     #[test]
     fn test_gag_flag() {
@@ -543,11 +664,53 @@ mod test_line {
         assert!(line.flags.screen_clear);
     }
 
+    #[test]
+    fn test_draws_screen() {
+        assert!(Line::from("\x1b[2J").draws_screen());
+        assert!(Line::from("\x1b[10;5H| map |").draws_screen());
+        assert!(Line::from("\x1b[H").draws_screen());
+        assert!(Line::from("menu\x1b[K").draws_screen());
+        assert!(Line::from("\x1b7status\x1b8").draws_screen());
+        assert!(!Line::from("\x1b[1;31mred\x1b[0m").draws_screen());
+        assert!(!Line::from("plain text").draws_screen());
+    }
+
+    #[test]
+    fn test_without_cursor_sequences() {
+        let line = Line::from("\x1b[2J\x1b[10;5H\x1b[31m| map |\x1b[K\x1b7x\x1b8\x1b[0m");
+        let copy = line.without_cursor_sequences();
+        assert_eq!(copy.line(), "\x1b[31m| map |x\x1b[0m");
+        assert!(!copy.draws_screen());
+    }
+
+    #[test]
+    fn test_bell_filter() {
+        let line = Line::from("Bob tells you: hi\x07");
+        assert_eq!(line.line(), "Bob tells you: hi");
+        assert!(line.flags.bell);
+        let line = Line::from("Bob tells you: hi");
+        assert!(!line.flags.bell);
+    }
+
+    #[test]
+    fn test_bell_filter_preserves_osc() {
+        let link = "\x1b]8;;https://example.com\x07link\x1b]8;;\x07";
+        let line = Line::from(link);
+        assert_eq!(line.line(), link);
+        assert!(!line.flags.bell);
+        let line = Line::from(format!("\x07{link}\x1b]2;title\x1b\\\x07"));
+        assert_eq!(line.line(), format!("{link}\x1b]2;title\x1b\\"));
+        assert!(line.flags.bell);
+    }
+
     #[test]
     fn test_tagged_line_default() {
         let line = Line::from("hello");
         // Default tag has no color: render two spaces
-        assert_eq!(line.tagged_line(), Some("  hello".to_string()));
+        assert_eq!(
+            line.tagged_line(DEFAULT_TAG_SYMBOL),
+            Some("  hello".to_string())
+        );
     }
 
     #[test]
@@ -555,16 +718,25 @@ mod test_line {
         let mut line = Line::from("hello");
         line.tag.color = "\x1b[31m".to_string();
         assert_eq!(
-            line.tagged_line(),
+            line.tagged_line(DEFAULT_TAG_SYMBOL),
             Some("\x1b[31m┃ \x1b[0mhello".to_string())
         );
+        assert_eq!(
+            line.tagged_line('|'),
+            Some("\x1b[31m| \x1b[0mhello".to_string())
+        );
+        line.tag.symbol = '!';
+        assert_eq!(
+            line.tagged_line('|'),
+            Some("\x1b[31m! \x1b[0mhello".to_string())
+        );
     }
 
     #[test]
     fn test_tagged_line_gagged() {
         let mut line = Line::from("hello");
         line.flags.gag = true;
-        assert_eq!(line.tagged_line(), None);
+        assert_eq!(line.tagged_line(DEFAULT_TAG_SYMBOL), None);
     }
 
     #[test]
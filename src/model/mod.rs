@@ -1,13 +1,29 @@
+mod bell;
+mod color_depth;
 mod completions;
 mod connection;
+mod input_settings;
 mod line;
+mod palette;
 mod prompt_mask;
 mod regex;
 mod settings;
+mod style;
+mod theme;
+mod vt;
+mod wrapping;
 
 pub use self::{regex::Regex, regex::RegexOptions};
-pub use completions::Completions;
+pub use bell::{BellAction, BellSettings};
+pub use color_depth::{recolor, ColorDepth, ColorSettings};
+pub use completions::{CompletionMenu, Completions};
 pub use connection::{Connection, Servers};
+pub use input_settings::InputSettings;
 pub use line::{Line, TagMask, ToLine};
+pub use palette::{Palette, Palettes};
 pub use prompt_mask::PromptMask;
 pub use settings::*;
+pub use style::{highlight, parse_spans, render_spans, substitute, Color, Span, Style};
+pub use theme::Theme;
+pub use vt::{VtGrid, VtSettings};
+pub use wrapping::*;
@@ -0,0 +1,186 @@
+use crate::io::SaveData;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+
+use super::Color;
+
+/// Stands for the terminal's own foreground or background color.
+const DEFAULT_COLOR: &str = "default";
+
+/// Colors drawn in place of the ones the mud asks for, keyed by the name of
+/// the replaced color. Only what's drawn changes, the content of lines seen by
+/// scripts is left as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    pub fg: BTreeMap<String, String>,
+    pub bg: BTreeMap<String, String>,
+}
+
+/// Parses a color for a palette, returning its canonical name.
+fn color_name(name: &str) -> Result<String> {
+    if name == DEFAULT_COLOR {
+        Ok(name.to_string())
+    } else {
+        Ok(Color::named(name)?.name())
+    }
+}
+
+impl Palette {
+    pub fn is_empty(&self) -> bool {
+        self.fg.is_empty() && self.bg.is_empty()
+    }
+
+    fn layer(&mut self, background: bool) -> &mut BTreeMap<String, String> {
+        if background {
+            &mut self.bg
+        } else {
+            &mut self.fg
+        }
+    }
+
+    /// Draws `color` as `to`, or as itself again when `to` is `None`.
+    pub fn remap(&mut self, color: &str, to: Option<&str>, background: bool) -> Result<()> {
+        let color = color_name(color)?;
+        match to {
+            Some(to) => {
+                let to = color_name(to)?;
+                self.layer(background).insert(color, to);
+            }
+            None => {
+                self.layer(background).remove(&color);
+            }
+        }
+        Ok(())
+    }
+
+    /// The color to draw in place of `color`, `None` standing for the
+    /// terminal's own one.
+    pub fn color(&self, color: Option<Color>, background: bool) -> Option<Color> {
+        let layer = if background { &self.bg } else { &self.fg };
+        let name = color.map_or_else(|| DEFAULT_COLOR.to_string(), |color| color.name());
+        match layer.get(&name) {
+            Some(to) => Color::named(to).ok(),
+            None => color,
+        }
+    }
+
+    /// Adds the entries of `other` over those of this palette.
+    fn merge(&mut self, other: &Palette) {
+        self.fg.extend(other.fg.clone());
+        self.bg.extend(other.bg.clone());
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palettes {
+    /// Used for every world
+    pub palette: Palette,
+    /// Entries used instead of those of `palette` for specific worlds
+    pub worlds: BTreeMap<String, Palette>,
+}
+
+impl Palettes {
+    /// The palette of `world`, or of all worlds when `None`.
+    pub fn get_mut(&mut self, world: Option<&str>) -> &mut Palette {
+        match world {
+            Some(world) => self.worlds.entry(world.to_string()).or_default(),
+            None => &mut self.palette,
+        }
+    }
+
+    /// The palette output is drawn with while connected to `world`.
+    pub fn active(&self, world: Option<&str>) -> Palette {
+        let mut palette = self.palette.clone();
+        if let Some(world) = world.and_then(|world| self.worlds.get(world)) {
+            palette.merge(world);
+        }
+        palette
+    }
+
+    /// Drops the worlds left with an empty palette.
+    pub fn prune(&mut self) {
+        self.worlds.retain(|_, palette| !palette.is_empty());
+    }
+}
+
+impl SaveData for Palettes {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("palettes.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod palette_test {
+    use super::{Palette, Palettes};
+    use crate::model::Color;
+
+    #[test]
+    fn test_remap() {
+        let mut palette = Palette::default();
+        palette.remap("blue", Some("#5c5cff"), false).unwrap();
+        palette.remap("4", Some("12"), true).unwrap();
+        palette.remap("default", Some("black"), true).unwrap();
+        assert_eq!(palette.fg.get("blue"), Some(&"#5c5cff".to_string()));
+        assert_eq!(palette.bg.get("blue"), Some(&"bblue".to_string()));
+        assert_eq!(palette.bg.get("default"), Some(&"black".to_string()));
+        assert!(palette.remap("pink", Some("red"), false).is_err());
+        assert!(palette.remap("red", Some("pink"), false).is_err());
+
+        assert_eq!(
+            palette.color(Some(Color::Indexed(4)), false),
+            Some(Color::Rgb(0x5c, 0x5c, 0xff))
+        );
+        assert_eq!(
+            palette.color(Some(Color::Indexed(1)), false),
+            Some(Color::Indexed(1))
+        );
+        assert_eq!(palette.color(None, false), None);
+        assert_eq!(palette.color(None, true), Some(Color::Indexed(0)));
+
+        palette.remap("blue", None, false).unwrap();
+        assert_eq!(
+            palette.color(Some(Color::Indexed(4)), false),
+            Some(Color::Indexed(4))
+        );
+    }
+
+    #[test]
+    fn test_world_palettes() {
+        let mut palettes = Palettes::default();
+        palettes
+            .get_mut(None)
+            .remap("blue", Some("bblue"), false)
+            .unwrap();
+        palettes
+            .get_mut(Some("arctic"))
+            .remap("blue", Some("cyan"), false)
+            .unwrap();
+        palettes
+            .get_mut(Some("arctic"))
+            .remap("red", Some("bred"), false)
+            .unwrap();
+        let blue = Some(Color::Indexed(4));
+        assert_eq!(
+            palettes.active(None).color(blue, false),
+            Some(Color::Indexed(12))
+        );
+        assert_eq!(
+            palettes.active(Some("other")).color(blue, false),
+            Some(Color::Indexed(12))
+        );
+        let arctic = palettes.active(Some("arctic"));
+        assert_eq!(arctic.color(blue, false), Some(Color::Indexed(6)));
+        assert_eq!(arctic.fg.len(), 2);
+
+        *palettes.get_mut(Some("arctic")) = Palette::default();
+        palettes.prune();
+        assert!(palettes.worlds.is_empty());
+    }
+}
@@ -2,6 +2,9 @@ use mlua::{Integer as LuaInt, Lua, Result as LuaResult, String as LuaString, Tab
 use std::collections::BTreeMap;
 use std::ops::{AddAssign, Deref, DerefMut};
 
+/// Shown in place of each character of secret input.
+const SECRET_CHAR: char = '*';
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct PromptMask {
     mask: BTreeMap<i32, String>,
@@ -33,6 +36,13 @@ impl PromptMask {
         masked_buf.iter().collect()
     }
 
+    /// Hides every character of the input, eg. while typing a password.
+    pub fn hide_buffer(buf: &[char]) -> String {
+        buf.iter()
+            .map(|c| if *c == '\n' { '\n' } else { SECRET_CHAR })
+            .collect()
+    }
+
     pub fn to_table<'a>(&'a self, ctx: &'a Lua) -> LuaResult<LuaTable> {
         ctx.create_table_from(self.iter().map(|(idx, mask)| (*idx + 1, (*mask).clone())))
     }
@@ -153,4 +163,12 @@ mod test_prompt_mask {
         let res = invalid_mask.mask_buffer(&buf);
         assert_eq!(res, "this is *important, ok");
     }
+
+    #[test]
+    fn test_hide_buffer() {
+        let buf: Vec<char> = "hunter2".chars().collect();
+        assert_eq!(PromptMask::hide_buffer(&buf), "*******");
+        let buf: Vec<char> = "ab\ncd".chars().collect();
+        assert_eq!(PromptMask::hide_buffer(&buf), "**\n**");
+    }
 }
@@ -23,10 +23,13 @@ pub const COMMAND_SEARCH: &str = "command_search";
 pub const SMART_HISTORY: &str = "smart_history";
 pub const ECHO_INPUT: &str = "echo_input";
 pub const LAST_COMMAND: &str = "last_command";
+pub const TIMESTAMPS: &str = "timestamps";
+pub const LOG_TIMESTAMPS: &str = "log_timestamps";
+pub const SAVE_SCROLLBACK: &str = "save_scrollback";
 
 pub const KEEPALIVE_ENABLED: &str = "keepalive_enabled";
 
-pub const SETTINGS: [&str; 14] = [
+pub const SETTINGS: [&str; 17] = [
     LOGGING_ENABLED,
     TTS_ENABLED,
     MOUSE_ENABLED,
@@ -40,6 +43,9 @@ pub const SETTINGS: [&str; 14] = [
     SMART_HISTORY,
     ECHO_INPUT,
     LAST_COMMAND,
+    TIMESTAMPS,
+    LOG_TIMESTAMPS,
+    SAVE_SCROLLBACK,
     KEEPALIVE_ENABLED,
 ];
 
@@ -78,6 +84,9 @@ impl Default for Settings {
         settings.insert(SMART_HISTORY.to_string(), false);
         settings.insert(ECHO_INPUT.to_string(), true);
         settings.insert(LAST_COMMAND.to_string(), true);
+        settings.insert(TIMESTAMPS.to_string(), false);
+        settings.insert(LOG_TIMESTAMPS.to_string(), false);
+        settings.insert(SAVE_SCROLLBACK.to_string(), false);
         settings.insert(KEEPALIVE_ENABLED.to_string(), true);
         Self { settings }
     }
@@ -0,0 +1,621 @@
+use anyhow::{bail, Result};
+use regex::Captures;
+
+use super::ColorDepth;
+use vte::{Params, Parser, Perform};
+
+/// The names of the 16 basic colors, as used by `cformat` in Lua.
+const COLOR_NAMES: [&str; 16] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white", "bblack", "bred",
+    "bgreen", "byellow", "bblue", "bmagenta", "bcyan", "bwhite",
+];
+
+/// The xterm colors for the 16 basic SGR colors.
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x00, 0x00),
+    (0x00, 0xcd, 0x00),
+    (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee),
+    (0xcd, 0x00, 0xcd),
+    (0x00, 0xcd, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0x00, 0x00),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+/// A color set by an SGR sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// One of the 256 palette colors, the first 16 being the basic ones
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Parses a basic color name like `red` or `bred`, a palette index or a
+    /// `#rrggbb` hex color.
+    pub fn named(name: &str) -> Result<Self> {
+        if let Some(index) = COLOR_NAMES.iter().position(|color| *color == name) {
+            Ok(Self::Indexed(index as u8))
+        } else if let Ok(index) = name.parse::<u8>() {
+            Ok(Self::Indexed(index))
+        } else if let Some(hex) = name.strip_prefix('#').filter(|hex| hex.len() == 6) {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+            match (channel(0), channel(2), channel(4)) {
+                (Ok(r), Ok(g), Ok(b)) => Ok(Self::Rgb(r, g, b)),
+                _ => bail!("Invalid color: {name}"),
+            }
+        } else {
+            bail!("Invalid color: {name}, use a name like red or bred, 0-255 or #rrggbb")
+        }
+    }
+
+    /// The name `Color::named` parses back.
+    pub fn name(&self) -> String {
+        match self {
+            Self::Indexed(index) if *index < 16 => COLOR_NAMES[*index as usize].to_string(),
+            Self::Indexed(index) => index.to_string(),
+            Self::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+
+    /// The xterm RGB value of the color.
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            Self::Indexed(index @ 0..=15) => BASIC_COLORS[index as usize],
+            Self::Indexed(index @ 16..=231) => {
+                let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+                let index = index - 16;
+                (level(index / 36), level(index / 6 % 6), level(index % 6))
+            }
+            Self::Indexed(index) => {
+                let gray = 8 + (index - 232) * 10;
+                (gray, gray, gray)
+            }
+            Self::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    /// The closest color a terminal with `depth` colors can show.
+    pub fn downsample(self, depth: ColorDepth) -> Self {
+        match (self, depth) {
+            (_, ColorDepth::TrueColor) => self,
+            (Self::Indexed(_), ColorDepth::Indexed) => self,
+            (Self::Indexed(index), ColorDepth::Basic) if index < 16 => self,
+            (_, ColorDepth::Indexed) => {
+                let rgb = self.rgb();
+                let cube = |value: u8| match value {
+                    0..=47 => 0,
+                    48..=114 => 1,
+                    _ => (value - 35) / 40,
+                };
+                let cube = Self::Indexed(16 + 36 * cube(rgb.0) + 6 * cube(rgb.1) + cube(rgb.2));
+                let average = (rgb.0 as u16 + rgb.1 as u16 + rgb.2 as u16) / 3;
+                let gray = Self::Indexed(232 + (average.saturating_sub(3) / 10).min(23) as u8);
+                nearest(rgb, [cube, gray])
+            }
+            (_, ColorDepth::Basic) => nearest(self.rgb(), (0..16).map(Self::Indexed)),
+        }
+    }
+
+    /// The SGR parameters selecting the color.
+    pub(super) fn sgr(&self, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+        match *self {
+            Self::Indexed(index @ 0..=7) => (base + index as u16).to_string(),
+            Self::Indexed(index @ 8..=15) => (base + 60 + index as u16 - 8).to_string(),
+            Self::Indexed(index) => format!("{};5;{index}", base + 8),
+            Self::Rgb(r, g, b) => format!("{};2;{r};{g};{b}", base + 8),
+        }
+    }
+}
+
+/// The color of `colors` closest to `rgb`.
+fn nearest(rgb: (u8, u8, u8), colors: impl IntoIterator<Item = Color>) -> Color {
+    let distance = |color: &Color| {
+        let other = color.rgb();
+        let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        channel(rgb.0, other.0) + channel(rgb.1, other.1) + channel(rgb.2, other.2)
+    };
+    colors
+        .into_iter()
+        .min_by_key(distance)
+        .unwrap_or(Color::Indexed(0))
+}
+
+/// Reads the color following a 38 or 48 SGR parameter.
+fn extended_color<'a>(params: &mut impl Iterator<Item = &'a u16>) -> Option<Color> {
+    match params.next()? {
+        5 => params
+            .next()
+            .map(|index| Color::Indexed((*index).min(255) as u8)),
+        2 => {
+            let mut channel = || params.next().map(|value| (*value).min(255) as u8);
+            Some(Color::Rgb(channel()?, channel()?, channel()?))
+        }
+        _ => None,
+    }
+}
+
+/// The colors and attributes text is printed with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+    pub strike: bool,
+}
+
+impl Style {
+    /// Applies the parameters of an SGR sequence.
+    pub fn apply(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            *self = Self::default();
+        }
+        let mut params = params.iter();
+        while let Some(param) = params.next() {
+            match param {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.reverse = true,
+                9 => self.strike = true,
+                21 | 22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.reverse = false,
+                29 => self.strike = false,
+                30..=37 => self.fg = Some(Color::Indexed((param - 30) as u8)),
+                38 => self.fg = extended_color(&mut params),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Indexed((param - 40) as u8)),
+                48 => self.bg = extended_color(&mut params),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(Color::Indexed((param - 90 + 8) as u8)),
+                100..=107 => self.bg = Some(Color::Indexed((param - 100 + 8) as u8)),
+                _ => {}
+            }
+        }
+    }
+
+    /// The SGR sequence switching to this style from any other.
+    pub fn sgr(&self) -> String {
+        let mut params = vec!["0".to_string()];
+        let attributes = [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.reverse, "7"),
+            (self.strike, "9"),
+        ];
+        for (set, param) in attributes {
+            if set {
+                params.push(param.to_string());
+            }
+        }
+        if let Some(fg) = self.fg {
+            params.push(fg.sgr(false));
+        }
+        if let Some(bg) = self.bg {
+            params.push(bg.sgr(true));
+        }
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+/// A run of text printed in the same style.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+/// A printable character of a line of output, with where it is in the line
+/// and the style it's printed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StyledChar {
+    c: char,
+    /// Byte range of the character in the line
+    start: usize,
+    end: usize,
+    style: Style,
+}
+
+struct CharCollector {
+    style: Style,
+    /// Byte offset of the byte being parsed
+    pos: usize,
+    chars: Vec<StyledChar>,
+}
+
+impl CharCollector {
+    fn push(&mut self, c: char) {
+        let end = self.pos + 1;
+        self.chars.push(StyledChar {
+            c,
+            start: end - c.len_utf8(),
+            end,
+            style: self.style,
+        });
+    }
+}
+
+impl Perform for CharCollector {
+    fn print(&mut self, c: char) {
+        self.push(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if byte == b'\t' {
+            self.push('\t');
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, _: &[u8], _: bool, action: char) {
+        if action == 'm' {
+            let params: Vec<u16> = params.iter().flatten().copied().collect();
+            self.style.apply(&params);
+        }
+    }
+}
+
+/// The printable characters of a line of output and the style at its end.
+fn styled_chars(raw: &str) -> (Vec<StyledChar>, Style) {
+    let mut collector = CharCollector {
+        style: Style::default(),
+        pos: 0,
+        chars: vec![],
+    };
+    let mut parser = Parser::new();
+    for (pos, byte) in raw.bytes().enumerate() {
+        collector.pos = pos;
+        parser.advance(&mut collector, &[byte]);
+    }
+    (collector.chars, collector.style)
+}
+
+/// Splits a line of output into runs of text with the same style. Escape
+/// sequences other than SGR are dropped.
+pub fn parse_spans(raw: &str) -> Vec<Span> {
+    let mut spans: Vec<Span> = vec![];
+    for styled in styled_chars(raw).0 {
+        match spans.last_mut() {
+            Some(span) if span.style == styled.style => span.text.push(styled.c),
+            _ => spans.push(Span {
+                text: styled.c.to_string(),
+                style: styled.style,
+            }),
+        }
+    }
+    spans
+}
+
+/// Rewrites the text `regex` matches in a line of output. Matches are found
+/// in the printable text of the line, `rewrite` returns the raw text to put
+/// in place of each given its characters and the style it starts in. Escape
+/// sequences outside the matches are kept as they are and the style the line
+/// had after a match is restored. Returns the line and the number of matches.
+fn rewrite(
+    raw: &str,
+    regex: &regex::Regex,
+    mut rewrite: impl FnMut(&Captures, &[StyledChar], Style) -> String,
+) -> (String, usize) {
+    let (chars, end_style) = styled_chars(raw);
+    let mut text = String::new();
+    // The character each byte of `text` belongs to
+    let mut char_at = vec![];
+    for (index, styled) in chars.iter().enumerate() {
+        text.push(styled.c);
+        char_at.resize(text.len(), index);
+    }
+    char_at.push(chars.len());
+
+    let mut output = String::with_capacity(raw.len());
+    let mut count = 0;
+    let mut pos = 0;
+    for captures in regex.captures_iter(&text) {
+        let found = captures.get(0).unwrap();
+        let matched = &chars[char_at[found.start()]..char_at[found.end()]];
+        let first = char_at[found.start()];
+        // Text inserted by an empty match goes right after the previous
+        // character, in its style
+        let (start, before) = match (matched.first(), first.checked_sub(1)) {
+            (Some(styled), _) => (styled.start, styled.style),
+            _ if first == chars.len() => (raw.len(), end_style),
+            (None, Some(previous)) => (chars[previous].end, chars[previous].style),
+            (None, None) => (0, Style::default()),
+        };
+        let (end, after) = match matched.last() {
+            Some(last) => (last.end, last.style),
+            None => (start, before),
+        };
+        output.push_str(&raw[pos..start]);
+        let replacement = rewrite(&captures, matched, before);
+        output.push_str(&replacement);
+        if after != before || replacement.contains('\x1b') {
+            output.push_str(&after.sgr());
+        }
+        pos = end;
+        count += 1;
+    }
+    output.push_str(&raw[pos..]);
+    (output, count)
+}
+
+/// Replaces the text `regex` matches in a line of output, keeping the colors
+/// around it. `$1` or `${name}` in `replacement` insert the captured groups.
+pub fn substitute(raw: &str, regex: &regex::Regex, replacement: &str) -> (String, usize) {
+    rewrite(raw, regex, |captures, _, _| {
+        let mut text = String::new();
+        captures.expand(replacement, &mut text);
+        text
+    })
+}
+
+/// Restyles the text `regex` matches in a line of output with the colors and
+/// attributes set in `overlay`. Other attributes of the matched text and the
+/// colors around it are kept.
+pub fn highlight(raw: &str, regex: &regex::Regex, overlay: &Style) -> (String, usize) {
+    rewrite(raw, regex, |_, matched, _| {
+        let mut text = String::new();
+        let mut current = None;
+        for styled in matched {
+            let style = Style {
+                fg: overlay.fg.or(styled.style.fg),
+                bg: overlay.bg.or(styled.style.bg),
+                bold: overlay.bold || styled.style.bold,
+                dim: overlay.dim || styled.style.dim,
+                italic: overlay.italic || styled.style.italic,
+                underline: overlay.underline || styled.style.underline,
+                reverse: overlay.reverse || styled.style.reverse,
+                strike: overlay.strike || styled.style.strike,
+            };
+            if current != Some(style) {
+                text.push_str(&style.sgr());
+                current = Some(style);
+            }
+            text.push(styled.c);
+        }
+        text
+    })
+}
+
+/// Builds a line of output from styled runs of text, see `parse_spans`.
+pub fn render_spans(spans: &[Span]) -> String {
+    let mut output = String::new();
+    let mut style = Style::default();
+    for span in spans {
+        if span.style != style {
+            output.push_str(&span.style.sgr());
+            style = span.style;
+        }
+        output.push_str(&span.text);
+    }
+    if style != Style::default() {
+        output.push_str("\x1b[0m");
+    }
+    output
+}
+
+#[cfg(test)]
+mod style_test {
+    use super::*;
+
+    #[test]
+    fn test_color_names() {
+        for name in ["red", "bwhite", "196", "#0a0b0c"] {
+            assert_eq!(Color::named(name).unwrap().name(), name);
+        }
+        assert_eq!(Color::named("1").unwrap(), Color::Indexed(1));
+        assert!(Color::named("pink").is_err());
+        assert!(Color::named("#12345z").is_err());
+        assert!(Color::named("256").is_err());
+    }
+
+    #[test]
+    fn test_rgb() {
+        assert_eq!(Color::Indexed(1).rgb(), (0xcd, 0x00, 0x00));
+        assert_eq!(Color::Indexed(16).rgb(), (0, 0, 0));
+        assert_eq!(Color::Indexed(231).rgb(), (255, 255, 255));
+        assert_eq!(Color::Indexed(232).rgb(), (8, 8, 8));
+        assert_eq!(Color::Indexed(255).rgb(), (238, 238, 238));
+    }
+
+    #[test]
+    fn test_parse_spans() {
+        let spans =
+            parse_spans("\x1b[1;31mred\x1b[22m still \x1b[0mplain\x1b[38;5;196;48;2;1;2;3mx");
+        assert_eq!(
+            spans,
+            vec![
+                Span {
+                    text: "red".to_string(),
+                    style: Style {
+                        fg: Some(Color::Indexed(1)),
+                        bold: true,
+                        ..Style::default()
+                    },
+                },
+                Span {
+                    text: " still ".to_string(),
+                    style: Style {
+                        fg: Some(Color::Indexed(1)),
+                        ..Style::default()
+                    },
+                },
+                Span {
+                    text: "plain".to_string(),
+                    style: Style::default(),
+                },
+                Span {
+                    text: "x".to_string(),
+                    style: Style {
+                        fg: Some(Color::Indexed(196)),
+                        bg: Some(Color::Rgb(1, 2, 3)),
+                        ..Style::default()
+                    },
+                },
+            ]
+        );
+        assert!(parse_spans("").is_empty());
+        assert_eq!(parse_spans("\x1b[31m\x1b[0mplain")[0].text, "plain");
+    }
+
+    #[test]
+    fn test_render_spans() {
+        let raw = "\x1b[1;31mred\x1b[22m still \x1b[0mplain\x1b[38;5;196;48;2;1;2;3mx";
+        let rendered = render_spans(&parse_spans(raw));
+        assert_eq!(
+            rendered,
+            "\x1b[0;1;31mred\x1b[0;31m still \x1b[0mplain\x1b[0;38;5;196;48;2;1;2;3mx\x1b[0m"
+        );
+        assert_eq!(parse_spans(&rendered), parse_spans(raw));
+        assert_eq!(render_spans(&parse_spans("plain")), "plain");
+    }
+
+    #[test]
+    fn test_styled_chars() {
+        let (chars, end) = styled_chars("a\x1b[31mé\x1b[0m");
+        assert_eq!(chars.len(), 2);
+        assert_eq!((chars[0].start, chars[0].end), (0, 1));
+        assert_eq!((chars[1].c, chars[1].start, chars[1].end), ('é', 6, 8));
+        assert_eq!(chars[1].style.fg, Some(Color::Indexed(1)));
+        assert_eq!(end, Style::default());
+    }
+
+    fn regex(pattern: &str) -> regex::Regex {
+        regex::Regex::new(pattern).unwrap()
+    }
+
+    fn fg(index: u8) -> Style {
+        Style {
+            fg: Some(Color::Indexed(index)),
+            ..Style::default()
+        }
+    }
+
+    #[test]
+    fn test_substitute() {
+        let raw = "\x1b[32mBob\x1b[0m says: \x1b[33mhello there\x1b[0m";
+        assert_eq!(
+            substitute(raw, &regex("hello"), "goodbye"),
+            (
+                "\x1b[32mBob\x1b[0m says: \x1b[33mgoodbye there\x1b[0m".to_string(),
+                1
+            )
+        );
+        // A match across a color change keeps the color after it
+        assert_eq!(
+            substitute(raw, &regex("Bob says"), "Alice yells").0,
+            "\x1b[32mAlice yells\x1b[0m: \x1b[33mhello there\x1b[0m"
+        );
+        assert_eq!(
+            substitute(raw, &regex(r"(\w+) there"), "$1 world").0,
+            "\x1b[32mBob\x1b[0m says: \x1b[33mhello world\x1b[0m"
+        );
+        assert_eq!(
+            substitute(raw, &regex("e"), "E"),
+            (
+                "\x1b[32mBob\x1b[0m says: \x1b[33mhEllo thErE\x1b[0m".to_string(),
+                3
+            )
+        );
+        assert_eq!(substitute(raw, &regex("^"), "> ").0, format!("> {raw}"));
+        assert_eq!(substitute(raw, &regex("$"), "!").0, format!("{raw}!"));
+        assert_eq!(
+            substitute(raw, &regex("nothing"), "x"),
+            (raw.to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn test_substitute_with_colors() {
+        let raw = "\x1b[33mgold\x1b[0m coins";
+        assert_eq!(
+            substitute(raw, &regex("gold"), "\x1b[1mGOLD").0,
+            "\x1b[33m\x1b[1mGOLD\x1b[0;33m\x1b[0m coins"
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let raw = "You hit the \x1b[1morc\x1b[0m hard";
+        assert_eq!(
+            highlight(raw, &regex("the orc"), &fg(1)),
+            (
+                "You hit \x1b[0;31mthe \x1b[0;1;31morc\x1b[0;1m\x1b[0m hard".to_string(),
+                1
+            )
+        );
+        assert_eq!(
+            highlight(
+                raw,
+                &regex("hit"),
+                &Style {
+                    bg: Some(Color::Indexed(4)),
+                    ..Style::default()
+                }
+            )
+            .0,
+            "You \x1b[0;44mhit\x1b[0m the \x1b[1morc\x1b[0m hard"
+        );
+        assert_eq!(
+            parse_spans(&highlight(raw, &regex("or"), &fg(2)).0),
+            vec![
+                Span {
+                    text: "You hit the ".to_string(),
+                    style: Style::default(),
+                },
+                Span {
+                    text: "or".to_string(),
+                    style: Style {
+                        fg: Some(Color::Indexed(2)),
+                        bold: true,
+                        ..Style::default()
+                    },
+                },
+                Span {
+                    text: "c".to_string(),
+                    style: Style {
+                        bold: true,
+                        ..Style::default()
+                    },
+                },
+                Span {
+                    text: " hard".to_string(),
+                    style: Style::default(),
+                },
+            ]
+        );
+        let underline = Style {
+            underline: true,
+            ..Style::default()
+        };
+        let spans = parse_spans(&highlight(raw, &regex("orc"), &underline).0);
+        assert_eq!(
+            spans[1].style,
+            Style {
+                bold: true,
+                underline: true,
+                ..Style::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use super::Style;
+use crate::io::SaveData;
+use anyhow::{bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+pub const DEFAULT_THEME: &str = "default";
+pub const BUILTIN_THEMES: [&str; 4] = [DEFAULT_THEME, "high-contrast", "monochrome", "ascii"];
+
+/// Colors and glyphs used for the UI chrome.
+///
+/// Styles are written as a space separated list of attributes and colors, eg.
+/// `"bold lightwhite on blue"`. Colors following `on` apply to the background.
+/// Colors are either a name (`red`, `lightred`...), a 256 color index or a
+/// `#rrggbb` hex value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    /// Top bar, status area bars and separators
+    pub bar: String,
+    pub bar_glyph: char,
+    pub top_bar_glyph: char,
+    /// Symbol used when rendering tags for lines that don't set their own
+    pub tag_symbol: char,
+    pub error: String,
+    pub error_prefix: String,
+    pub info: String,
+    pub info_prefix: String,
+    /// Echoed user input
+    pub echo: String,
+    pub echo_prefix: String,
+    /// Highlighted search matches in the scrollback
+    pub search_match: String,
+    /// Scroll mode cursor and the selected completion
+    pub selection: String,
+    /// The 'last command' shown in an empty prompt
+    pub last_command: String,
+    /// The receive time gutter, shown when the `timestamps` setting is on
+    pub timestamp: String,
+    /// A `strftime` style format for the receive time gutter
+    pub timestamp_format: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_THEME.to_string(),
+            bar: "green".to_string(),
+            bar_glyph: '━',
+            top_bar_glyph: '═',
+            tag_symbol: '┃',
+            error: "red".to_string(),
+            error_prefix: "[!!]".to_string(),
+            info: String::new(),
+            info_prefix: "[**]".to_string(),
+            echo: "lightyellow".to_string(),
+            echo_prefix: ">".to_string(),
+            search_match: "lightwhite on blue".to_string(),
+            selection: "inverse".to_string(),
+            last_command: "on blue".to_string(),
+            timestamp: "lightblack".to_string(),
+            timestamp_format: "%H:%M:%S".to_string(),
+        }
+    }
+}
+
+impl SaveData for Theme {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("theme.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+impl Theme {
+    /// Loads a built-in theme or a user theme from the `themes` directory in
+    /// the config dir.
+    pub fn named(name: &str) -> Result<Self> {
+        let theme = match name {
+            DEFAULT_THEME => Self::default(),
+            "high-contrast" => Self {
+                name: name.to_string(),
+                bar: "bold lightwhite".to_string(),
+                error: "bold lightwhite on red".to_string(),
+                info: "bold lightwhite".to_string(),
+                echo: "bold lightcyan".to_string(),
+                search_match: "bold black on lightyellow".to_string(),
+                selection: "bold black on lightwhite".to_string(),
+                last_command: "black on lightwhite".to_string(),
+                timestamp: "lightwhite".to_string(),
+                ..Self::default()
+            },
+            "monochrome" => Self {
+                name: name.to_string(),
+                bar: String::new(),
+                error: "bold".to_string(),
+                echo: "bold".to_string(),
+                search_match: "underline".to_string(),
+                last_command: "inverse".to_string(),
+                timestamp: "dim".to_string(),
+                ..Self::default()
+            },
+            "ascii" => Self {
+                name: name.to_string(),
+                bar_glyph: '-',
+                top_bar_glyph: '=',
+                tag_symbol: '|',
+                ..Self::default()
+            },
+            _ => {
+                let path = Self::themes_dir().join(format!("{name}.ron"));
+                if !path.exists() {
+                    bail!("Unknown theme: {name}");
+                }
+                let mut theme: Self = ron::from_str(&fs::read_to_string(path)?)?;
+                theme.name = name.to_string();
+                theme
+            }
+        };
+        theme.validate()?;
+        Ok(theme)
+    }
+
+    /// Lists the built-in themes followed by the user themes.
+    pub fn available() -> Vec<String> {
+        let mut user_themes: Vec<String> = fs::read_dir(Self::themes_dir())
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+                    .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+                    .filter(|name| !BUILTIN_THEMES.contains(&name.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        user_themes.sort();
+        BUILTIN_THEMES
+            .iter()
+            .map(|name| name.to_string())
+            .chain(user_themes)
+            .collect()
+    }
+
+    fn themes_dir() -> PathBuf {
+        crate::CONFIG_DIR.join("themes")
+    }
+
+    fn validate(&self) -> Result<()> {
+        for style in [
+            &self.bar,
+            &self.error,
+            &self.info,
+            &self.echo,
+            &self.search_match,
+            &self.selection,
+            &self.last_command,
+            &self.timestamp,
+        ] {
+            ansi_style(style)?;
+        }
+        if StrftimeItems::new(&self.timestamp_format).any(|item| item == Item::Error) {
+            bail!("Invalid timestamp format: {}", self.timestamp_format);
+        }
+        Ok(())
+    }
+
+    /// The escape sequence for one of the theme's styles.
+    pub fn escape(&self, style: &str) -> String {
+        ansi_style(style).unwrap_or_default()
+    }
+
+    /// One of the theme's styles, for restyling parts of a line of output.
+    pub fn style(&self, style: &str) -> Style {
+        let mut parsed = Style::default();
+        let escape = self.escape(style);
+        if let Some(params) = escape
+            .strip_prefix("\x1b[")
+            .and_then(|params| params.strip_suffix('m'))
+        {
+            let params: Vec<u16> = params
+                .split(';')
+                .filter_map(|param| param.parse().ok())
+                .collect();
+            parsed.apply(&params);
+        }
+        parsed
+    }
+
+    /// Formats a receive time for the timestamp gutter.
+    pub fn format_time(&self, time: &DateTime<Local>) -> String {
+        let mut output = String::new();
+        if write!(output, "{}", time.format(&self.timestamp_format)).is_err() {
+            output.clear();
+        }
+        output
+    }
+
+    /// Wraps content in one of the theme's styles.
+    pub fn paint(&self, style: &str, content: &str) -> String {
+        let style = self.escape(style);
+        if style.is_empty() {
+            content.to_string()
+        } else {
+            format!("{style}{content}\x1b[0m")
+        }
+    }
+}
+
+/// Converts a theme style into the ANSI escape sequence producing it.
+fn ansi_style(style: &str) -> Result<String> {
+    let mut codes: Vec<String> = vec![];
+    let mut background = false;
+    for token in style.split_whitespace() {
+        let token = token.to_lowercase();
+        let code = match token.as_str() {
+            "on" => {
+                background = true;
+                continue;
+            }
+            "none" | "reset" => continue,
+            "bold" => "1".to_string(),
+            "faint" | "dim" => "2".to_string(),
+            "italic" => "3".to_string(),
+            "underline" => "4".to_string(),
+            "inverse" | "reverse" => "7".to_string(),
+            color => color_code(color, background)?,
+        };
+        codes.push(code);
+    }
+    if codes.is_empty() {
+        Ok(String::new())
+    } else {
+        Ok(format!("\x1b[{}m", codes.join(";")))
+    }
+}
+
+fn color_code(color: &str, background: bool) -> Result<String> {
+    const COLORS: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    let (base, extended) = if background { (40, 48) } else { (30, 38) };
+
+    let (light, name) = match color.strip_prefix("light") {
+        Some(name) => (true, name),
+        None => (false, color),
+    };
+    if let Some(index) = COLORS.iter().position(|c| *c == name) {
+        let offset = if light { 60 } else { 0 };
+        return Ok((base + offset + index).to_string());
+    }
+
+    if let Ok(index) = color.parse::<u8>() {
+        return Ok(format!("{extended};5;{index}"));
+    }
+
+    if let Some(hex) = color.strip_prefix('#') {
+        if hex.len() == 6 {
+            if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+                let (r, g, b) = (rgb >> 16, (rgb >> 8) & 0xff, rgb & 0xff);
+                return Ok(format!("{extended};2;{r};{g};{b}"));
+            }
+        }
+    }
+
+    bail!("Invalid theme color: {color}")
+}
+
+#[cfg(test)]
+mod theme_test {
+    use super::{ansi_style, Theme, BUILTIN_THEMES};
+    use crate::model::{Color, Style};
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn test_ansi_style() {
+        assert_eq!(ansi_style("").unwrap(), "");
+        assert_eq!(ansi_style("green").unwrap(), "\x1b[32m");
+        assert_eq!(ansi_style("lightwhite on blue").unwrap(), "\x1b[97;44m");
+        assert_eq!(ansi_style("bold Red").unwrap(), "\x1b[1;31m");
+        assert_eq!(ansi_style("inverse").unwrap(), "\x1b[7m");
+        assert_eq!(
+            ansi_style("208 on #102030").unwrap(),
+            "\x1b[38;5;208;48;2;16;32;48m"
+        );
+        assert!(ansi_style("purple").is_err());
+        assert!(ansi_style("#12345").is_err());
+    }
+
+    #[test]
+    fn test_builtin_themes() {
+        for name in BUILTIN_THEMES {
+            let theme = Theme::named(name).unwrap();
+            assert_eq!(theme.name, name);
+        }
+        assert!(Theme::named("no-such-theme").is_err());
+        assert_eq!(Theme::available()[..4], BUILTIN_THEMES);
+    }
+
+    #[test]
+    fn test_paint() {
+        let theme = Theme::default();
+        assert_eq!(theme.paint(&theme.bar, "bar"), "\x1b[32mbar\x1b[0m");
+        assert_eq!(theme.paint(&theme.info, "info"), "info");
+    }
+
+    #[test]
+    fn test_style() {
+        let theme = Theme::default();
+        assert_eq!(
+            theme.style(&theme.search_match),
+            Style {
+                fg: Some(Color::Indexed(15)),
+                bg: Some(Color::Indexed(4)),
+                ..Style::default()
+            }
+        );
+        assert_eq!(
+            theme.style("underline 208"),
+            Style {
+                fg: Some(Color::Indexed(208)),
+                underline: true,
+                ..Style::default()
+            }
+        );
+        assert_eq!(theme.style(""), Style::default());
+    }
+
+    #[test]
+    fn test_partial_theme_file() {
+        let theme: Theme = ron::from_str("(name: \"mine\", bar: \"blue\")").unwrap();
+        assert_eq!(theme.bar, "blue");
+        assert_eq!(theme.bar_glyph, Theme::default().bar_glyph);
+    }
+
+    #[test]
+    fn test_timestamp_format() {
+        let time = Local.with_ymd_and_hms(2024, 3, 1, 14, 5, 9).unwrap();
+        let mut theme = Theme::default();
+        assert_eq!(theme.format_time(&time), "14:05:09");
+        theme.timestamp_format = "%d/%m %H:%M".to_string();
+        assert_eq!(theme.format_time(&time), "01/03 14:05");
+        assert!(theme.validate().is_ok());
+        theme.timestamp_format = "%Q".to_string();
+        assert!(theme.validate().is_err());
+        assert_eq!(theme.format_time(&time), "");
+    }
+}
@@ -0,0 +1,349 @@
+use crate::io::SaveData;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, ops::Range, path::PathBuf};
+use vte::{Params, Parser, Perform};
+
+use super::{render_spans, Line, Span, Style};
+
+/// Which worlds draw screens in the VT pane and how big the pane is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VtSettings {
+    /// Worlds whose screen drawing output goes to the VT pane
+    pub worlds: BTreeSet<String>,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl Default for VtSettings {
+    fn default() -> Self {
+        Self {
+            worlds: BTreeSet::new(),
+            cols: 80,
+            rows: 24,
+        }
+    }
+}
+
+impl VtSettings {
+    /// The size of the VT pane while connected to `world`, if it has one.
+    pub fn pane_for(&self, world: Option<&str>) -> Option<(u16, u16)> {
+        world
+            .filter(|world| self.worlds.contains(*world))
+            .map(|_| (self.cols, self.rows))
+    }
+}
+
+impl SaveData for VtSettings {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("vt.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    c: char,
+    style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            c: ' ',
+            style: Style::default(),
+        }
+    }
+}
+
+/// The cells of the grid and the state of the cursor drawing them.
+struct Screen {
+    cols: usize,
+    rows: usize,
+    cells: Vec<Vec<Cell>>,
+    row: usize,
+    col: usize,
+    saved: (usize, usize),
+    style: Style,
+}
+
+impl Screen {
+    fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            cols,
+            rows,
+            cells: vec![vec![Cell::default(); cols]; rows],
+            row: 0,
+            col: 0,
+            saved: (0, 0),
+            style: Style::default(),
+        }
+    }
+
+    fn line_feed(&mut self) {
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            self.cells.remove(0);
+            self.cells.push(vec![Cell::default(); self.cols]);
+        }
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.rows - 1);
+        self.col = col.min(self.cols - 1);
+    }
+
+    fn erase(&mut self, row: usize, cols: impl Iterator<Item = usize>) {
+        for col in cols {
+            self.cells[row][col] = Cell::default();
+        }
+    }
+
+    /// ED, erasing below the cursor, above it or everything.
+    fn erase_display(&mut self, mode: u16) {
+        let (row, col) = (self.row, self.col);
+        match mode {
+            0 => {
+                self.erase(row, col..self.cols);
+                for row in row + 1..self.rows {
+                    self.erase(row, 0..self.cols);
+                }
+            }
+            1 => {
+                for row in 0..row {
+                    self.erase(row, 0..self.cols);
+                }
+                self.erase(row, 0..(col + 1).min(self.cols));
+            }
+            _ => {
+                for row in 0..self.rows {
+                    self.erase(row, 0..self.cols);
+                }
+            }
+        }
+    }
+
+    /// EL, erasing right of the cursor, left of it or the whole row.
+    fn erase_line(&mut self, mode: u16) {
+        let (row, col) = (self.row, self.col);
+        match mode {
+            0 => self.erase(row, col..self.cols),
+            1 => self.erase(row, 0..(col + 1).min(self.cols)),
+            _ => self.erase(row, 0..self.cols),
+        }
+    }
+}
+
+impl Perform for Screen {
+    fn print(&mut self, c: char) {
+        if self.col >= self.cols {
+            self.col = 0;
+            self.line_feed();
+        }
+        self.cells[self.row][self.col] = Cell {
+            c,
+            style: self.style,
+        };
+        self.col += 1;
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\r' => self.col = 0,
+            b'\n' => self.line_feed(),
+            0x08 => self.col = self.col.saturating_sub(1),
+            b'\t' => self.col = ((self.col / 8 + 1) * 8).min(self.cols - 1),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _: bool, action: char) {
+        // Private modes like `?25l` don't affect the grid
+        if !intermediates.is_empty() {
+            return;
+        }
+        let param = |index: usize, default: u16| {
+            params
+                .iter()
+                .nth(index)
+                .and_then(|param| param.first().copied())
+                .filter(|value| *value != 0)
+                .unwrap_or(default)
+        };
+        let n = param(0, 1) as usize;
+        let (row, col) = (self.row, self.col);
+        match action {
+            'A' => self.move_to(row.saturating_sub(n), col),
+            'B' | 'e' => self.move_to(row + n, col),
+            'C' | 'a' => self.move_to(row, col + n),
+            'D' => self.move_to(row, col.saturating_sub(n)),
+            'E' => self.move_to(row + n, 0),
+            'F' => self.move_to(row.saturating_sub(n), 0),
+            'G' | '`' => self.move_to(row, n - 1),
+            'd' => self.move_to(n - 1, col),
+            'H' | 'f' => self.move_to(n - 1, param(1, 1) as usize - 1),
+            'J' => self.erase_display(param(0, 0)),
+            'K' => self.erase_line(param(0, 0)),
+            'm' => {
+                let params: Vec<u16> = params.iter().flatten().copied().collect();
+                self.style.apply(&params);
+            }
+            's' => self.saved = (row, col),
+            'u' => self.move_to(self.saved.0, self.saved.1),
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _: bool, byte: u8) {
+        match (intermediates, byte) {
+            ([], b'7') => self.saved = (self.row, self.col),
+            ([], b'8') => self.move_to(self.saved.0, self.saved.1),
+            _ => {}
+        }
+    }
+}
+
+/// A small virtual terminal that output drawing a screen with cursor
+/// movement, like maps and menus, is played into.
+pub struct VtGrid {
+    parser: Parser,
+    screen: Screen,
+}
+
+impl VtGrid {
+    pub fn new(cols: u16, rows: u16) -> Self {
+        Self {
+            parser: Parser::new(),
+            screen: Screen::new(cols.max(1) as usize, rows.max(1) as usize),
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.screen.cols as u16, self.screen.rows as u16)
+    }
+
+    /// Draws a line of mud output, moving to the start of the next row
+    /// after it.
+    pub fn feed(&mut self, line: &Line) {
+        // ED sequences are taken out of lines, leaving only this flag
+        if line.flags.screen_clear {
+            self.screen.erase_display(2);
+        }
+        self.parser
+            .advance(&mut self.screen, line.line().as_bytes());
+        self.parser.advance(&mut self.screen, b"\r\n");
+    }
+
+    /// The `count` rows to show in a pane too small for the whole grid,
+    /// keeping the cursor in the middle where possible.
+    pub fn visible_rows(&self, count: usize) -> Range<usize> {
+        let count = count.min(self.screen.rows);
+        let start = self
+            .screen
+            .row
+            .saturating_sub(count / 2)
+            .min(self.screen.rows - count);
+        start..start + count
+    }
+
+    /// The text of a row with its colors, cut to `width` columns.
+    pub fn render_row(&self, row: usize, width: usize) -> String {
+        let mut spans: Vec<Span> = vec![];
+        for cell in self.screen.cells[row].iter().take(width) {
+            match spans.last_mut() {
+                Some(span) if span.style == cell.style => span.text.push(cell.c),
+                _ => spans.push(Span {
+                    text: cell.c.to_string(),
+                    style: cell.style,
+                }),
+            }
+        }
+        render_spans(&spans)
+    }
+}
+
+#[cfg(test)]
+mod vt_test {
+    use super::{VtGrid, VtSettings};
+    use crate::model::Line;
+
+    fn rows(grid: &VtGrid) -> Vec<String> {
+        let (cols, rows) = grid.size();
+        (0..rows as usize)
+            .map(|row| grid.render_row(row, cols as usize))
+            .collect()
+    }
+
+    #[test]
+    fn test_cursor_positioning() {
+        let mut grid = VtGrid::new(6, 4);
+        grid.feed(&Line::from("\x1b[2;3Hab\x1b[1;1Hx"));
+        assert_eq!(rows(&grid), vec!["x     ", "  ab  ", "      ", "      "]);
+        grid.feed(&Line::from("\x1b[3;6Hz\x1b[3;1H\x1b[Cy"));
+        assert_eq!(rows(&grid), vec!["x     ", "  ab  ", " y   z", "      "]);
+    }
+
+    #[test]
+    fn test_erase() {
+        let mut grid = VtGrid::new(4, 3);
+        grid.feed(&Line::from("abcd"));
+        grid.feed(&Line::from("efgh\x1b[1;3H\x1b[K"));
+        assert_eq!(rows(&grid), vec!["ab  ", "efgh", "    "]);
+        grid.feed(&Line::from("\x1b[2;2H\x1b[1K"));
+        assert_eq!(rows(&grid), vec!["ab  ", "  gh", "    "]);
+        grid.feed(&Line::from("\x1b[2J\x1b[Hnew"));
+        assert_eq!(rows(&grid), vec!["new ", "    ", "    "]);
+    }
+
+    #[test]
+    fn test_erase_from_last_column() {
+        let mut grid = VtGrid::new(4, 3);
+        grid.feed(&Line::from("abcd\x1b[1K"));
+        assert_eq!(rows(&grid), vec!["    ", "    ", "    "]);
+        // ED sequences are filtered out of lines, so this one is fed directly
+        grid.parser.advance(&mut grid.screen, b"efgh\x1b[1J");
+        assert_eq!(rows(&grid), vec!["    ", "    ", "    "]);
+    }
+
+    #[test]
+    fn test_visible_rows() {
+        let mut grid = VtGrid::new(4, 10);
+        assert_eq!(grid.visible_rows(4), 0..4);
+        grid.feed(&Line::from("\x1b[6;1H"));
+        assert_eq!(grid.visible_rows(4), 4..8);
+        grid.feed(&Line::from("\x1b[10;1H"));
+        assert_eq!(grid.visible_rows(4), 6..10);
+        assert_eq!(grid.visible_rows(20), 0..10);
+    }
+
+    #[test]
+    fn test_scrolls_and_wraps() {
+        let mut grid = VtGrid::new(3, 2);
+        grid.feed(&Line::from("one"));
+        grid.feed(&Line::from("two"));
+        assert_eq!(rows(&grid), vec!["two", "   "]);
+        grid.feed(&Line::from("abcde"));
+        assert_eq!(rows(&grid), vec!["de ", "   "]);
+    }
+
+    #[test]
+    fn test_colors() {
+        let mut grid = VtGrid::new(4, 2);
+        grid.feed(&Line::from("\x1b[H\x1b[31mab\x1b[0mc"));
+        assert_eq!(grid.render_row(0, 4), "\x1b[0;31mab\x1b[0mc ");
+        assert_eq!(grid.render_row(0, 1), "\x1b[0;31ma\x1b[0m");
+    }
+
+    #[test]
+    fn test_pane_for() {
+        let mut settings = VtSettings::default();
+        settings.worlds.insert("arctic".to_string());
+        assert_eq!(settings.pane_for(Some("arctic")), Some((80, 24)));
+        assert_eq!(settings.pane_for(Some("other")), None);
+        assert_eq!(settings.pane_for(None), None);
+    }
+}
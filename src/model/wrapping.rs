@@ -0,0 +1,84 @@
+use crate::io::SaveData;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub const OUTPUT_WINDOW: &str = "output";
+pub const READER_WINDOW: &str = "reader";
+
+/// How lines are wrapped in an output window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WrapOptions {
+    /// Break lines at whitespace where possible instead of at the last column
+    pub words: bool,
+    /// Columns continuation rows are indented by
+    pub indent: usize,
+}
+
+impl Default for WrapOptions {
+    fn default() -> Self {
+        Self {
+            words: true,
+            indent: 0,
+        }
+    }
+}
+
+/// Wrap options for each of the output windows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Wrapping {
+    /// The output area of the regular screen
+    pub output: WrapOptions,
+    /// The screen reader friendly screen
+    pub reader: WrapOptions,
+}
+
+impl SaveData for Wrapping {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("wrapping.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+impl Wrapping {
+    pub fn get(&self, window: &str) -> Result<WrapOptions> {
+        match window {
+            OUTPUT_WINDOW => Ok(self.output),
+            READER_WINDOW => Ok(self.reader),
+            _ => bail!("Unknown output window: {window}"),
+        }
+    }
+
+    pub fn set(&mut self, window: &str, options: WrapOptions) -> Result<()> {
+        match window {
+            OUTPUT_WINDOW => self.output = options,
+            READER_WINDOW => self.reader = options,
+            _ => bail!("Unknown output window: {window}"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod wrapping_test {
+    use super::{WrapOptions, Wrapping, OUTPUT_WINDOW, READER_WINDOW};
+
+    #[test]
+    fn test_windows() {
+        let mut wrapping = Wrapping::default();
+        let options = WrapOptions {
+            words: false,
+            indent: 2,
+        };
+        wrapping.set(READER_WINDOW, options).unwrap();
+        assert_eq!(wrapping.get(READER_WINDOW).unwrap(), options);
+        assert_eq!(wrapping.get(OUTPUT_WINDOW).unwrap(), WrapOptions::default());
+        assert!(wrapping.get("nowhere").is_err());
+        assert!(wrapping.set("nowhere", options).is_err());
+    }
+}
@@ -116,6 +116,9 @@ impl TelnetHandler {
                             self.main_writer
                                 .send(Event::AddTag("GA".to_string()))
                                 .unwrap();
+                        } else if neg.option == opt::ECHO && neg.command == cmd::WILL {
+                            // The server echoes input itself, eg. for a password
+                            self.main_writer.send(Event::SetSecretInput(true)).unwrap();
                         }
                         self.main_writer
                             .send(Event::ProtoEnabled(neg.option))
@@ -132,6 +135,8 @@ impl TelnetHandler {
                             self.main_writer
                                 .send(Event::RemoveTag("GA".to_string()))
                                 .unwrap();
+                        } else if neg.option == opt::ECHO {
+                            self.main_writer.send(Event::SetSecretInput(false)).unwrap();
                         }
                         self.main_writer
                             .send(Event::ProtoDisabled(neg.option))
@@ -258,6 +263,20 @@ mod test {
         assert_eq!(th.mode, TelnetMode::UnterminatedPrompt);
     }
 
+    #[test]
+    fn test_echo_negotiation() {
+        let (session, reader, _timer_reader) = build_session();
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(&[cmd::IAC, cmd::WILL, opt::ECHO]);
+        let events: Vec<Event> = reader.try_iter().collect();
+        assert!(events.contains(&Event::SetSecretInput(true)));
+
+        th.parse(&[cmd::IAC, cmd::WONT, opt::ECHO]);
+        let events: Vec<Event> = reader.try_iter().collect();
+        assert!(events.contains(&Event::SetSecretInput(false)));
+    }
+
     #[test]
     fn test_parse_plain_text() {
         let (session, reader, _timer_reader) = build_session();
@@ -8,8 +8,9 @@ use std::sync::{atomic::AtomicBool, mpsc::Sender, Arc, Mutex};
 
 use crate::{
     event::QuitMethod,
-    io::{LogWriter, Logger},
+    io::{LogOptions, LogWriter, Logger, Replay, ReplayEntry, SaveData, Scrollback},
     lua::{LuaScript, LuaScriptBuilder},
+    model::{BellSettings, ColorDepth, ColorSettings, Palette, Palettes},
     net::MudConnection,
     net::BUFFER_SIZE,
     net::{OutputBuffer, TelnetMode},
@@ -33,6 +34,15 @@ pub struct Session {
     pub prompt_input: Arc<Mutex<String>>,
     pub lua_script: Arc<Mutex<LuaScript>>,
     pub logger: Arc<Mutex<dyn LogWriter + Send>>,
+    pub scrollback: Arc<Mutex<Scrollback>>,
+    pub replay: Arc<Mutex<Option<Replay>>>,
+    pub bell: Arc<Mutex<BellSettings>>,
+    /// The color depth output is converted to before it's drawn
+    pub color_depth: Arc<Mutex<ColorDepth>>,
+    /// The palette of the current world
+    pub palette: Arc<Mutex<Palette>>,
+    /// The size of the VT pane when screen drawing is on for the current world
+    pub vt: Arc<Mutex<Option<(u16, u16)>>>,
     pub tts_ctrl: Arc<Mutex<TTSController>>,
     pub command_buffer: Arc<Mutex<CommandBuffer>>,
     pub echo_input: Arc<AtomicBool>,
@@ -67,7 +77,11 @@ impl Session {
         }
         if connected {
             self.main_writer
-                .send(Event::StartLogging(host.to_string(), false))
+                .send(Event::StartLogging(
+                    host.to_string(),
+                    false,
+                    LogOptions::default(),
+                ))
                 .unwrap();
             self.main_writer.send(Event::Connected(conn_id)).unwrap();
         }
@@ -87,6 +101,9 @@ impl Session {
             };
 
             self.stop_logging();
+            if let Ok(mut scrollback) = self.scrollback.lock() {
+                scrollback.close();
+            }
         }
     }
 
@@ -103,6 +120,9 @@ impl Session {
                 };
 
                 self.stop_logging();
+                if let Ok(mut scrollback) = self.scrollback.lock() {
+                    scrollback.close();
+                }
             }
         }
     }
@@ -117,6 +137,15 @@ impl Session {
         connection.host.clone()
     }
 
+    /// The name of the server connected to, or its host when it isn't saved.
+    pub fn world(&self) -> Option<String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .name
+            .clone()
+            .or_else(|| Some(connection.host.clone()).filter(|host| !host.is_empty()))
+    }
+
     pub fn port(&self) -> u16 {
         let connection = self.connection.lock().unwrap();
         connection.port
@@ -135,12 +164,12 @@ impl Session {
         }
     }
 
-    pub fn start_logging(&self, host: &str) {
+    pub fn start_logging(&self, host: &str, options: &LogOptions) {
         if let Ok(mut logger) = self.logger.lock() {
             self.main_writer
                 .send(Event::Info(format!("Started logging for: {host}")))
                 .unwrap();
-            logger.start_logging(host).ok();
+            logger.start_logging(host, options).ok();
         }
     }
 
@@ -153,6 +182,45 @@ impl Session {
         }
     }
 
+    pub fn start_replay(&self, entries: Vec<ReplayEntry>, speed: f64, triggers: bool) {
+        if let Ok(mut replay) = self.replay.lock() {
+            if let Some(replay) = replay.take() {
+                replay.stop();
+            }
+            *replay = Some(Replay::start(
+                entries,
+                speed,
+                triggers,
+                self.main_writer.clone(),
+            ));
+        }
+    }
+
+    pub fn stop_replay(&self) {
+        if let Ok(mut replay) = self.replay.lock() {
+            if let Some(replay) = replay.take() {
+                replay.stop();
+            }
+        }
+    }
+
+    /// Stops running the triggers once a replay has played all its lines.
+    /// The replay stays on screen until it's stopped.
+    pub fn finish_replay(&self) {
+        if let Ok(mut replay) = self.replay.lock() {
+            if let Some(replay) = replay.as_mut() {
+                replay.triggers = false;
+            }
+        }
+    }
+
+    /// Whether a replay runs the triggers on the lines it plays.
+    pub fn replay_triggers(&self) -> bool {
+        self.replay
+            .lock()
+            .is_ok_and(|replay| replay.as_ref().is_some_and(|replay| replay.triggers))
+    }
+
     #[cfg(test)]
     pub fn send_event(&mut self, event: Event) {
         self.main_writer.send(event).unwrap();
@@ -279,6 +347,12 @@ impl SessionBuilder {
             prompt_input: Arc::new(Mutex::new(String::new())),
             lua_script: lua_script.clone(),
             logger: Arc::new(Mutex::new(Logger::default())),
+            scrollback: Arc::new(Mutex::new(Scrollback::default())),
+            replay: Arc::new(Mutex::new(None)),
+            bell: Arc::new(Mutex::new(BellSettings::load())),
+            color_depth: Arc::new(Mutex::new(ColorSettings::load().depth())),
+            palette: Arc::new(Mutex::new(Palettes::load().active(None))),
+            vt: Arc::new(Mutex::new(None)),
             tts_ctrl: tts_ctrl.clone(),
             command_buffer: Arc::new(Mutex::new(CommandBuffer::new(
                 tts_ctrl,
@@ -346,13 +420,13 @@ mod session_test {
         let mut logger = MockLogWriter::new();
         logger
             .expect_start_logging()
-            .with(eq("mysteryhost"))
+            .with(eq("mysteryhost"), eq(LogOptions::default()))
             .times(1)
-            .returning(|_| Ok(()));
+            .returning(|_, _| Ok(()));
         logger.expect_stop_logging().times(1).returning(|| Ok(()));
         session.logger = Arc::new(Mutex::new(logger));
 
-        session.start_logging("mysteryhost");
+        session.start_logging("mysteryhost", &LogOptions::default());
         assert_eq!(
             reader.recv(),
             Ok(Event::Info("Started logging for: mysteryhost".to_string()))
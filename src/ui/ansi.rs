@@ -1,3 +1,4 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
 use std::fmt;
 
 pub struct ScrollRegion(pub u16, pub u16);
@@ -21,6 +22,82 @@ impl fmt::Display for DisableOriginMode {
     }
 }
 
+/// OSC 52 sequence asking the terminal to put the text on the clipboard.
+pub struct SetClipboard<'a>(pub &'a str);
+impl fmt::Display for SetClipboard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b]52;c;{}\x07", STANDARD.encode(self.0))
+    }
+}
+
+/// DECSCNM, showing the whole screen in reverse video while enabled.
+pub struct ReverseVideo(pub bool);
+impl fmt::Display for ReverseVideo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b[?5{}", if self.0 { 'h' } else { 'l' })
+    }
+}
+
+/// Drops control characters that would end an OSC sequence early.
+fn osc_text(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// OSC 2 sequence setting the terminal window title.
+pub struct SetTitle<'a>(pub &'a str);
+impl fmt::Display for SetTitle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b]2;{}\x07", osc_text(self.0))
+    }
+}
+
+/// How a desktop notification is raised.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyMethod {
+    /// OSC 777, understood by eg. foot, kitty, urxvt and WezTerm
+    #[default]
+    Osc777,
+    /// OSC 9, understood by eg. iTerm2, kitty and Windows Terminal
+    Osc9,
+    /// The terminal bell, for terminals without notifications
+    Bell,
+}
+
+impl NotifyMethod {
+    pub fn named(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "osc777" => Ok(Self::Osc777),
+            "osc9" => Ok(Self::Osc9),
+            "bell" => Ok(Self::Bell),
+            _ => anyhow::bail!("Unknown notify method: {name}, use osc777, osc9 or bell"),
+        }
+    }
+}
+
+/// Sequence raising a desktop notification.
+pub struct Notify<'a> {
+    pub title: &'a str,
+    pub body: &'a str,
+    pub method: NotifyMethod,
+}
+impl fmt::Display for Notify<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let title = osc_text(self.title);
+        let body = osc_text(self.body);
+        match self.method {
+            NotifyMethod::Osc777 => write!(
+                f,
+                "\x1b]777;notify;{};{}\x07",
+                title.replace(';', ","),
+                body
+            ),
+            NotifyMethod::Osc9 if title.is_empty() => write!(f, "\x1b]9;{body}\x07"),
+            NotifyMethod::Osc9 => write!(f, "\x1b]9;{title}: {body}\x07"),
+            NotifyMethod::Bell => write!(f, "\x07"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,4 +125,44 @@ mod tests {
         let disable = DisableOriginMode;
         assert_eq!(format!("{}", disable), "\x1b[?6l");
     }
+
+    #[test]
+    fn test_set_clipboard_display() {
+        let clip = SetClipboard("hello");
+        assert_eq!(format!("{}", clip), "\x1b]52;c;aGVsbG8=\x07");
+    }
+
+    #[test]
+    fn test_reverse_video_display() {
+        assert_eq!(format!("{}", ReverseVideo(true)), "\x1b[?5h");
+        assert_eq!(format!("{}", ReverseVideo(false)), "\x1b[?5l");
+    }
+
+    #[test]
+    fn test_set_title_display() {
+        assert_eq!(format!("{}", SetTitle("Arctic")), "\x1b]2;Arctic\x07");
+        assert_eq!(format!("{}", SetTitle("a\x07b\x1bc")), "\x1b]2;abc\x07");
+    }
+
+    #[test]
+    fn test_notify_display() {
+        let notify = |method| {
+            format!(
+                "{}",
+                Notify {
+                    title: "Tell; Bob",
+                    body: "hi\nthere",
+                    method,
+                }
+            )
+        };
+        assert_eq!(
+            notify(NotifyMethod::Osc777),
+            "\x1b]777;notify;Tell, Bob;hithere\x07"
+        );
+        assert_eq!(notify(NotifyMethod::Osc9), "\x1b]9;Tell; Bob: hithere\x07");
+        assert_eq!(notify(NotifyMethod::Bell), "\x07");
+        assert_eq!(NotifyMethod::named("osc9").unwrap(), NotifyMethod::Osc9);
+        assert!(NotifyMethod::named("popup").is_err());
+    }
 }
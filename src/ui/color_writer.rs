@@ -0,0 +1,163 @@
+use std::{
+    io::{Result, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::model::{recolor, ColorDepth, Palette};
+
+/// Longest unfinished escape sequence held back waiting for the rest of it.
+const MAX_PENDING: usize = 64;
+
+/// Terminal writer drawing colors as the user's palette says, converted to
+/// ones the terminal can show.
+pub struct ColorWriter {
+    inner: Box<dyn Write>,
+    depth: Arc<Mutex<ColorDepth>>,
+    palette: Arc<Mutex<Palette>>,
+    /// The start of an SGR sequence split across writes
+    pending: Vec<u8>,
+}
+
+impl ColorWriter {
+    pub fn new(
+        inner: Box<dyn Write>,
+        depth: Arc<Mutex<ColorDepth>>,
+        palette: Arc<Mutex<Palette>>,
+    ) -> Self {
+        Self {
+            inner,
+            depth,
+            palette,
+            pending: vec![],
+        }
+    }
+}
+
+/// Where an escape sequence at the end of `bytes` starts, if it could be the
+/// start of an SGR sequence.
+fn unfinished_sgr(bytes: &[u8]) -> Option<usize> {
+    let start = bytes.iter().rposition(|b| *b == 0x1b)?;
+    match &bytes[start + 1..] {
+        [] => Some(start),
+        [b'[', params @ ..]
+            if params
+                .iter()
+                .all(|b| b.is_ascii_digit() || b";:".contains(b)) =>
+        {
+            Some(start)
+        }
+        _ => None,
+    }
+}
+
+impl Write for ColorWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let depth = *self.depth.lock().unwrap();
+        let palette = self.palette.lock().unwrap();
+        if depth == ColorDepth::TrueColor && palette.is_empty() && self.pending.is_empty() {
+            self.inner.write_all(buf)?;
+            return Ok(buf.len());
+        }
+        self.pending.extend_from_slice(buf);
+        let end = match unfinished_sgr(&self.pending) {
+            Some(start) if self.pending.len() - start < MAX_PENDING => start,
+            _ => self.pending.len(),
+        };
+        let ready: Vec<u8> = self.pending.drain(..end).collect();
+        self.inner.write_all(&recolor(&ready, &palette, depth))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // Whatever was held back has to go out now, finished or not
+        let pending: Vec<u8> = self.pending.drain(..).collect();
+        self.inner.write_all(&pending)?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod color_writer_test {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{unfinished_sgr, ColorWriter};
+    use crate::model::{ColorDepth, Palette};
+
+    /// Collects what's written for the test to check.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_unfinished_sgr() {
+        assert_eq!(unfinished_sgr(b"abc"), None);
+        assert_eq!(unfinished_sgr(b"abc\x1b"), Some(3));
+        assert_eq!(unfinished_sgr(b"abc\x1b[38;2"), Some(3));
+        assert_eq!(unfinished_sgr(b"abc\x1b[1m"), None);
+        assert_eq!(unfinished_sgr(b"abc\x1b]0;title"), None);
+    }
+
+    #[test]
+    fn test_downsamples_output() {
+        let output = Output::default();
+        let depth = Arc::new(Mutex::new(ColorDepth::Basic));
+        let mut writer = ColorWriter::new(Box::new(output.clone()), depth.clone(), Arc::default());
+        write!(writer, "\x1b[38;2;255;0;0mred").unwrap();
+        assert_eq!(output.text(), "\x1b[91mred");
+
+        *depth.lock().unwrap() = ColorDepth::TrueColor;
+        write!(writer, "\x1b[38;2;255;0;0mred").unwrap();
+        assert_eq!(output.text(), "\x1b[91mred\x1b[38;2;255;0;0mred");
+    }
+
+    #[test]
+    fn test_split_sequences() {
+        let output = Output::default();
+        let depth = Arc::new(Mutex::new(ColorDepth::Indexed));
+        let mut writer = ColorWriter::new(Box::new(output.clone()), depth, Arc::default());
+        writer.write_all(b"a\x1b[48;2;").unwrap();
+        assert_eq!(output.text(), "a");
+        writer.write_all(b"0;0;255mb\x1b").unwrap();
+        assert_eq!(output.text(), "a\x1b[48;5;21mb");
+        writer.write_all(b"[2Jc").unwrap();
+        assert_eq!(output.text(), "a\x1b[48;5;21mb\x1b[2Jc");
+        writer.write_all(b"d\x1b[1").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(output.text(), "a\x1b[48;5;21mb\x1b[2Jcd\x1b[1");
+    }
+
+    #[test]
+    fn test_palette() {
+        let output = Output::default();
+        let depth = Arc::new(Mutex::new(ColorDepth::TrueColor));
+        let palette = Arc::new(Mutex::new(Palette::default()));
+        let mut writer = ColorWriter::new(Box::new(output.clone()), depth, palette.clone());
+        write!(writer, "\x1b[34mblue").unwrap();
+        palette
+            .lock()
+            .unwrap()
+            .remap("blue", Some("bblue"), false)
+            .unwrap();
+        write!(writer, "\x1b[34mblue").unwrap();
+        assert_eq!(output.text(), "\x1b[34mblue\x1b[94mblue");
+    }
+}
@@ -1,20 +1,28 @@
 use crate::event::QuitMethod;
-use crate::model::{Completions, Line, PromptMask, Servers};
+use crate::model::{CompletionMenu, Completions, InputSettings, Line, PromptMask, Servers, Theme};
+use crate::ui::command_search::CommandSearch;
+use crate::ui::ScrollMotion;
 use crate::{event::Event, tts::TTSController};
 use crate::{lua::LuaScript, lua::UiEvent, session::Session, SaveData};
-use log::debug;
+use log::{debug, error};
 use rs_complete::CompletionTree;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
 use std::{
     io::stdin,
     sync::{mpsc::Sender, Arc, Mutex},
+    time::Duration,
 };
 use std::{mem, thread};
 use termion::{event::Key, input::TermRead};
 
+const UNDO_LIMIT: usize = 100;
+const KILL_RING_SIZE: usize = 16;
+
 #[derive(Default)]
 struct CompletionStepData {
     options: Vec<String>,
+    descriptions: Vec<Option<String>>,
     index: usize,
     base: String,
 }
@@ -24,16 +32,36 @@ impl CompletionStepData {
         self.options.is_empty()
     }
 
-    fn set_options(&mut self, base: &str, options: Vec<String>) {
+    fn set_options(&mut self, base: &str, options: Vec<String>, descriptions: Vec<Option<String>>) {
         self.options = options;
+        self.descriptions = descriptions;
         self.base = base.to_string();
     }
 
     fn clear(&mut self) {
         self.options.clear();
+        self.descriptions.clear();
         self.index = 0;
     }
 
+    /// Builds the menu of candidates, there is no menu unless there is more
+    /// than one candidate to choose from.
+    fn menu(&self) -> Option<CompletionMenu> {
+        if self.options.len() > 1 {
+            Some(CompletionMenu {
+                options: self
+                    .options
+                    .iter()
+                    .cloned()
+                    .zip(self.descriptions.iter().cloned())
+                    .collect(),
+                selected: self.index.checked_sub(1),
+            })
+        } else {
+            None
+        }
+    }
+
     fn next(&mut self) -> Option<&String> {
         if !self.is_empty() {
             let last_index = self.index;
@@ -55,6 +83,23 @@ pub struct CommandBuffer {
     prompt_mask: PromptMask,
     script: Arc<Mutex<LuaScript>>,
     tts_ctrl: Arc<Mutex<TTSController>>,
+    scroll_mode: bool,
+    pending_scroll_key: Option<char>,
+    multiline: bool,
+    undo_stack: Vec<(Vec<char>, usize)>,
+    redo_stack: Vec<(Vec<char>, usize)>,
+    last_insert_pos: Option<usize>,
+    kill_ring: VecDeque<String>,
+    last_yank: Option<(Range<usize>, usize)>,
+    command_history: Vec<String>,
+    search: Option<CommandSearch>,
+    completed: bool,
+    last_command_style: String,
+    /// The server echoes input itself, eg. while asking for a password, so
+    /// it's hidden and not remembered
+    secret: bool,
+    /// Time to wait between sending the lines of a multi-line input
+    send_delay: Duration,
 }
 
 impl CommandBuffer {
@@ -65,6 +110,7 @@ impl CommandBuffer {
     ) -> Self {
         let mut completion = CompletionTree::with_inclusions(&['/', '_']);
         completion.set_min_word_len(3);
+        let theme = Theme::load();
 
         Self {
             buffer: vec![],
@@ -76,6 +122,20 @@ impl CommandBuffer {
             prompt_mask: PromptMask::new(),
             script,
             tts_ctrl,
+            scroll_mode: false,
+            pending_scroll_key: None,
+            multiline: false,
+            undo_stack: vec![],
+            redo_stack: vec![],
+            last_insert_pos: None,
+            kill_ring: VecDeque::new(),
+            last_yank: None,
+            command_history: vec![],
+            search: None,
+            completed: false,
+            last_command_style: theme.escape(&theme.last_command),
+            secret: false,
+            send_delay: InputSettings::load().send_delay(),
         }
     }
 
@@ -84,7 +144,35 @@ impl CommandBuffer {
     }
 
     pub fn get_masked_buffer(&self) -> String {
-        self.prompt_mask.mask_buffer(&self.buffer)
+        if self.secret {
+            PromptMask::hide_buffer(&self.buffer)
+        } else {
+            self.prompt_mask.mask_buffer(&self.buffer)
+        }
+    }
+
+    /// The input as shown in the prompt and to scripts, hidden while it's
+    /// secret.
+    pub fn get_shown_buffer(&mut self) -> String {
+        if self.secret {
+            PromptMask::hide_buffer(&self.buffer)
+        } else {
+            self.get_buffer()
+        }
+    }
+
+    /// Puts commands saved in earlier sessions before the ones entered in
+    /// this one so history search covers both.
+    fn add_saved_history(&mut self, saved: Vec<String>) {
+        self.command_history.splice(0..0, saved);
+    }
+
+    pub fn set_send_delay(&mut self, delay: Duration) {
+        self.send_delay = delay;
+    }
+
+    pub fn set_secret(&mut self, secret: bool) {
+        self.secret = secret;
     }
 
     pub fn get_mask(&self) -> &PromptMask {
@@ -93,11 +181,15 @@ impl CommandBuffer {
 
     pub fn get_last_command_mask(&self) -> PromptMask {
         let mut mask = PromptMask::new();
-        mask.insert(0, "\x1b[44m".to_string()); // BG blue
+        mask.insert(0, self.last_command_style.clone());
         mask.insert(self.last_buffer.len() as i32, "\x1b[0m".to_string());
         mask
     }
 
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.last_command_style = theme.escape(&theme.last_command);
+    }
+
     pub fn enable_last_command(&mut self, enabled: bool) {
         self.last_command_enabled = enabled;
         if !enabled {
@@ -110,6 +202,10 @@ impl CommandBuffer {
     }
 
     fn submit(&mut self) -> String {
+        if self.secret {
+            return self.submit_secret();
+        }
+
         // If we have no buffer then swap in the last buffer
         if self.last_command_enabled && self.buffer.is_empty() {
             mem::swap(&mut self.last_buffer, &mut self.buffer);
@@ -118,7 +214,12 @@ impl CommandBuffer {
         // Insert history
         let cmd = if !self.buffer.is_empty() {
             let command = self.get_buffer();
-            self.completion_tree.insert(&command);
+            for line in command.lines().filter(|line| !line.is_empty()) {
+                self.completion_tree.insert(line);
+                if self.command_history.last().map(String::as_str) != Some(line) {
+                    self.command_history.push(line.to_string());
+                }
+            }
             command
         } else {
             String::new()
@@ -134,10 +235,147 @@ impl CommandBuffer {
 
         self.clear_mask();
         self.cursor_pos = 0;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.last_insert_pos = None;
+        self.last_yank = None;
 
         cmd
     }
 
+    /// Submits the input without keeping it in the history or as the last
+    /// command.
+    fn submit_secret(&mut self) -> String {
+        let cmd = self.get_buffer();
+        self.buffer.clear();
+        self.clear_mask();
+        self.cursor_pos = 0;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.last_insert_pos = None;
+        self.last_yank = None;
+        cmd
+    }
+
+    /// Stores the current state of the buffer so the next edit can be undone.
+    fn save_undo(&mut self) {
+        self.undo_stack.push((self.buffer.clone(), self.cursor_pos));
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+        self.last_insert_pos = None;
+        self.last_yank = None;
+    }
+
+    fn restore(&mut self, state: (Vec<char>, usize)) {
+        let (buffer, pos) = state;
+        self.buffer = buffer;
+        self.cursor_pos = pos.min(self.buffer.len());
+        self.last_insert_pos = None;
+        self.last_yank = None;
+        self.completion.clear();
+        self.clear_mask();
+    }
+
+    fn undo(&mut self) {
+        if let Some(state) = self.undo_stack.pop() {
+            self.redo_stack
+                .push((mem::take(&mut self.buffer), self.cursor_pos));
+            self.restore(state);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(state) = self.redo_stack.pop() {
+            self.undo_stack
+                .push((mem::take(&mut self.buffer), self.cursor_pos));
+            self.restore(state);
+        }
+    }
+
+    /// Removes `range` from the buffer, storing the removed text in the kill
+    /// ring.
+    fn kill(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.save_undo();
+        let killed: String = self.buffer.drain(range).collect();
+        self.kill_ring.push_front(killed);
+        self.kill_ring.truncate(KILL_RING_SIZE);
+        self.clear_mask();
+    }
+
+    /// Inserts the most recently killed text at the cursor.
+    fn yank(&mut self) {
+        if let Some(text) = self.kill_ring.front().cloned() {
+            self.save_undo();
+            let start = self.cursor_pos;
+            self.insert_str(&text);
+            self.last_yank = Some((start..self.cursor_pos, 0));
+        }
+    }
+
+    /// Replaces the text inserted by the previous yank with the next older
+    /// entry in the kill ring.
+    fn yank_pop(&mut self) {
+        if let Some((range, index)) = self.last_yank.take() {
+            if range.end != self.cursor_pos || self.kill_ring.len() < 2 {
+                self.last_yank = Some((range, index));
+                return;
+            }
+            let index = (index + 1) % self.kill_ring.len();
+            let text = self.kill_ring[index].clone();
+            self.buffer.drain(range.clone());
+            self.cursor_pos = range.start;
+            self.insert_str(&text);
+            self.last_yank = Some((range.start..self.cursor_pos, index));
+        }
+    }
+
+    fn start_search(&mut self) {
+        self.search = Some(CommandSearch::new(&self.command_history));
+    }
+
+    /// Ends the history search, replacing the input with the selected command
+    /// if `accept` is set.
+    fn end_search(&mut self, accept: bool) {
+        if let Some(search) = self.search.take() {
+            if let Some(command) = search.current(&self.command_history) {
+                if accept {
+                    let command = command.to_string();
+                    self.save_undo();
+                    self.set(command);
+                }
+            }
+        }
+    }
+
+    /// Returns the search prompt to display in place of the input along with
+    /// the cursor position within it.
+    fn search_prompt(&self) -> Option<(String, usize)> {
+        self.search.as_ref().map(|search| {
+            let current = search.current(&self.command_history);
+            let prefix = format!(
+                "({}reverse-i-search)`{}",
+                if current.is_none() { "failed " } else { "" },
+                search.query()
+            );
+            let pos = prefix.chars().count();
+            (format!("{prefix}': {}", current.unwrap_or_default()), pos)
+        })
+    }
+
+    fn insert_str(&mut self, text: &str) {
+        let chars: Vec<char> = text.chars().collect();
+        let len = chars.len();
+        self.buffer.splice(self.cursor_pos..self.cursor_pos, chars);
+        self.cursor_pos += len;
+        self.clear_mask();
+        self.completion.clear();
+    }
+
     fn step_left(&mut self) {
         if self.cursor_pos > 0 {
             self.cursor_pos -= 1;
@@ -154,6 +392,58 @@ impl CommandBuffer {
         }
     }
 
+    /// Returns the index of the first character on the line the cursor is on.
+    fn line_start(&self, pos: usize) -> usize {
+        self.buffer[..pos]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Returns the index of the line break ending the line at `pos`, or the
+    /// length of the buffer when it's the last line.
+    fn line_end(&self, pos: usize) -> usize {
+        self.buffer[pos..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(self.buffer.len(), |i| pos + i)
+    }
+
+    /// Moves the cursor to the same column on the previous line of a
+    /// multi-line input. Returns false if the cursor is on the first line.
+    fn step_line_up(&mut self) -> bool {
+        let start = self.line_start(self.cursor_pos);
+        if start == 0 {
+            return false;
+        }
+        let column = self.cursor_pos - start;
+        let prev_start = self.line_start(start - 1);
+        self.cursor_pos = (prev_start + column).min(start - 1);
+        true
+    }
+
+    /// Moves the cursor to the same column on the next line of a multi-line
+    /// input. Returns false if the cursor is on the last line.
+    fn step_line_down(&mut self) -> bool {
+        let end = self.line_end(self.cursor_pos);
+        if end == self.buffer.len() {
+            return false;
+        }
+        let column = self.cursor_pos - self.line_start(self.cursor_pos);
+        let next_end = self.line_end(end + 1);
+        self.cursor_pos = (end + 1 + column).min(next_end);
+        true
+    }
+
+    fn insert_newline(&mut self) {
+        self.push_key('\n');
+    }
+
+    fn toggle_multiline(&mut self) -> bool {
+        self.multiline = !self.multiline;
+        self.multiline
+    }
+
     fn move_to_start(&mut self) {
         self.cursor_pos = 0;
     }
@@ -182,17 +472,17 @@ impl CommandBuffer {
     }
 
     fn delete_to_end(&mut self) {
-        self.buffer.drain(self.cursor_pos..self.buffer.len());
-        self.clear_mask();
+        self.kill(self.cursor_pos..self.buffer.len());
     }
 
     fn delete_from_start(&mut self) {
-        self.buffer.drain(0..self.cursor_pos);
+        self.kill(0..self.cursor_pos);
         self.cursor_pos = 0;
     }
 
     fn delete_right(&mut self) {
         if self.cursor_pos < self.buffer.len() {
+            self.save_undo();
             self.buffer.remove(self.cursor_pos);
             self.clear_mask();
         }
@@ -201,24 +491,23 @@ impl CommandBuffer {
     fn delete_word_right(&mut self) {
         let origin = self.cursor_pos;
         self.step_word_right();
-        if origin != self.cursor_pos {
-            self.buffer.drain(origin..self.cursor_pos);
-            self.clear_mask();
-            self.cursor_pos = origin;
-        }
+        let end = self.cursor_pos;
+        self.cursor_pos = origin;
+        self.kill(origin..end);
     }
 
     fn delete_word_left(&mut self) {
         let origin = self.cursor_pos;
         self.step_word_left();
-        if origin != self.cursor_pos {
-            self.buffer.drain(self.cursor_pos..origin);
-            self.clear_mask();
-        }
+        let start = self.cursor_pos;
+        self.cursor_pos = origin;
+        self.kill(start..origin);
+        self.cursor_pos = start;
     }
 
     fn remove(&mut self) -> Option<char> {
         if self.cursor_pos > 0 {
+            self.save_undo();
             let removed = if self.cursor_pos < self.buffer.len() {
                 Some(self.buffer.remove(self.cursor_pos - 1))
             } else {
@@ -233,6 +522,10 @@ impl CommandBuffer {
     }
 
     fn push_key(&mut self, c: char) {
+        // Consecutively typed characters are undone together
+        if self.last_insert_pos != Some(self.cursor_pos) {
+            self.save_undo();
+        }
         if self.cursor_pos >= self.buffer.len() {
             self.buffer.push(c);
         } else {
@@ -241,6 +534,7 @@ impl CommandBuffer {
         self.clear_mask();
         self.completion.clear();
         self.step_right();
+        self.last_insert_pos = Some(self.cursor_pos);
     }
 
     fn tab_complete(&mut self) {
@@ -251,6 +545,7 @@ impl CommandBuffer {
         } else if self.buffer.len() > 1 {
             // Otherwise attempt a tab-complete
             if self.completion.is_empty() {
+                self.save_undo();
                 let mut completions = Completions::default();
                 let strbuf = self.get_buffer();
                 completions.merge(self.script.lock().unwrap().tab_complete(&strbuf));
@@ -260,16 +555,21 @@ impl CommandBuffer {
 
                 // Remove duplicates but preserve order of occurence
                 let mut occurences: HashSet<&String> = HashSet::new();
-                let completions = completions.iter().fold(vec![], |mut acc, word| {
+                let options = completions.iter().fold(vec![], |mut acc, word| {
                     if !occurences.contains(word) {
                         acc.push(word.clone());
                     }
                     occurences.insert(word);
                     acc
                 });
+                let descriptions = options
+                    .iter()
+                    .map(|word| completions.description(word).cloned())
+                    .collect();
 
-                self.completion.set_options(&strbuf, completions);
+                self.completion.set_options(&strbuf, options, descriptions);
             }
+            self.completed = true;
             if let Some(comp) = self.completion.next() {
                 self.tts_ctrl.lock().unwrap().speak(comp, true);
                 self.buffer = comp.chars().collect();
@@ -279,7 +579,18 @@ impl CommandBuffer {
         }
     }
 
+    /// Returns the completion menu to show if a completion was performed since
+    /// the last call.
+    fn take_completion_menu(&mut self) -> Option<Option<CompletionMenu>> {
+        if mem::take(&mut self.completed) {
+            Some(self.completion.menu())
+        } else {
+            None
+        }
+    }
+
     pub fn clear(&mut self) {
+        self.save_undo();
         self.buffer.clear();
         self.clear_mask();
         self.cursor_pos = self.buffer.len();
@@ -303,6 +614,26 @@ impl CommandBuffer {
     pub fn clear_mask(&mut self) {
         self.prompt_mask.clear();
     }
+
+    fn set_scroll_mode(&mut self, enabled: bool, writer: &Sender<Event>) {
+        if self.scroll_mode != enabled {
+            self.scroll_mode = enabled;
+            self.pending_scroll_key = None;
+            writer.send(Event::ScrollMode(enabled)).unwrap();
+        }
+    }
+
+    fn scroll_cursor(&mut self, motion: ScrollMotion, writer: &Sender<Event>) {
+        self.set_scroll_mode(true, writer);
+        writer.send(Event::ScrollCursor(motion)).unwrap();
+    }
+
+    /// Enters scroll mode waiting for the name of a mark, `m` to set it or
+    /// `'` to jump to it.
+    fn await_scroll_mark(&mut self, pending: char, writer: &Sender<Event>) {
+        self.set_scroll_mode(true, writer);
+        self.pending_scroll_key = Some(pending);
+    }
 }
 
 fn parse_mouse_event(event: termion::event::MouseEvent, writer: &Sender<Event>) {
@@ -314,6 +645,54 @@ fn parse_mouse_event(event: termion::event::MouseEvent, writer: &Sender<Event>)
     }
 }
 
+/// Submits the input buffer, sending each line of a multi-line input to the
+/// server in order. With a send delay set the lines after the first are sent
+/// from a separate thread, waiting the delay before each.
+fn submit_input(buffer: &mut CommandBuffer, writer: &Sender<Event>) {
+    let secret = buffer.secret;
+    let input = buffer.submit();
+    let mut lines: Vec<Line> = input
+        .split('\n')
+        .map(|input| {
+            let mut line = Line::from(input);
+            line.flags.source = Some("user".to_string());
+            line.flags.secret = secret;
+            line
+        })
+        .collect();
+    if lines.len() > 1 && lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    let delay = buffer.send_delay;
+    let delayed = if delay.is_zero() {
+        vec![]
+    } else {
+        lines.split_off(1)
+    };
+    for line in lines {
+        writer.send(Event::ServerInput(line)).unwrap();
+    }
+    if !delayed.is_empty() {
+        spawn_send_delay_thread(delayed, delay, writer.clone());
+    }
+}
+
+fn spawn_send_delay_thread(lines: Vec<Line>, delay: Duration, writer: Sender<Event>) {
+    let spawned = thread::Builder::new()
+        .name("send-delay-thread".to_string())
+        .spawn(move || {
+            for line in lines {
+                thread::sleep(delay);
+                if writer.send(Event::ServerInput(line)).is_err() {
+                    break;
+                }
+            }
+        });
+    if let Err(err) = spawned {
+        error!("Failed to spawn send delay thread: {err}");
+    }
+}
+
 fn parse_key_event(
     key: termion::event::Key,
     buffer: &mut CommandBuffer,
@@ -322,20 +701,21 @@ fn parse_key_event(
     script: &mut Arc<Mutex<LuaScript>>,
 ) {
     match key {
+        Key::Char('\n') if buffer.multiline => buffer.insert_newline(),
         Key::Char('\n') => {
-            let mut line = Line::from(buffer.submit());
-            line.flags.source = Some("user".to_string());
-            writer.send(Event::ServerInput(line)).unwrap();
+            submit_input(buffer, writer);
             if let Ok(mut script) = script.lock() {
                 script.set_prompt_content(String::new(), 0);
             }
         }
         Key::Char('\t') => buffer.tab_complete(),
         Key::Char(c) => {
-            tts_ctrl.lock().unwrap().key_press(c);
+            if !buffer.secret {
+                tts_ctrl.lock().unwrap().key_press(c);
+            }
             buffer.push_key(c);
             if let Ok(mut script) = script.lock() {
-                script.set_prompt_content(buffer.get_buffer(), buffer.get_pos());
+                script.set_prompt_content(buffer.get_shown_buffer(), buffer.get_pos());
             }
             buffer.last_buffer.clear();
         }
@@ -348,13 +728,13 @@ fn parse_key_event(
         Key::Left => buffer.step_left(),
         Key::Right => buffer.step_right(),
         Key::Backspace => {
-            if let Some(c) = buffer.remove() {
+            if let Some(c) = buffer.remove().filter(|_| !buffer.secret) {
                 if let Ok(mut tts_ctrl) = tts_ctrl.lock() {
                     tts_ctrl.key_press(c);
                 }
             }
             if let Ok(mut script) = script.lock() {
-                script.set_prompt_content(buffer.get_buffer(), buffer.get_pos());
+                script.set_prompt_content(buffer.get_shown_buffer(), buffer.get_pos());
             }
             buffer.last_buffer.clear();
         }
@@ -363,6 +743,86 @@ fn parse_key_event(
     };
 }
 
+/// Handles a key press while the scrollback cursor is active. Returns false
+/// for keys scroll mode has no use for, which are left to the bindings.
+/// Nothing is typed into the input buffer either way.
+fn parse_scroll_mode_key(key: Key, buffer: &mut CommandBuffer, writer: &Sender<Event>) -> bool {
+    if let Some(pending) = buffer.pending_scroll_key.take() {
+        if let Key::Char(name) = key {
+            match pending {
+                'm' => writer.send(Event::ScrollMark(name)).unwrap(),
+                _ => buffer.scroll_cursor(ScrollMotion::Mark(name), writer),
+            }
+        }
+        return true;
+    }
+
+    match key {
+        Key::Char('k') | Key::Up => buffer.scroll_cursor(ScrollMotion::LineUp, writer),
+        Key::Char('j') | Key::Down => buffer.scroll_cursor(ScrollMotion::LineDown, writer),
+        Key::Ctrl('u') | Key::PageUp => buffer.scroll_cursor(ScrollMotion::HalfPageUp, writer),
+        Key::Ctrl('d') | Key::PageDown => buffer.scroll_cursor(ScrollMotion::HalfPageDown, writer),
+        Key::Char('g') | Key::Home => buffer.scroll_cursor(ScrollMotion::Top, writer),
+        Key::Char('G') | Key::End => buffer.scroll_cursor(ScrollMotion::Bottom, writer),
+        Key::Char('n') => buffer.scroll_cursor(ScrollMotion::NextMatch, writer),
+        Key::Char('N') => buffer.scroll_cursor(ScrollMotion::PrevMatch, writer),
+        Key::Char(c @ ('m' | '\'')) => buffer.await_scroll_mark(c, writer),
+        Key::Char('y') => writer.send(Event::ScrollYank).unwrap(),
+        Key::Char('f') => writer.send(Event::SetFilter(None)).unwrap(),
+        Key::Char('q') | Key::Char('\n') | Key::Esc => buffer.set_scroll_mode(false, writer),
+        Key::Ctrl('l') => writer.send(Event::Redraw).unwrap(),
+        Key::Ctrl('c') => {
+            buffer.set_scroll_mode(false, writer);
+            writer.send(Event::Quit(QuitMethod::CtrlC)).unwrap();
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Handles a key press during an incremental history search.
+fn parse_search_key(
+    key: Key,
+    buffer: &mut CommandBuffer,
+    writer: &Sender<Event>,
+    script: &Arc<Mutex<LuaScript>>,
+) {
+    let CommandBuffer {
+        search,
+        command_history,
+        ..
+    } = buffer;
+    if let Some(search) = search.as_mut() {
+        match key {
+            Key::Char('\n') => {
+                buffer.end_search(true);
+                submit_input(buffer, writer);
+            }
+            Key::Char('\t') | Key::Left | Key::Right | Key::Home | Key::End => {
+                buffer.end_search(true)
+            }
+            Key::Esc | Key::Ctrl('g') | Key::Ctrl('c') => buffer.end_search(false),
+            Key::Ctrl('r') | Key::Up => search.next(),
+            Key::Ctrl('s') | Key::Down => search.prev(),
+            Key::Backspace => search.pop(command_history),
+            Key::Char(c) => search.push(c, command_history),
+            _ => {}
+        }
+    }
+
+    let (input, pos) = buffer
+        .search_prompt()
+        .unwrap_or_else(|| (buffer.get_buffer(), buffer.get_pos()));
+    if let Some(search) = &buffer.search {
+        if let Some(command) = search.current(&buffer.command_history) {
+            buffer.tts_ctrl.lock().unwrap().speak(command, true);
+        }
+    } else if let Ok(mut script) = script.lock() {
+        script.set_prompt_content(buffer.get_shown_buffer(), buffer.get_pos());
+    }
+    writer.send(Event::UserInputBuffer(input, pos)).unwrap();
+}
+
 fn check_command_binds(
     cmd: termion::event::Key,
     buffer: &mut CommandBuffer,
@@ -408,6 +868,7 @@ fn human_key(prefix: &str, c: char) -> String {
     match c {
         '\u{7f}' => out.push_str("backspace"),
         '\u{1b}' => out.push_str("escape"),
+        '\r' | '\n' => out.push_str("enter"),
         _ => out.push(c),
     }
     out
@@ -429,7 +890,7 @@ fn check_escape_bindings(
     handle_script_ui_io(buffer, script, writer);
     writer
         .send(Event::UserInputBuffer(
-            buffer.get_buffer(),
+            buffer.get_shown_buffer(),
             buffer.get_pos(),
         ))
         .unwrap();
@@ -460,16 +921,57 @@ fn handle_script_ui_io(
             UiEvent::ScrollUp => writer.send(Event::ScrollUp).unwrap(),
             UiEvent::ScrollTop => writer.send(Event::ScrollTop).unwrap(),
             UiEvent::ScrollBottom => writer.send(Event::ScrollBottom).unwrap(),
+            UiEvent::ScrollMode => buffer.set_scroll_mode(true, writer),
+            UiEvent::ScrollModeExit => buffer.set_scroll_mode(false, writer),
+            UiEvent::ScrollLineUp => buffer.scroll_cursor(ScrollMotion::LineUp, writer),
+            UiEvent::ScrollLineDown => buffer.scroll_cursor(ScrollMotion::LineDown, writer),
+            UiEvent::ScrollHalfPageUp => buffer.scroll_cursor(ScrollMotion::HalfPageUp, writer),
+            UiEvent::ScrollHalfPageDown => buffer.scroll_cursor(ScrollMotion::HalfPageDown, writer),
+            UiEvent::ScrollYank => writer.send(Event::ScrollYank).unwrap(),
+            UiEvent::ScrollSetMark => buffer.await_scroll_mark('m', writer),
+            UiEvent::ScrollJumpMark => buffer.await_scroll_mark('\'', writer),
+            UiEvent::HistorySearch => {
+                buffer.start_search();
+                if let Some((input, pos)) = buffer.search_prompt() {
+                    writer.send(Event::UserInputBuffer(input, pos)).unwrap();
+                }
+            }
+            UiEvent::Undo => buffer.undo(),
+            UiEvent::Redo => buffer.redo(),
+            UiEvent::Yank => buffer.yank(),
+            UiEvent::YankPop => buffer.yank_pop(),
+            UiEvent::Newline if buffer.multiline => submit_input(buffer, writer),
+            UiEvent::Newline => buffer.insert_newline(),
+            UiEvent::Multiline => {
+                let enabled = buffer.toggle_multiline();
+                writer
+                    .send(Event::Info(format!(
+                        "Multi-line input {}",
+                        if enabled { "enabled" } else { "disabled" }
+                    )))
+                    .unwrap();
+            }
             UiEvent::Complete => buffer.tab_complete(),
             UiEvent::Unknown(_) => {}
         });
-        script.set_prompt_content(buffer.get_buffer(), buffer.get_pos());
+        script.set_prompt_content(buffer.get_shown_buffer(), buffer.get_pos());
         script.get_output_lines().iter().for_each(|l| {
             writer.send(Event::Output(Line::from(l))).unwrap();
         });
     }
 }
 
+/// Loads the command history saved by the lua history module, if there is
+/// any. It's searched along with this session's commands even when
+/// `save_history` has since been turned off.
+fn load_command_history() -> Vec<String> {
+    let store: HashMap<String, String> = HashMap::load();
+    store
+        .get("__command_history")
+        .and_then(|history| serde_json::from_str(history).ok())
+        .unwrap_or_default()
+}
+
 pub fn spawn_input_thread(session: Session) -> thread::JoinHandle<()> {
     thread::Builder::new()
         .name("input-thread".to_string())
@@ -488,53 +990,84 @@ pub fn spawn_input_thread(session: Session) -> thread::JoinHandle<()> {
                 buffer
                     .completion_tree
                     .insert(include_str!("../../resources/completions.txt"));
+                buffer.add_saved_history(load_command_history());
             }
 
+            let mut menu_shown = false;
             for e in stdin.events() {
                 match e.unwrap() {
                     termion::event::Event::Key(key) => {
                         if let Ok(mut buffer) = buffer.lock() {
-                            let orig_pos = buffer.get_pos();
-                            let orig_len = buffer.buffer.len();
-                            let bind_ran = check_command_binds(key, &mut buffer, &script, &writer);
-                            if !bind_ran {
-                                parse_key_event(
-                                    key,
-                                    &mut buffer,
-                                    &writer,
-                                    &mut tts_ctrl,
-                                    &mut script,
-                                );
-                            }
-                            if orig_len == buffer.buffer.len() && orig_pos != buffer.get_pos() {
-                                writer
-                                    .send(Event::UserInputCursor(buffer.get_pos()))
-                                    .unwrap();
-                            } else if !bind_ran || orig_len != buffer.buffer.len() {
-                                if let Ok(mut luascript) = script.lock() {
-                                    luascript.set_prompt_mask_content(&buffer.prompt_mask);
-                                    luascript
-                                        .set_prompt_content(buffer.get_buffer(), buffer.get_pos());
+                            if buffer.scroll_mode {
+                                if !parse_scroll_mode_key(key, &mut buffer, &writer) {
+                                    check_command_binds(key, &mut buffer, &script, &writer);
                                 }
-
-                                // If 'last command' is applicable then render it.
-                                if buffer.buffer.is_empty() && !buffer.last_buffer.is_empty() {
-                                    let mask = buffer.get_last_command_mask();
+                            } else if buffer.search.is_some() {
+                                parse_search_key(key, &mut buffer, &writer, &script);
+                            } else {
+                                let orig_pos = buffer.get_pos();
+                                let orig_len = buffer.buffer.len();
+                                let bind_ran =
+                                    match key {
+                                        Key::Up => buffer.step_line_up(),
+                                        Key::Down => buffer.step_line_down(),
+                                        _ => false,
+                                    } || check_command_binds(key, &mut buffer, &script, &writer);
+                                if !bind_ran {
+                                    parse_key_event(
+                                        key,
+                                        &mut buffer,
+                                        &writer,
+                                        &mut tts_ctrl,
+                                        &mut script,
+                                    );
+                                }
+                                if orig_len == buffer.buffer.len() && orig_pos != buffer.get_pos() {
                                     writer
-                                        .send(Event::UserInputBuffer(
-                                            mask.mask_buffer(&buffer.last_buffer),
-                                            0,
-                                        ))
+                                        .send(Event::UserInputCursor(buffer.get_pos()))
                                         .unwrap();
-                                } else {
-                                    writer
-                                        .send(Event::UserInputBuffer(
-                                            buffer.get_buffer(),
+                                } else if !bind_ran || orig_len != buffer.buffer.len() {
+                                    if let Ok(mut luascript) = script.lock() {
+                                        luascript.set_prompt_mask_content(&buffer.prompt_mask);
+                                        luascript.set_prompt_content(
+                                            buffer.get_shown_buffer(),
                                             buffer.get_pos(),
-                                        ))
-                                        .unwrap();
+                                        );
+                                    }
+
+                                    // If 'last command' is applicable then render it.
+                                    if buffer.buffer.is_empty() && !buffer.last_buffer.is_empty() {
+                                        let mask = buffer.get_last_command_mask();
+                                        writer
+                                            .send(Event::UserInputBuffer(
+                                                mask.mask_buffer(&buffer.last_buffer),
+                                                0,
+                                            ))
+                                            .unwrap();
+                                    } else {
+                                        writer
+                                            .send(Event::UserInputBuffer(
+                                                buffer.get_shown_buffer(),
+                                                buffer.get_pos(),
+                                            ))
+                                            .unwrap();
+                                    }
                                 }
                             }
+
+                            // Show the completion menu after a completion and
+                            // hide it on any other key press.
+                            match buffer.take_completion_menu() {
+                                Some(menu) => {
+                                    menu_shown = menu.is_some();
+                                    writer.send(Event::ShowCompletions(menu)).unwrap();
+                                }
+                                None if menu_shown => {
+                                    menu_shown = false;
+                                    writer.send(Event::ShowCompletions(None)).unwrap();
+                                }
+                                None => {}
+                            }
                         }
                     }
                     termion::event::Event::Mouse(event) => parse_mouse_event(event, &writer),
@@ -572,6 +1105,7 @@ mod command_test {
     use super::check_command_binds;
     use super::CommandBuffer;
     use crate::lua::LuaScriptBuilder;
+    use crate::model::Line;
     use crate::tts::TTSController;
     use crate::Event;
 
@@ -729,6 +1263,278 @@ mod command_test {
         assert_eq!(buffer.get_buffer().len(), input.len());
     }
 
+    #[test]
+    fn test_undo_redo() {
+        let mut buffer = get_command().0;
+        push_string(&mut buffer, "some random");
+        push_string(&mut buffer, " words");
+        buffer.delete_word_left();
+        assert_eq!(buffer.get_buffer(), "some random ");
+        buffer.remove();
+        assert_eq!(buffer.get_buffer(), "some random");
+
+        buffer.undo();
+        assert_eq!(buffer.get_buffer(), "some random ");
+        buffer.undo();
+        assert_eq!(buffer.get_buffer(), "some random words");
+        assert_eq!(buffer.get_pos(), 17);
+        buffer.undo();
+        assert_eq!(buffer.get_buffer(), "");
+        buffer.undo();
+        assert_eq!(buffer.get_buffer(), "");
+
+        buffer.redo();
+        assert_eq!(buffer.get_buffer(), "some random words");
+        buffer.redo();
+        assert_eq!(buffer.get_buffer(), "some random ");
+        buffer.undo();
+        push_string(&mut buffer, "!");
+        buffer.redo();
+        assert_eq!(buffer.get_buffer(), "some random words!");
+
+        buffer.submit();
+        buffer.undo();
+        assert_eq!(buffer.get_buffer(), "");
+    }
+
+    #[test]
+    fn test_kill_ring() {
+        let mut buffer = get_command().0;
+        push_string(&mut buffer, "one two three");
+        buffer.delete_word_left();
+        buffer.delete_word_left();
+        assert_eq!(buffer.get_buffer(), "one ");
+        buffer.move_to_start();
+        buffer.delete_to_end();
+        assert!(buffer.get_buffer().is_empty());
+
+        buffer.yank();
+        assert_eq!(buffer.get_buffer(), "one ");
+        buffer.yank_pop();
+        assert_eq!(buffer.get_buffer(), "two ");
+        buffer.yank_pop();
+        assert_eq!(buffer.get_buffer(), "three");
+        buffer.yank_pop();
+        assert_eq!(buffer.get_buffer(), "one ");
+        assert_eq!(buffer.get_pos(), 4);
+
+        // Yank pop only applies directly after a yank
+        push_string(&mut buffer, "x");
+        buffer.yank_pop();
+        assert_eq!(buffer.get_buffer(), "one x");
+
+        buffer.undo();
+        buffer.undo();
+        assert!(buffer.get_buffer().is_empty());
+    }
+
+    #[test]
+    fn test_history_search() {
+        use super::parse_search_key;
+
+        let (mut buffer, _rx) = get_command();
+        let script = buffer.script.clone();
+        let (writer, rx): (Sender<Event>, Receiver<Event>) = channel();
+        for cmd in ["cast fireball", "look", "kill orc"] {
+            push_string(&mut buffer, cmd);
+            buffer.submit();
+        }
+        push_string(&mut buffer, "draft");
+
+        buffer.start_search();
+        for c in "fb".chars() {
+            parse_search_key(Key::Char(c), &mut buffer, &writer, &script);
+        }
+        assert_eq!(
+            rx.try_iter().last(),
+            Some(Event::UserInputBuffer(
+                "(reverse-i-search)`fb': cast fireball".to_string(),
+                21
+            ))
+        );
+        parse_search_key(Key::Esc, &mut buffer, &writer, &script);
+        assert!(buffer.search.is_none());
+        assert_eq!(buffer.get_buffer(), "draft");
+
+        buffer.start_search();
+        parse_search_key(Key::Char('o'), &mut buffer, &writer, &script);
+        parse_search_key(Key::Ctrl('r'), &mut buffer, &writer, &script);
+        parse_search_key(Key::Char('\t'), &mut buffer, &writer, &script);
+        assert_eq!(buffer.get_buffer(), "look");
+        buffer.undo();
+        assert_eq!(buffer.get_buffer(), "draft");
+
+        buffer.start_search();
+        parse_search_key(Key::Char('x'), &mut buffer, &writer, &script);
+        assert_eq!(
+            rx.try_iter().last(),
+            Some(Event::UserInputBuffer(
+                "(failed reverse-i-search)`x': ".to_string(),
+                27
+            ))
+        );
+    }
+
+    #[test]
+    fn test_history_search_saved_history() {
+        let mut buffer = get_command().0;
+        push_string(&mut buffer, "look");
+        buffer.submit();
+        buffer.add_saved_history(vec!["cast fireball".to_string(), "north".to_string()]);
+        assert_eq!(buffer.command_history, ["cast fireball", "north", "look"]);
+
+        buffer.start_search();
+        assert_eq!(
+            buffer.search_prompt().unwrap().0,
+            "(reverse-i-search)`': look"
+        );
+        let search = buffer.search.as_mut().unwrap();
+        "fb".chars()
+            .for_each(|c| search.push(c, &buffer.command_history));
+        assert_eq!(
+            search.current(&buffer.command_history),
+            Some("cast fireball")
+        );
+    }
+
+    #[test]
+    fn test_multiline_navigation() {
+        let mut buffer = get_command().0;
+        push_string(&mut buffer, "first line");
+        buffer.insert_newline();
+        push_string(&mut buffer, "2nd");
+        buffer.insert_newline();
+        push_string(&mut buffer, "the third line");
+        assert_eq!(buffer.get_buffer(), "first line\n2nd\nthe third line");
+
+        assert!(!buffer.step_line_down());
+        assert!(buffer.step_line_up());
+        assert_eq!(buffer.get_pos(), 14);
+        assert!(buffer.step_line_up());
+        assert_eq!(buffer.get_pos(), 3);
+        assert!(!buffer.step_line_up());
+        assert!(buffer.step_line_down());
+        assert_eq!(buffer.get_pos(), 14);
+        assert!(buffer.step_line_down());
+        assert_eq!(buffer.get_pos(), 18);
+    }
+
+    #[test]
+    fn test_multiline_submit() {
+        use super::submit_input;
+
+        let (mut buffer, _rx) = get_command();
+        let (writer, rx): (Sender<Event>, Receiver<Event>) = channel();
+        push_string(&mut buffer, "say one");
+        buffer.insert_newline();
+        push_string(&mut buffer, "say two");
+        buffer.insert_newline();
+        submit_input(&mut buffer, &writer);
+
+        let sent: Vec<String> = rx
+            .try_iter()
+            .map(|event| match event {
+                Event::ServerInput(line) => line.line().to_string(),
+                _ => panic!("Unexpected event"),
+            })
+            .collect();
+        assert_eq!(sent, vec!["say one", "say two"]);
+        assert!(buffer.get_buffer().is_empty());
+    }
+
+    #[test]
+    fn test_multiline_toggle() {
+        let mut buffer = get_command().0;
+        assert!(buffer.toggle_multiline());
+        assert!(!buffer.toggle_multiline());
+    }
+
+    #[test]
+    fn test_scroll_mode_keys() {
+        use super::parse_scroll_mode_key;
+        use crate::ui::ScrollMotion;
+
+        let mut buffer = get_command().0;
+        let (writer, rx2): (Sender<Event>, Receiver<Event>) = channel();
+        buffer.set_scroll_mode(true, &writer);
+        assert_eq!(rx2.try_recv().unwrap(), Event::ScrollMode(true));
+
+        parse_scroll_mode_key(Key::Char('k'), &mut buffer, &writer);
+        assert_eq!(
+            rx2.try_recv().unwrap(),
+            Event::ScrollCursor(ScrollMotion::LineUp)
+        );
+        parse_scroll_mode_key(Key::Char('m'), &mut buffer, &writer);
+        assert!(rx2.try_recv().is_err());
+        parse_scroll_mode_key(Key::Char('a'), &mut buffer, &writer);
+        assert_eq!(rx2.try_recv().unwrap(), Event::ScrollMark('a'));
+        parse_scroll_mode_key(Key::Char('\''), &mut buffer, &writer);
+        parse_scroll_mode_key(Key::Char('a'), &mut buffer, &writer);
+        assert_eq!(
+            rx2.try_recv().unwrap(),
+            Event::ScrollCursor(ScrollMotion::Mark('a'))
+        );
+        parse_scroll_mode_key(Key::Char('f'), &mut buffer, &writer);
+        assert_eq!(rx2.try_recv().unwrap(), Event::SetFilter(None));
+        assert!(!parse_scroll_mode_key(Key::Char('x'), &mut buffer, &writer));
+        assert!(!parse_scroll_mode_key(Key::F(5), &mut buffer, &writer));
+        assert!(rx2.try_recv().is_err());
+        assert!(buffer.get_buffer().is_empty());
+        parse_scroll_mode_key(Key::Esc, &mut buffer, &writer);
+        assert_eq!(rx2.try_recv().unwrap(), Event::ScrollMode(false));
+        assert!(!buffer.scroll_mode);
+    }
+
+    #[test]
+    fn test_scroll_mode_bindings() {
+        use super::{check_command_binds, parse_scroll_mode_key};
+
+        let (tx, _rx): (Sender<Event>, Receiver<Event>) = channel();
+        let script = Arc::new(Mutex::new(
+            LuaScriptBuilder::new(tx).dimensions((100, 100)).build(),
+        ));
+        let mut buffer = CommandBuffer::new(
+            Arc::new(Mutex::new(TTSController::new(false, true))),
+            script.clone(),
+            false,
+        );
+        script
+            .lock()
+            .unwrap()
+            .eval(r#"blight.bind("f5", function () blight.ui("scroll_set_mark") end)"#)
+            .unwrap();
+        let (writer, rx2): (Sender<Event>, Receiver<Event>) = channel();
+        assert!(!parse_scroll_mode_key(Key::F(5), &mut buffer, &writer));
+        assert!(check_command_binds(
+            Key::F(5),
+            &mut buffer,
+            &script,
+            &writer
+        ));
+        assert_eq!(rx2.try_recv().unwrap(), Event::ScrollMode(true));
+        assert!(parse_scroll_mode_key(Key::Char('b'), &mut buffer, &writer));
+        assert_eq!(rx2.try_recv().unwrap(), Event::ScrollMark('b'));
+    }
+
+    #[test]
+    fn test_scroll_mode_match_keys() {
+        use super::parse_scroll_mode_key;
+        use crate::ui::ScrollMotion;
+
+        let mut buffer = get_command().0;
+        let (writer, rx): (Sender<Event>, Receiver<Event>) = channel();
+        parse_scroll_mode_key(Key::Char('n'), &mut buffer, &writer);
+        assert_eq!(
+            rx.try_iter().last(),
+            Some(Event::ScrollCursor(ScrollMotion::NextMatch))
+        );
+        parse_scroll_mode_key(Key::Char('N'), &mut buffer, &writer);
+        assert_eq!(
+            rx.try_iter().last(),
+            Some(Event::ScrollCursor(ScrollMotion::PrevMatch))
+        );
+    }
+
     #[test]
     fn test_human_key() {
         use super::human_key;
@@ -738,6 +1544,7 @@ mod command_test {
         assert_eq!(human_key("alt-", '\u{1b}'), "alt-escape");
         assert_eq!(human_key("ctrl-", '\u{1b}'), "ctrl-escape");
         assert_eq!(human_key("ctrl-", 'd'), "ctrl-d");
+        assert_eq!(human_key("alt-", '\r'), "alt-enter");
         assert_eq!(human_key("f", 'x'), "fx");
     }
 
@@ -751,6 +1558,48 @@ mod command_test {
         assert_eq!(buffer.completion.options, vec!["batman".to_string()]);
     }
 
+    #[test]
+    fn test_completion_menu() {
+        use crate::model::CompletionMenu;
+
+        let mut buffer = get_command().0;
+        buffer
+            .script
+            .lock()
+            .unwrap()
+            .eval(
+                r#"blight.on_complete(function (input)
+                    return {{"kill", "Attack"}, "kick"}
+                end)"#,
+            )
+            .unwrap();
+        push_string(&mut buffer, "ki");
+        assert_eq!(buffer.take_completion_menu(), None);
+
+        buffer.tab_complete();
+        assert_eq!(buffer.get_buffer(), "kick");
+        let menu = CompletionMenu {
+            options: vec![
+                ("kick".to_string(), None),
+                ("kill".to_string(), Some("Attack".to_string())),
+            ],
+            selected: Some(0),
+        };
+        assert_eq!(buffer.take_completion_menu(), Some(Some(menu.clone())));
+        assert_eq!(buffer.take_completion_menu(), None);
+
+        buffer.tab_complete();
+        buffer.tab_complete();
+        assert_eq!(buffer.get_buffer(), "ki");
+        assert_eq!(
+            buffer.take_completion_menu(),
+            Some(Some(CompletionMenu {
+                selected: None,
+                ..menu
+            }))
+        );
+    }
+
     #[test]
     fn test_completion_with_big_chars() {
         // Issue #522
@@ -889,6 +1738,90 @@ mod command_test {
         (buffer, rx)
     }
 
+    #[test]
+    fn test_secret_input() {
+        let mut buffer = get_command_with_last().0;
+        push_string(&mut buffer, "north");
+        buffer.submit();
+        buffer.set_secret(true);
+        push_string(&mut buffer, "hunter2");
+        assert_eq!(buffer.get_masked_buffer(), "*******");
+        assert_eq!(buffer.get_shown_buffer(), "*******");
+        assert_eq!(buffer.get_buffer(), "hunter2");
+        assert_eq!(buffer.submit(), "hunter2");
+        assert_eq!(buffer.command_history, vec!["north".to_string()]);
+        assert_eq!(buffer.last_buffer.iter().collect::<String>(), "north");
+        // An empty submit doesn't send the last command as the password
+        assert_eq!(buffer.submit(), "");
+
+        buffer.set_secret(false);
+        push_string(&mut buffer, "look");
+        assert_eq!(buffer.get_shown_buffer(), "look");
+    }
+
+    #[test]
+    fn test_secret_input_hidden_from_scripts() {
+        use super::parse_key_event;
+        let (writer, _rx): (Sender<Event>, Receiver<Event>) = channel();
+        let mut buffer = get_command().0;
+        let mut script = buffer.script.clone();
+        let mut tts_ctrl = buffer.tts_ctrl.clone();
+        buffer.set_secret(true);
+        for key in "hunter2".chars().map(Key::Char).chain([Key::Backspace]) {
+            parse_key_event(key, &mut buffer, &writer, &mut tts_ctrl, &mut script);
+        }
+        assert_eq!(buffer.get_buffer(), "hunter");
+
+        let mut script = script.lock().unwrap();
+        script.eval("blight.output(prompt.get())").unwrap();
+        assert_eq!(script.get_output_lines(), vec![Line::from("******")]);
+    }
+
+    #[test]
+    fn test_multiline_send_delay() {
+        use super::submit_input;
+        use std::time::{Duration, Instant};
+
+        let (tx, rx): (Sender<Event>, Receiver<Event>) = channel();
+        let mut buffer = get_command().0;
+        let lines = |rx: &Receiver<Event>| {
+            rx.iter()
+                .take(3)
+                .map(|event| match event {
+                    Event::ServerInput(line) => line.line().to_string(),
+                    event => panic!("Unexpected event: {event:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+        for delay in [0, 50] {
+            buffer.set_send_delay(Duration::from_millis(delay));
+            push_string(&mut buffer, "write");
+            buffer.insert_newline();
+            push_string(&mut buffer, "first");
+            buffer.insert_newline();
+            push_string(&mut buffer, "second");
+            let start = Instant::now();
+            submit_input(&mut buffer, &tx);
+            assert_eq!(lines(&rx), ["write", "first", "second"]);
+            assert!(start.elapsed() >= Duration::from_millis(delay * 2));
+        }
+    }
+
+    #[test]
+    fn test_secret_input_flag() {
+        use super::submit_input;
+        let (tx, rx): (Sender<Event>, Receiver<Event>) = channel();
+        let mut buffer = get_command().0;
+        buffer.set_secret(true);
+        push_string(&mut buffer, "hunter2");
+        submit_input(&mut buffer, &tx);
+        let Ok(Event::ServerInput(line)) = rx.recv() else {
+            panic!("Expected a ServerInput event");
+        };
+        assert_eq!(line.line(), "hunter2");
+        assert!(line.flags.secret);
+    }
+
     #[test]
     fn test_last_command_stored_after_submit() {
         let mut buffer = get_command_with_last().0;
@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 8;
+const WORD_START_BONUS: i64 = 12;
+const PREFIX_BONUS: i64 = 20;
+const SUBSTRING_BONUS: i64 = 50;
+const RECENCY_WEIGHT: i64 = 2;
+const MAX_GAP_PENALTY: usize = 10;
+
+/// Scores how well `query` fuzzy matches `candidate`. All characters of the
+/// query have to appear in order in the candidate for it to match. Matches at
+/// the start of words, consecutive characters and exact substrings score
+/// higher, and the best scoring alignment of the query is used rather than the
+/// first one found. The match is case insensitive unless the query contains
+/// upper case characters.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let normalize = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+
+    let query: Vec<char> = query.chars().map(normalize).collect();
+    let candidate: Vec<char> = candidate.chars().map(normalize).collect();
+
+    let position_score = |ci: usize| {
+        if ci == 0 {
+            MATCH_SCORE + PREFIX_BONUS
+        } else if !candidate[ci - 1].is_alphanumeric() {
+            MATCH_SCORE + WORD_START_BONUS
+        } else {
+            MATCH_SCORE
+        }
+    };
+
+    // The best score of matching the query so far with its last character at
+    // each position of the candidate, along with the consecutive streak it
+    // ends with.
+    let mut best: Vec<Option<(i64, i64)>> = candidate
+        .iter()
+        .enumerate()
+        .map(|(ci, c)| (*c == query[0]).then(|| (position_score(ci), 0)))
+        .collect();
+    for qc in &query[1..] {
+        let mut next = vec![None; candidate.len()];
+        // The best score of the positions too far back for the gap penalty
+        // to grow any further
+        let mut distant: Option<i64> = None;
+        for ci in 1..candidate.len() {
+            if ci > MAX_GAP_PENALTY + 1 {
+                if let Some((score, _)) = best[ci - MAX_GAP_PENALTY - 2] {
+                    distant = distant.max(Some(score));
+                }
+            }
+            if candidate[ci] != *qc {
+                continue;
+            }
+            let mut options = vec![];
+            if let Some((score, streak)) = best[ci - 1] {
+                options.push((score + CONSECUTIVE_BONUS * (streak + 1), streak + 1));
+            }
+            let nearby = &best[ci.saturating_sub(MAX_GAP_PENALTY + 1)..ci - 1];
+            for (gap, last) in nearby.iter().rev().enumerate() {
+                if let Some((score, _)) = last {
+                    options.push((score - gap as i64 - 1, 0));
+                }
+            }
+            if let Some(score) = distant {
+                options.push((score - MAX_GAP_PENALTY as i64, 0));
+            }
+            next[ci] = options
+                .into_iter()
+                .max_by_key(|(score, _)| *score)
+                .map(|(score, streak)| (score + position_score(ci), streak));
+        }
+        best = next;
+    }
+
+    let mut score = best.into_iter().flatten().map(|(score, _)| score).max()?;
+    if candidate
+        .windows(query.len())
+        .any(|window| window == query.as_slice())
+    {
+        score += SUBSTRING_BONUS;
+    }
+
+    Some(score)
+}
+
+/// State of an incremental search through the command history.
+#[derive(Default)]
+pub struct CommandSearch {
+    query: String,
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl CommandSearch {
+    pub fn new(commands: &[String]) -> Self {
+        let mut search = Self::default();
+        search.update(commands);
+        search
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn push(&mut self, c: char, commands: &[String]) {
+        self.query.push(c);
+        self.update(commands);
+    }
+
+    pub fn pop(&mut self, commands: &[String]) {
+        self.query.pop();
+        self.update(commands);
+    }
+
+    /// Selects the next (lower ranked) match, stopping at the last one.
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Selects the previous (higher ranked) match.
+    pub fn prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Returns the currently selected command, if any.
+    pub fn current<'a>(&self, commands: &'a [String]) -> Option<&'a str> {
+        self.matches
+            .get(self.selected)
+            .and_then(|i| commands.get(*i))
+            .map(String::as_str)
+    }
+
+    /// Ranks all commands against the query. Commands are ranked by their
+    /// fuzzy score with a penalty that grows with their age, so recent
+    /// commands win when the scores are close. Duplicates are only listed
+    /// once, at their most recent position.
+    fn update(&mut self, commands: &[String]) {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut ranked: Vec<(i64, usize)> = commands
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, cmd)| seen.insert(cmd.as_str()))
+            .filter_map(|(i, cmd)| {
+                fuzzy_score(&self.query, cmd).map(|score| {
+                    let age = commands.len() - i;
+                    let penalty = (usize::BITS - age.leading_zeros()) as i64 * RECENCY_WEIGHT;
+                    (score - penalty, i)
+                })
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+        self.matches = ranked.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+}
+
+#[cfg(test)]
+mod command_search_test {
+    use super::{fuzzy_score, CommandSearch};
+
+    fn commands(cmds: &[&str]) -> Vec<String> {
+        cmds.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("kbo", "kill bob").is_some());
+        assert!(fuzzy_score("bok", "kill bob").is_none());
+        assert!(fuzzy_score("KILL", "kill bob").is_none());
+        assert!(fuzzy_score("kill", "KILL bob").is_some());
+        assert!(fuzzy_score("bob", "kill bob") > fuzzy_score("bob", "b o b"));
+        assert!(fuzzy_score("ki", "kill") > fuzzy_score("ki", "sky kite"));
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn test_fuzzy_score_best_alignment() {
+        // The first 'f' is a poor match, the one starting "fire" is better
+        assert!(fuzzy_score("fbl", "af x fire bolt") > fuzzy_score("fbl", "fireball"));
+        let cmds = commands(&["fireball", "af x fire bolt"]);
+        let mut search = CommandSearch::new(&cmds);
+        "fbl".chars().for_each(|c| search.push(c, &cmds));
+        assert_eq!(search.current(&cmds), Some("af x fire bolt"));
+        search.next();
+        assert_eq!(search.current(&cmds), Some("fireball"));
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let cmds = commands(&[
+            "cast fireball",
+            "look",
+            "cast fire shield",
+            "north",
+            "cast fireball",
+        ]);
+        let mut search = CommandSearch::new(&cmds);
+        assert_eq!(search.current(&cmds), Some("cast fireball"));
+
+        "cfs".chars().for_each(|c| search.push(c, &cmds));
+        assert_eq!(search.current(&cmds), Some("cast fire shield"));
+
+        search.pop(&cmds);
+        search.pop(&cmds);
+        search.pop(&cmds);
+        "cast f".chars().for_each(|c| search.push(c, &cmds));
+        assert_eq!(search.current(&cmds), Some("cast fireball"));
+        search.next();
+        assert_eq!(search.current(&cmds), Some("cast fire shield"));
+        search.next();
+        assert_eq!(search.current(&cmds), Some("cast fire shield"));
+        search.prev();
+        assert_eq!(search.current(&cmds), Some("cast fireball"));
+
+        search.push('z', &cmds);
+        assert_eq!(search.current(&cmds), None);
+    }
+
+    #[test]
+    fn test_search_large_history() {
+        let cmds: Vec<String> = (0..50000).map(|i| format!("say message {i}")).collect();
+        let mut search = CommandSearch::new(&cmds);
+        "49999".chars().for_each(|c| search.push(c, &cmds));
+        assert_eq!(search.current(&cmds), Some("say message 49999"));
+    }
+}
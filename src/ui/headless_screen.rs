@@ -2,7 +2,9 @@ use std::io::Write;
 
 use anyhow::bail;
 
-use super::UserInterface;
+use crate::model::{CompletionMenu, Theme, Wrapping};
+
+use super::{NotifyMethod, ScrollMotion, UserInterface};
 
 pub struct HeadlessScreen {}
 
@@ -35,6 +37,10 @@ impl UserInterface for HeadlessScreen {
         }
     }
 
+    fn show_completions(&mut self, _menu: Option<CompletionMenu>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     fn reset(&mut self) -> anyhow::Result<()> {
         Ok(())
     }
@@ -76,6 +82,22 @@ impl UserInterface for HeadlessScreen {
         Ok(())
     }
 
+    fn set_scroll_mode(&mut self, _enabled: bool) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn move_scroll_cursor(&mut self, _motion: ScrollMotion) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn set_scroll_mark(&mut self, _name: char) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn yank_scroll_line(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     fn set_host(&mut self, _host: &str, _port: u16) -> anyhow::Result<()> {
         Ok(())
     }
@@ -96,16 +118,67 @@ impl UserInterface for HeadlessScreen {
         Ok(())
     }
 
+    fn set_vt_pane(&mut self, _size: Option<(u16, u16)>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_vt(&mut self, line: &crate::model::Line) {
+        self.print_output(line);
+    }
+
     fn set_show_tags(&mut self, _show: bool) -> anyhow::Result<()> {
         Ok(())
     }
 
     fn set_tag_mask(&mut self, _mask: crate::model::TagMask) {}
 
+    fn set_filter(&mut self, _filter: Option<crate::model::Regex>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn set_theme(&mut self, _theme: Theme) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn set_wrapping(&mut self, _wrapping: Wrapping) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn set_history_capacity(&mut self, _capacity: usize) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn output_lines(&self, _count: Option<usize>) -> Vec<crate::model::Line> {
+        vec![]
+    }
+
+    fn set_replay(&mut self, _name: Option<String>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_replay(&mut self, _line: &crate::model::Line) {}
+
     fn set_status_line(&mut self, _line: usize, _info: String) -> anyhow::Result<()> {
         Ok(())
     }
 
+    fn set_title(&mut self, _title: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn notify(&mut self, title: &str, body: &str, _method: NotifyMethod) -> anyhow::Result<()> {
+        self.print_info(&format!("{title}: {body}"));
+        Ok(())
+    }
+
+    fn ring_bell(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn flash(&mut self, _on: bool) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     fn flush(&mut self) {
         std::io::stdout().flush().ok();
     }
@@ -193,6 +266,16 @@ mod tests {
         assert!(screen.find_down(&pattern).is_ok());
     }
 
+    #[test]
+    fn test_headless_screen_scroll_mode() {
+        let mut screen = HeadlessScreen {};
+        assert!(screen.set_scroll_mode(true).is_ok());
+        assert!(screen.move_scroll_cursor(ScrollMotion::LineUp).is_ok());
+        assert!(screen.set_scroll_mark('a').is_ok());
+        assert!(screen.yank_scroll_line().is_ok());
+        assert!(screen.set_scroll_mode(false).is_ok());
+    }
+
     #[test]
     fn test_headless_screen_set_host() {
         let mut screen = HeadlessScreen {};
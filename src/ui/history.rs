@@ -1,33 +1,180 @@
-use crate::model::{Line, Regex, TagMask};
+use std::collections::{HashMap, VecDeque};
 
+use crate::model::{Line, Regex, TagMask, WrapOptions};
+
+use super::wrap;
+
+/// A screen row, either a whole line or one of the segments it wraps into.
+struct Row {
+    /// Id of the line the row belongs to
+    line: usize,
+    wrapped: Option<Line>,
+}
+
+/// The scrollback. Lines are kept as received in a ring buffer of `capacity`
+/// lines, the oldest line being dropped for each new one once it's full.
+///
+/// Each line gets an id counting up from the first line ever appended. The
+/// lines shown through the tag mask and filter are kept as ids and wrapped
+/// into screen rows on demand. The rows are cached for the current wrap
+/// width, all indexes handed out (`len`, `get`, searches, marks) refer to
+/// those rows.
 pub struct History {
-    inner: Vec<Line>,
-    visible: Vec<Line>,
+    lines: VecDeque<Line>,
+    /// Id of the oldest line kept
+    first_id: usize,
+    /// Ids of the visible lines
+    visible: VecDeque<usize>,
+    rows: VecDeque<Row>,
+    /// Number of rows dropped from the front since the start
+    dropped_rows: usize,
+    wrap_width: usize,
+    wrap_options: WrapOptions,
     tag_mask: TagMask,
-    pub capacity: usize,
-    pub drain_length: usize,
+    /// Only lines matching the filter are visible while it's set
+    filter: Option<Regex>,
+    /// Marks point at line ids so they survive a rewrap
+    marks: HashMap<char, usize>,
+    capacity: usize,
 }
 
 impl History {
+    #[cfg(test)]
     pub fn new() -> Self {
-        let drain_length: usize = 1024;
-        let capacity: usize = 32 * drain_length;
+        Self::with_capacity(crate::io::ScrollbackSettings::default().capacity)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
         Self {
-            inner: Vec::with_capacity(capacity),
-            visible: Vec::with_capacity(capacity),
+            lines: VecDeque::with_capacity(capacity),
+            first_id: 0,
+            visible: VecDeque::new(),
+            rows: VecDeque::new(),
+            dropped_rows: 0,
+            wrap_width: 0,
+            wrap_options: WrapOptions::default(),
             tag_mask: TagMask::default(),
+            filter: None,
+            marks: HashMap::new(),
             capacity,
-            drain_length,
         }
     }
 
+    /// Changes how many lines are kept, dropping the oldest ones if there
+    /// are more.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.lines.len() > self.capacity {
+            self.drop_oldest();
+        }
+        self.lines.shrink_to(self.capacity);
+    }
+
+    /// The number of rows dropped from the front of the history so far.
+    /// Row indexes held on to shift down by the rows dropped in between.
+    pub fn dropped_rows(&self) -> usize {
+        self.dropped_rows
+    }
+
+    fn line(&self, id: usize) -> &Line {
+        &self.lines[id - self.first_id]
+    }
+
+    fn is_shown(&self, line: &Line) -> bool {
+        !line.is_masked(&self.tag_mask)
+            && self
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(line.clean_line()))
+    }
+
     fn rebuild_visible(&mut self) {
         self.visible = self
-            .inner
+            .lines
             .iter()
-            .filter(|l| !l.is_masked(&self.tag_mask))
-            .cloned()
+            .enumerate()
+            .filter(|(_, line)| self.is_shown(line))
+            .map(|(index, _)| self.first_id + index)
             .collect();
+        self.rebuild_rows();
+    }
+
+    fn rebuild_rows(&mut self) {
+        self.rows.clear();
+        for index in 0..self.visible.len() {
+            self.push_rows(self.visible[index]);
+        }
+    }
+
+    /// Wraps a visible line into rows, returning how many were added.
+    fn push_rows(&mut self, id: usize) -> usize {
+        let line = self.line(id);
+        let content = line.line();
+        if self.wrap_width == 0 || !line.is_utf8() || content.trim().is_empty() {
+            self.rows.push_back(Row {
+                line: id,
+                wrapped: None,
+            });
+            return 1;
+        }
+        let segments = wrap(content, self.wrap_width, &self.wrap_options);
+        if segments.len() == 1 {
+            // Lines that fit aren't copied
+            self.rows.push_back(Row {
+                line: id,
+                wrapped: None,
+            });
+            return 1;
+        }
+        let rows: Vec<Row> = segments
+            .iter()
+            .map(|segment| {
+                let mut row = line.clone();
+                row.set_content(segment);
+                Row {
+                    line: id,
+                    wrapped: Some(row),
+                }
+            })
+            .collect();
+        let count = rows.len();
+        self.rows.extend(rows);
+        count
+    }
+
+    /// Sets how lines are wrapped, a width of 0 disables wrapping. Returns
+    /// true if the rows were rewrapped.
+    pub fn set_wrap(&mut self, width: usize, options: WrapOptions) -> bool {
+        if width == self.wrap_width && options == self.wrap_options {
+            return false;
+        }
+        self.wrap_width = width;
+        self.wrap_options = options;
+        self.rebuild_rows();
+        true
+    }
+
+    /// The id of the line the given row belongs to.
+    pub fn line_of_row(&self, row: usize) -> usize {
+        self.rows
+            .get(row)
+            .or(self.rows.back())
+            .map(|row| row.line)
+            .unwrap_or(self.first_id)
+    }
+
+    /// The first row of the line with the given id, or of the closest visible
+    /// line following it.
+    pub fn first_row_of(&self, line: usize) -> usize {
+        self.rows
+            .partition_point(|row| row.line < line)
+            .min(self.rows.len().saturating_sub(1))
+    }
+
+    /// True if the row continues a line that was wrapped.
+    pub fn is_continuation(&self, row: usize) -> bool {
+        row > 0 && row < self.rows.len() && self.rows[row - 1].line == self.rows[row].line
     }
 
     pub fn set_tag_mask(&mut self, mask: TagMask) {
@@ -35,13 +182,65 @@ impl History {
         self.rebuild_visible();
     }
 
-    pub fn drain(&mut self) {
-        if self.inner.len() >= self.capacity {
-            self.inner.drain(0..self.drain_length);
-            self.rebuild_visible();
+    /// Shows only the lines matching `filter`, or all lines again when it's
+    /// `None`. Returns the number of visible lines.
+    pub fn set_filter(&mut self, filter: Option<Regex>) -> usize {
+        self.filter = filter;
+        self.rebuild_visible();
+        self.visible.len()
+    }
+
+    pub fn filter(&self) -> Option<&Regex> {
+        self.filter.as_ref()
+    }
+
+    /// The number of visible lines, as opposed to wrapped rows.
+    pub fn line_count(&self) -> usize {
+        self.visible.len()
+    }
+
+    /// The last `count` visible lines, or all of them, oldest first.
+    pub fn last_lines(&self, count: Option<usize>) -> Vec<Line> {
+        let skip = count.map_or(0, |count| self.visible.len().saturating_sub(count));
+        self.visible
+            .iter()
+            .skip(skip)
+            .map(|id| self.line(*id).clone())
+            .collect()
+    }
+
+    /// Drops the oldest line along with its rows and marks.
+    fn drop_oldest(&mut self) {
+        let id = self.first_id;
+        if self.lines.pop_front().is_none() {
+            return;
+        }
+        self.first_id += 1;
+        if self.visible.front() == Some(&id) {
+            self.visible.pop_front();
+            while self.rows.front().is_some_and(|row| row.line == id) {
+                self.rows.pop_front();
+                self.dropped_rows += 1;
+            }
+        }
+        self.marks.retain(|_, line| *line != id);
+    }
+
+    pub fn set_mark(&mut self, name: char, index: usize) {
+        if let Some(row) = self.rows.get(index) {
+            self.marks.insert(name, row.line);
         }
     }
 
+    /// The first row of the marked line, unless it's hidden by the filter or
+    /// tag mask.
+    pub fn get_mark(&self, name: char) -> Option<usize> {
+        self.marks
+            .get(&name)
+            .filter(|line| self.visible.binary_search(line).is_ok())
+            .map(|line| self.first_row_of(*line))
+    }
+
     pub fn append_str(&mut self, line: &str) {
         self.append(line);
     }
@@ -49,40 +248,45 @@ impl History {
     pub fn append(&mut self, line: &str) {
         if !line.trim().is_empty() {
             for segment in line.lines() {
-                let l = Line::from(segment);
-                if !l.is_masked(&self.tag_mask) {
-                    self.visible.push(l.clone());
-                }
-                self.inner.push(l);
+                self.push(Line::from(segment));
             }
         } else {
-            let l = Line::from("");
-            if !l.is_masked(&self.tag_mask) {
-                self.visible.push(l.clone());
-            }
-            self.inner.push(l);
+            self.push(Line::from(""));
         }
-        self.drain();
     }
 
-    pub fn append_line(&mut self, line: Line) {
-        if !line.is_masked(&self.tag_mask) {
-            self.visible.push(line.clone());
+    /// Appends a line, returning the number of rows it takes up.
+    pub fn append_line(&mut self, line: Line) -> usize {
+        self.push(line)
+    }
+
+    fn push(&mut self, line: Line) -> usize {
+        if self.lines.len() >= self.capacity {
+            self.drop_oldest();
+        }
+        let id = self.first_id + self.lines.len();
+        let shown = self.is_shown(&line);
+        self.lines.push_back(line);
+        if shown {
+            self.visible.push_back(id);
+            self.push_rows(id)
+        } else {
+            0
         }
-        self.inner.push(line);
-        self.drain();
     }
 
     pub fn remove_last_if_prefix(&mut self, line: &str) -> Option<Line> {
-        if let Some(prefix) = self.inner.last() {
+        if let Some(prefix) = self.lines.back() {
             if line.starts_with(prefix.line()) {
-                let popped = self.inner.pop();
-                if let Some(ref l) = popped {
-                    if !l.is_masked(&self.tag_mask) {
-                        self.visible.pop();
+                let id = self.first_id + self.lines.len() - 1;
+                if self.visible.back() == Some(&id) {
+                    self.visible.pop_back();
+                    while self.rows.back().is_some_and(|row| row.line == id) {
+                        self.rows.pop_back();
                     }
+                    self.marks.retain(|_, line| *line != id);
                 }
-                popped
+                self.lines.pop_back()
             } else {
                 None
             }
@@ -92,39 +296,39 @@ impl History {
     }
 
     pub fn get(&self, index: usize) -> &Line {
-        &self.visible[index]
+        let row = &self.rows[index];
+        row.wrapped.as_ref().unwrap_or_else(|| self.line(row.line))
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &Line> {
-        self.visible.iter()
+        (0..self.rows.len()).map(|index| self.get(index))
     }
 
     pub fn len(&self) -> usize {
-        self.visible.len()
+        self.rows.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.visible.is_empty()
+        self.rows.is_empty()
     }
 
     pub fn clear(&mut self) {
-        self.inner.clear();
+        self.first_id += self.lines.len();
+        self.dropped_rows += self.rows.len();
+        self.lines.clear();
         self.visible.clear();
+        self.rows.clear();
+        self.marks.clear();
     }
 
     pub fn find_forward(&self, pattern: &Regex, pos: usize) -> Option<usize> {
-        self.visible[pos..]
-            .iter()
-            .position(|l| pattern.is_match(l.clean_line()))
-            .map(|index| pos + index)
+        (pos..self.rows.len()).find(|index| pattern.is_match(self.get(*index).clean_line()))
     }
 
     pub fn find_backward(&self, pattern: &Regex, pos: usize) -> Option<usize> {
-        self.visible[..pos]
-            .iter()
+        (0..pos)
             .rev()
-            .position(|l| pattern.is_match(l.clean_line()))
-            .map(|index| pos - index - 1)
+            .find(|index| pattern.is_match(self.get(*index).clean_line()))
     }
 }
 
@@ -157,15 +361,44 @@ mod test {
 
     #[test]
     fn confirm_drain() {
-        let mut history = History::new();
-        for _ in 0..31 * 1024 {
-            history.append("test");
+        let mut history = History::with_capacity(1024);
+        for i in 0..1030 {
+            history.append(&format!("line {i}"));
         }
-        assert_eq!(history.len(), 31 * 1024);
-        for _ in 0..1024 {
-            history.append("test");
+        assert_eq!(history.len(), 1024);
+        assert_eq!(history.get(0).clean_line(), "line 6");
+        assert_eq!(history.dropped_rows(), 6);
+
+        history.set_capacity(10);
+        assert_eq!(history.len(), 10);
+        assert_eq!(history.get(0).clean_line(), "line 1020");
+        assert_eq!(history.dropped_rows(), 1020);
+    }
+
+    #[test]
+    fn test_drain_masked() {
+        let mut history = History::with_capacity(4);
+        for i in 0..4 {
+            let mut line = Line::from(format!("line {i}"));
+            if i % 2 == 0 {
+                line.tag.key = "combat".to_string();
+            }
+            history.append_line(line);
         }
-        assert_eq!(history.len(), 31 * 1024);
+        history.set_tag_mask(TagMask {
+            key: Some("combat".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(history.len(), 2);
+
+        // Dropping a hidden line leaves the rows alone
+        history.append("line 4");
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.dropped_rows(), 0);
+        history.append("line 5");
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(0).clean_line(), "line 3");
+        assert_eq!(history.dropped_rows(), 1);
     }
 
     #[test]
@@ -210,8 +443,8 @@ mod test {
         line.tag.color = "\x1b[31m".to_string();
         history.append_line(line);
         assert_eq!(history.len(), 1);
-        assert_eq!(history.inner[0].clean_line(), "hello world");
-        assert_eq!(history.inner[0].tag.color, "\x1b[31m");
+        assert_eq!(history.lines[0].clean_line(), "hello world");
+        assert_eq!(history.lines[0].tag.color, "\x1b[31m");
     }
 
     #[test]
@@ -235,6 +468,28 @@ mod test {
         assert_eq!(history.get(0).clean_line(), "normal line");
     }
 
+    #[test]
+    fn test_filter() {
+        let mut history = History::new();
+        for i in 0..10 {
+            history.append(&format!("line {i}"));
+        }
+        let pattern = Regex::new("[37]$", None).unwrap();
+        assert_eq!(history.set_filter(Some(pattern)), 2);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(1).clean_line(), "line 7");
+        assert_eq!(history.line_of_row(1), 7);
+
+        history.append("line 13");
+        history.append("line 14");
+        assert_eq!(history.line_count(), 3);
+        assert_eq!(history.line_of_row(2), 10);
+
+        assert_eq!(history.set_filter(None), 12);
+        assert_eq!(history.first_row_of(7), 7);
+        assert_eq!(history.get(10).clean_line(), "line 13");
+    }
+
     #[test]
     fn test_tag_mask_inner_preserved() {
         let mut history = History::new();
@@ -249,7 +504,7 @@ mod test {
         history.set_tag_mask(mask);
 
         assert_eq!(history.len(), 0); // not visible
-        assert_eq!(history.inner.len(), 1); // still in inner
+        assert_eq!(history.lines.len(), 1); // still in inner
     }
 
     #[test]
@@ -287,7 +542,7 @@ mod test {
 
         assert_eq!(history.len(), 1);
         assert_eq!(history.get(0).clean_line(), "normal line");
-        assert_eq!(history.inner.len(), 2);
+        assert_eq!(history.lines.len(), 2);
     }
 
     #[test]
@@ -309,6 +564,65 @@ mod test {
         assert_eq!(history.get(0).clean_line(), "combat line");
     }
 
+    #[test]
+    fn test_marks() {
+        let mut history = History::new();
+        for i in 0..10 {
+            history.append(&format!("line {i}"));
+        }
+        history.set_mark('a', 3);
+        history.set_mark('b', 100);
+        assert_eq!(history.get_mark('a'), Some(3));
+        assert_eq!(history.get_mark('b'), None);
+        history.set_mark('a', 5);
+        assert_eq!(history.get_mark('a'), Some(5));
+        history.clear();
+        assert_eq!(history.get_mark('a'), None);
+    }
+
+    #[test]
+    fn test_marks_follow_drain() {
+        let mut history = History::with_capacity(20);
+        for _ in 0..15 {
+            history.append("test");
+        }
+        history.set_mark('a', 5);
+        history.set_mark('b', 12);
+        for _ in 0..11 {
+            history.append("test");
+        }
+        assert_eq!(history.len(), 20);
+        assert_eq!(history.get_mark('a'), None);
+        assert_eq!(history.get_mark('b'), Some(6));
+    }
+
+    #[test]
+    fn test_marks_survive_filters() {
+        let mut history = History::new();
+        history.append("first");
+        let mut tagged = Line::from("tagged");
+        tagged.tag.key = "combat".to_string();
+        history.append_line(tagged);
+        history.append("last");
+        history.set_mark('a', 1);
+        history.set_mark('b', 2);
+
+        history.set_filter(Some(Regex::new("first|tagged", None).unwrap()));
+        assert_eq!(history.get_mark('a'), Some(1));
+        assert_eq!(history.get_mark('b'), None);
+        history.set_filter(None);
+        assert_eq!(history.get_mark('b'), Some(2));
+
+        history.set_tag_mask(TagMask {
+            key: Some("combat".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(history.get_mark('a'), None);
+        assert_eq!(history.get_mark('b'), Some(1));
+        history.set_tag_mask(TagMask::default());
+        assert_eq!(history.get_mark('a'), Some(1));
+    }
+
     #[test]
     fn test_remove_last_if_prefix_masked() {
         let mut history = History::new();
@@ -327,6 +641,42 @@ mod test {
         history.remove_last_if_prefix("combat prompt extended");
         // masked line removed from inner, visible unchanged
         assert_eq!(history.len(), 1);
-        assert_eq!(history.inner.len(), 1);
+        assert_eq!(history.lines.len(), 1);
+    }
+
+    #[test]
+    fn test_rewrap() {
+        let mut history = History::new();
+        history.append("short");
+        history.append("a line long enough to wrap");
+        history.append("tail");
+        assert_eq!(history.len(), 3);
+
+        assert!(history.set_wrap(10, WrapOptions::default()));
+        assert!(!history.set_wrap(10, WrapOptions::default()));
+        assert_eq!(history.len(), 6);
+        assert_eq!(history.get(1).clean_line(), "a line");
+        assert!(!history.is_continuation(1));
+        assert!(history.is_continuation(2));
+        assert_eq!(history.line_of_row(3), 1);
+        assert_eq!(history.first_row_of(2), 5);
+
+        assert_eq!(history.append_line(Line::from("another long line")), 2);
+        assert_eq!(history.len(), 8);
+
+        history.set_wrap(0, WrapOptions::default());
+        assert_eq!(history.len(), 4);
+    }
+
+    #[test]
+    fn test_marks_survive_rewrap() {
+        let mut history = History::new();
+        history.append("a line long enough to wrap");
+        history.append("marked");
+        history.set_mark('a', 1);
+        history.set_wrap(10, WrapOptions::default());
+        assert_eq!(history.get_mark('a'), Some(4));
+        history.set_wrap(0, WrapOptions::default());
+        assert_eq!(history.get_mark('a'), Some(1));
     }
 }
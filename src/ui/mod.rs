@@ -1,20 +1,16 @@
 pub use self::{
-    ansi::*,
-    command::spawn_input_thread,
-    command::CommandBuffer,
-    headless_screen::HeadlessScreen,
-    help_handler::HelpHandler,
-    reader_screen::ReaderScreen,
-    split_screen::SplitScreen,
-    ui_wrapper::UiWrapper,
-    user_interface::{wrap_line, UserInterface},
+    ansi::*, command::spawn_input_thread, command::CommandBuffer, headless_screen::HeadlessScreen,
+    help_handler::HelpHandler, reader_screen::ReaderScreen, scroll_data::ScrollMotion,
+    split_screen::SplitScreen, ui_wrapper::UiWrapper, user_interface::UserInterface, wrap::wrap,
 };
 
 #[cfg(test)]
 pub use self::user_interface::MockUserInterface;
 
 mod ansi;
+mod color_writer;
 mod command;
+mod command_search;
 mod headless_screen;
 mod help_handler;
 mod history;
@@ -23,3 +19,4 @@ mod scroll_data;
 mod split_screen;
 mod ui_wrapper;
 mod user_interface;
+mod wrap;
@@ -7,14 +7,20 @@ use termion::{
 };
 
 use crate::{
-    model::{Line, Regex},
+    io::SaveData,
+    model::{CompletionMenu, Line, Regex, Settings, Theme, WrapOptions, Wrapping, TIMESTAMPS},
     tools::printable_chars::PrintableCharsIterator,
-    ui::{DisableOriginMode, ResetScrollRegion, ScrollRegion},
+    ui::{
+        DisableOriginMode, Notify, NotifyMethod, ResetScrollRegion, ReverseVideo, ScrollRegion,
+        SetClipboard, SetTitle,
+    },
 };
 
 use super::{
-    history::History, scroll_data::ScrollData, user_interface::TerminalSizeError, wrap_line,
-    UserInterface,
+    history::History,
+    scroll_data::{ScrollData, ScrollMotion},
+    user_interface::TerminalSizeError,
+    wrap, UserInterface,
 };
 
 pub struct ReaderScreen {
@@ -26,10 +32,17 @@ pub struct ReaderScreen {
     width: u16,
     height: u16,
     prompt_input: Option<(String, usize)>,
+    show_timestamps: bool,
+    /// Replayed logs are printed in line with the output
+    replaying: bool,
+    theme: Theme,
+    wrap: WrapOptions,
 }
 
 impl ReaderScreen {
-    pub fn new(screen: Box<dyn Write>, history: History) -> Result<Self> {
+    pub fn new(screen: Box<dyn Write>, mut history: History) -> Result<Self> {
+        // Output is wrapped as it's printed, see `print_output`
+        history.set_wrap(0, WrapOptions::default());
         let (width, height) = termion::terminal_size()?;
         let output_line = height - 1;
         let prompt_line = height;
@@ -43,18 +56,23 @@ impl ReaderScreen {
             width,
             height,
             prompt_input: None,
+            show_timestamps: false,
+            replaying: false,
+            theme: Theme::load(),
+            wrap: Wrapping::load().reader,
         })
     }
 
     #[inline]
-    fn print(&mut self, line: &str, new_line: bool) {
+    fn print(&mut self, line: &str, new_line: bool, stamp: &str) {
         self.history.append_str(line);
         if !self.scroll_data.active {
             write!(
                 self.screen,
-                "{}{}{}{}",
+                "{}{}{}{}{}",
                 Goto(1, self.height - 1),
                 if new_line { "\n" } else { "" },
+                stamp,
                 line,
                 Goto(1, self.height)
             )
@@ -62,6 +80,16 @@ impl ReaderScreen {
         }
     }
 
+    /// The receive time written in front of a line of output. Only the
+    /// screen gets it so continued partial lines still match the history.
+    fn stamp(&self, line: &Line) -> String {
+        if self.show_timestamps {
+            format!("{} ", self.theme.format_time(&line.time()))
+        } else {
+            String::new()
+        }
+    }
+
     #[inline]
     fn print_line(&mut self, line: &Line) {
         if let Some(print_line) = &line.print_line() {
@@ -143,17 +171,39 @@ impl ReaderScreen {
     fn draw_scroll(&mut self) -> Result<()> {
         for i in 0..self.height - 1 {
             let index = self.scroll_data.pos + i as usize;
+            let line = if index >= self.history.len() {
+                String::new()
+            } else if self.scroll_data.cursor == Some(index) {
+                format!(
+                    "{}{}{}",
+                    termion::style::Invert,
+                    self.history.get(index).clean_line(),
+                    termion::style::Reset
+                )
+            } else {
+                self.history.get(index).line().to_string()
+            };
             write!(
                 self.screen,
                 "{}{}{}{}",
                 termion::cursor::Goto(1, i + 1),
                 termion::clear::CurrentLine,
-                self.history.get(index).line(),
+                line,
                 cursor::Goto(1, self.prompt_line),
             )?;
         }
         Ok(())
     }
+
+    fn draw_scroll_cursor(&mut self) -> Result<()> {
+        if self.scroll_data.cursor.is_some() {
+            self.scroll_data.active = true;
+            self.scroll_data
+                .follow_cursor(&self.history, self.output_line as usize);
+            self.draw_scroll()?;
+        }
+        Ok(())
+    }
 }
 
 impl UserInterface for ReaderScreen {
@@ -165,6 +215,7 @@ impl UserInterface for ReaderScreen {
             self.prompt_line = height;
             self.width = width;
             self.height = height;
+            self.show_timestamps = Settings::try_load()?.get(TIMESTAMPS)?;
             write!(
                 self.screen,
                 "{}{}{}",
@@ -199,15 +250,25 @@ impl UserInterface for ReaderScreen {
             }
         }
         if let Some(print_line) = line.print_line() {
+            let new_line = !line.flags.separate_receives;
+            let stamp = if new_line {
+                self.stamp(line)
+            } else {
+                String::new()
+            };
             if !line.is_utf8() || print_line.trim().is_empty() {
-                self.print(print_line, !line.flags.separate_receives);
+                self.print(print_line, new_line, &stamp);
             } else {
-                let mut new_line = !line.flags.separate_receives;
+                let mut new_line = new_line;
+                let mut stamp = stamp.as_str();
                 let mut count = 0;
                 let cur_line = self.history.len();
-                for l in wrap_line(print_line, self.width as usize, 0) {
-                    self.print(l, new_line);
+                let padding = stamp.display_width();
+                let width = (self.width as usize).saturating_sub(padding);
+                for l in wrap(print_line, width, &self.wrap) {
+                    self.print(&l, new_line, stamp);
                     new_line = true;
+                    stamp = "";
                     count += 1;
                 }
                 if self.scroll_data.scroll_lock && count > self.output_line {
@@ -225,6 +286,21 @@ impl UserInterface for ReaderScreen {
 
     // This is fancy logic to make 'tdsr' less noisy
     fn print_prompt_input(&mut self, input: &str, pos: usize) {
+        // Only the line holding the cursor of a multi-line input is shown
+        let mut pos = pos;
+        let input = input
+            .split('\n')
+            .find(|line| {
+                let len = line.chars().count();
+                if pos > len {
+                    pos -= len + 1;
+                    false
+                } else {
+                    true
+                }
+            })
+            .unwrap_or_default();
+
         // Reader screens only operate on printable input characters (no term control sequences, e.g. ANSI colour).
         let sanitized_input = input.printable_chars().collect::<String>();
         let input = sanitized_input.as_str();
@@ -270,6 +346,23 @@ impl UserInterface for ReaderScreen {
         }
     }
 
+    fn show_completions(&mut self, menu: Option<CompletionMenu>) -> Result<()> {
+        // Announce the selected candidate as a line of output so screen
+        // readers pick it up along with its position in the list.
+        if let Some(menu) = menu {
+            if let Some(selected) = menu.selected {
+                let (word, description) = &menu.options[selected];
+                let mut announcement =
+                    format!("{word} ({} of {})", selected + 1, menu.options.len());
+                if let Some(description) = description {
+                    announcement.push_str(&format!(": {description}"));
+                }
+                self.print_info(&announcement);
+            }
+        }
+        Ok(())
+    }
+
     fn reset(&mut self) -> Result<()> {
         write!(self.screen, "{}{}", termion::clear::All, ResetScrollRegion)?;
         Ok(())
@@ -385,6 +478,10 @@ impl UserInterface for ReaderScreen {
     }
 
     fn find_up(&mut self, pattern: &Regex) -> Result<()> {
+        if self.scroll_data.cursor.is_some() {
+            self.scroll_data.hilite = Some(pattern.clone());
+            return self.move_scroll_cursor(ScrollMotion::PrevMatch);
+        }
         self.scroll_data.clamp(&self.history);
         let scroll_range = self.output_line as usize;
         let pos = if self.scroll_data.active {
@@ -402,6 +499,10 @@ impl UserInterface for ReaderScreen {
     }
 
     fn find_down(&mut self, pattern: &Regex) -> Result<()> {
+        if self.scroll_data.cursor.is_some() {
+            self.scroll_data.hilite = Some(pattern.clone());
+            return self.move_scroll_cursor(ScrollMotion::NextMatch);
+        }
         self.scroll_data.clamp(&self.history);
         if self.scroll_data.active {
             if let Some(line) = self
@@ -415,6 +516,51 @@ impl UserInterface for ReaderScreen {
         Ok(())
     }
 
+    fn set_scroll_mode(&mut self, enabled: bool) -> Result<()> {
+        if enabled {
+            if self.scroll_data.cursor.is_none() && !self.history.is_empty() {
+                let last = self.history.len() - 1;
+                self.scroll_data.cursor = if self.scroll_data.active {
+                    Some(last.min(self.scroll_data.pos + self.output_line as usize - 1))
+                } else {
+                    Some(last)
+                };
+                self.draw_scroll_cursor()?;
+            }
+        } else if self.scroll_data.cursor.take().is_some() {
+            self.reset_scroll()?;
+        }
+        Ok(())
+    }
+
+    fn move_scroll_cursor(&mut self, motion: ScrollMotion) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        self.scroll_data
+            .move_cursor(&motion, &self.history, self.output_line as usize);
+        self.draw_scroll_cursor()
+    }
+
+    fn set_scroll_mark(&mut self, name: char) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        if let Some(index) = self
+            .scroll_data
+            .cursor
+            .or(self.history.len().checked_sub(1))
+        {
+            self.history.set_mark(name, index);
+        }
+        Ok(())
+    }
+
+    fn yank_scroll_line(&mut self) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        if let Some(index) = self.scroll_data.cursor {
+            let line = self.history.get(index).clean_line().to_string();
+            write!(self.screen, "{}", SetClipboard(&line))?;
+        }
+        Ok(())
+    }
+
     fn set_host(&mut self, _host: &str, _port: u16) -> Result<()> {
         Ok(())
     }
@@ -435,16 +581,114 @@ impl UserInterface for ReaderScreen {
         Ok(())
     }
 
+    fn set_vt_pane(&mut self, _size: Option<(u16, u16)>) -> Result<()> {
+        Ok(())
+    }
+
+    fn print_vt(&mut self, line: &Line) {
+        self.print_output(line);
+    }
+
     fn set_show_tags(&mut self, _show: bool) -> Result<()> {
         Ok(())
     }
 
     fn set_tag_mask(&mut self, _mask: crate::model::TagMask) {}
 
+    fn set_filter(&mut self, filter: Option<Regex>) -> Result<()> {
+        if filter.is_none() && self.history.filter().is_none() {
+            return Ok(());
+        }
+        let source = self
+            .scroll_data
+            .cursor
+            .map(|row| self.history.line_of_row(row));
+        let pattern = filter.as_ref().map(|filter| filter.as_str().to_string());
+        let count = self.history.set_filter(filter);
+        self.scroll_data.cursor = None;
+        self.reset_scroll()?;
+        if let Some(pattern) = pattern {
+            self.print_info(&format!("{count} lines matching {pattern}"));
+        } else if let Some(source) = source {
+            self.scroll_data.cursor = Some(self.history.first_row_of(source));
+            self.draw_scroll_cursor()?;
+        }
+        Ok(())
+    }
+
+    fn set_theme(&mut self, theme: Theme) -> Result<()> {
+        self.theme = theme;
+        Ok(())
+    }
+
+    fn set_history_capacity(&mut self, capacity: usize) -> Result<()> {
+        self.history.set_capacity(capacity);
+        self.scroll_data.clamp(&self.history);
+        Ok(())
+    }
+
+    fn output_lines(&self, count: Option<usize>) -> Vec<Line> {
+        self.history.last_lines(count)
+    }
+
+    fn set_replay(&mut self, name: Option<String>) -> Result<()> {
+        if let Some(name) = &name {
+            self.print_info(&format!("Replaying {name}"));
+        } else if self.replaying {
+            self.print_info("Replay stopped");
+        }
+        self.replaying = name.is_some();
+        Ok(())
+    }
+
+    fn print_replay(&mut self, line: &Line) {
+        if self.replaying {
+            self.print_output(line);
+        }
+    }
+
+    fn set_wrapping(&mut self, wrapping: Wrapping) -> Result<()> {
+        // Only output printed from now on is affected, the terminal owns the
+        // scrollback in this mode
+        self.wrap = wrapping.reader;
+        Ok(())
+    }
+
     fn set_status_line(&mut self, _line: usize, _info: String) -> Result<()> {
         Ok(())
     }
 
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        write!(self.screen, "{}", SetTitle(title))?;
+        Ok(())
+    }
+
+    fn notify(&mut self, title: &str, body: &str, method: NotifyMethod) -> Result<()> {
+        // Also printed as the screen reader may not announce desktop
+        // notifications
+        self.print_info(&format!("{title}: {body}"));
+        write!(
+            self.screen,
+            "{}",
+            Notify {
+                title,
+                body,
+                method
+            }
+        )?;
+        Ok(())
+    }
+
+    fn ring_bell(&mut self) -> Result<()> {
+        write!(self.screen, "\x07")?;
+        Ok(())
+    }
+
+    fn flash(&mut self, on: bool) -> Result<()> {
+        write!(self.screen, "{}", ReverseVideo(on))?;
+        Ok(())
+    }
+
     fn flush(&mut self) {
         self.screen.flush().unwrap();
     }
@@ -1,11 +1,25 @@
 use crate::{
     io::SaveData,
-    model::{Regex, Settings, SCROLL_LOCK, SCROLL_SPLIT},
+    model::{Regex, Settings, WrapOptions, SCROLL_LOCK, SCROLL_SPLIT},
 };
 
 use super::history::History;
 use anyhow::Result;
 
+/// Movements of the scrollback cursor used in scroll mode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScrollMotion {
+    LineUp,
+    LineDown,
+    HalfPageUp,
+    HalfPageDown,
+    Top,
+    Bottom,
+    PrevMatch,
+    NextMatch,
+    Mark(char),
+}
+
 pub struct ScrollData {
     pub active: bool,
     pub split: bool,
@@ -14,6 +28,10 @@ pub struct ScrollData {
     pub hilite: Option<Regex>,
     pub allow_split: bool,
     pub allow_scroll_lock: bool,
+    /// History index of the scrollback cursor while in scroll mode
+    pub cursor: Option<usize>,
+    /// The history's dropped row count when last clamped
+    dropped_rows: usize,
 }
 
 impl ScrollData {
@@ -27,6 +45,8 @@ impl ScrollData {
             hilite: None,
             allow_split: settings.get(SCROLL_SPLIT).unwrap_or(true),
             allow_scroll_lock: settings.get(SCROLL_LOCK).unwrap_or(true),
+            cursor: None,
+            dropped_rows: 0,
         }
     }
 
@@ -34,6 +54,7 @@ impl ScrollData {
         self.active = false;
         self.split = false;
         self.hilite = None;
+        self.dropped_rows = history.dropped_rows();
         self.pos = if history.is_empty() {
             0
         } else {
@@ -54,11 +75,74 @@ impl ScrollData {
         !self.active || self.split
     }
 
+    /// Keeps the scroll position and cursor on the same rows as the oldest
+    /// ones are dropped from the history, and within its bounds.
     pub fn clamp(&mut self, history: &History) {
+        let dropped = history.dropped_rows().saturating_sub(self.dropped_rows);
+        self.dropped_rows = history.dropped_rows();
+        let last = history.len().saturating_sub(1);
         if self.active {
-            while self.pos >= history.len() {
-                self.pos -= history.drain_length;
+            self.pos = self.pos.saturating_sub(dropped).min(last);
+        }
+        if let Some(cursor) = self.cursor {
+            self.cursor = if history.is_empty() {
+                None
+            } else {
+                Some(cursor.saturating_sub(dropped).min(last))
+            };
+        }
+    }
+
+    pub fn move_cursor(&mut self, motion: &ScrollMotion, history: &History, page_height: usize) {
+        if history.is_empty() {
+            return;
+        }
+        let last = history.len() - 1;
+        let cursor = self.cursor.unwrap_or(last).min(last);
+        let half_page = (page_height / 2).max(1);
+        let cursor = match motion {
+            ScrollMotion::LineUp => cursor.saturating_sub(1),
+            ScrollMotion::LineDown => (cursor + 1).min(last),
+            ScrollMotion::HalfPageUp => cursor.saturating_sub(half_page),
+            ScrollMotion::HalfPageDown => (cursor + half_page).min(last),
+            ScrollMotion::Top => 0,
+            ScrollMotion::Bottom => last,
+            ScrollMotion::PrevMatch => self
+                .hilite
+                .as_ref()
+                .and_then(|pattern| history.find_backward(pattern, cursor))
+                .unwrap_or(cursor),
+            ScrollMotion::NextMatch => self
+                .hilite
+                .as_ref()
+                .and_then(|pattern| history.find_forward(pattern, cursor + 1))
+                .unwrap_or(cursor),
+            ScrollMotion::Mark(name) => history.get_mark(*name).unwrap_or(cursor),
+        };
+        self.cursor = Some(cursor);
+    }
+
+    /// Rewraps the history to a new width or wrap options, keeping the scroll
+    /// position and cursor on the same lines.
+    pub fn rewrap(&mut self, history: &mut History, width: usize, options: WrapOptions) {
+        let pos = history.line_of_row(self.pos);
+        let cursor = self.cursor.map(|row| history.line_of_row(row));
+        if history.set_wrap(width, options) {
+            self.pos = history.first_row_of(pos);
+            self.cursor = cursor.map(|line| history.first_row_of(line));
+        }
+    }
+
+    /// Moves the scroll position so that the cursor is within the `range`
+    /// lines currently shown.
+    pub fn follow_cursor(&mut self, history: &History, range: usize) {
+        if let Some(cursor) = self.cursor {
+            if cursor < self.pos {
+                self.pos = cursor;
+            } else if cursor >= self.pos + range {
+                self.pos = cursor + 1 - range;
             }
+            self.pos = self.pos.min(history.len().saturating_sub(range));
         }
     }
 }
@@ -89,13 +173,20 @@ mod test {
         scroll.active = true;
         scroll.pos = 1200;
 
-        let mut history = History::new();
+        let mut history = History::with_capacity(1024);
         for _ in 0..1024 {
             history.append_str("test")
         }
         assert!(scroll.pos > history.len());
         scroll.clamp(&history);
-        assert_eq!(scroll.pos, 1200 - history.drain_length);
+        assert_eq!(scroll.pos, 1023);
+
+        scroll.pos = 500;
+        for _ in 0..10 {
+            history.append_str("test")
+        }
+        scroll.clamp(&history);
+        assert_eq!(scroll.pos, 490);
     }
 
     #[test]
@@ -173,6 +264,96 @@ mod test {
         assert_eq!(scroll.pos, history.len() - 1);
     }
 
+    #[test]
+    fn test_move_cursor() {
+        let mut scroll = ScrollData::new();
+        let mut history = History::new();
+        for i in 0..100 {
+            history.append_str(&format!("line {i}"));
+        }
+
+        scroll.move_cursor(&ScrollMotion::LineUp, &history, 20);
+        assert_eq!(scroll.cursor, Some(98));
+        scroll.move_cursor(&ScrollMotion::HalfPageUp, &history, 20);
+        assert_eq!(scroll.cursor, Some(88));
+        scroll.move_cursor(&ScrollMotion::LineDown, &history, 20);
+        assert_eq!(scroll.cursor, Some(89));
+        scroll.move_cursor(&ScrollMotion::HalfPageDown, &history, 20);
+        assert_eq!(scroll.cursor, Some(99));
+        scroll.move_cursor(&ScrollMotion::LineDown, &history, 20);
+        assert_eq!(scroll.cursor, Some(99));
+        scroll.move_cursor(&ScrollMotion::Top, &history, 20);
+        assert_eq!(scroll.cursor, Some(0));
+        scroll.move_cursor(&ScrollMotion::LineUp, &history, 20);
+        assert_eq!(scroll.cursor, Some(0));
+        scroll.move_cursor(&ScrollMotion::Bottom, &history, 20);
+        assert_eq!(scroll.cursor, Some(99));
+    }
+
+    #[test]
+    fn test_move_cursor_matches_and_marks() {
+        let mut scroll = ScrollData::new();
+        let mut history = History::new();
+        for i in 0..100 {
+            history.append_str(&format!("line {i}"));
+        }
+        scroll.hilite = Some(Regex::new("^line [24]0$", None).unwrap());
+        scroll.move_cursor(&ScrollMotion::PrevMatch, &history, 20);
+        assert_eq!(scroll.cursor, Some(40));
+        scroll.move_cursor(&ScrollMotion::PrevMatch, &history, 20);
+        assert_eq!(scroll.cursor, Some(20));
+        scroll.move_cursor(&ScrollMotion::PrevMatch, &history, 20);
+        assert_eq!(scroll.cursor, Some(20));
+        scroll.move_cursor(&ScrollMotion::NextMatch, &history, 20);
+        assert_eq!(scroll.cursor, Some(40));
+
+        history.set_mark('a', 7);
+        scroll.move_cursor(&ScrollMotion::Mark('a'), &history, 20);
+        assert_eq!(scroll.cursor, Some(7));
+        scroll.move_cursor(&ScrollMotion::Mark('b'), &history, 20);
+        assert_eq!(scroll.cursor, Some(7));
+    }
+
+    #[test]
+    fn test_follow_cursor() {
+        let mut scroll = ScrollData::new();
+        let mut history = History::new();
+        for _ in 0..100 {
+            history.append_str("line");
+        }
+        scroll.pos = 50;
+        scroll.cursor = Some(10);
+        scroll.follow_cursor(&history, 20);
+        assert_eq!(scroll.pos, 10);
+        scroll.cursor = Some(45);
+        scroll.follow_cursor(&history, 20);
+        assert_eq!(scroll.pos, 26);
+        scroll.cursor = Some(99);
+        scroll.follow_cursor(&history, 20);
+        assert_eq!(scroll.pos, 80);
+    }
+
+    #[test]
+    fn test_clamp_cursor() {
+        let mut scroll = ScrollData::new();
+        let mut history = History::with_capacity(1024);
+        for _ in 0..1024 {
+            history.append_str("test")
+        }
+        scroll.cursor = Some(1200);
+        scroll.clamp(&history);
+        assert_eq!(scroll.cursor, Some(1023));
+        scroll.cursor = Some(5);
+        for _ in 0..10 {
+            history.append_str("test")
+        }
+        scroll.clamp(&history);
+        assert_eq!(scroll.cursor, Some(0));
+        history.clear();
+        scroll.clamp(&history);
+        assert_eq!(scroll.cursor, None);
+    }
+
     #[test]
     fn test_reset_with_empty_history() {
         let mut scroll = ScrollData::new();
@@ -182,4 +363,24 @@ mod test {
         assert!(scroll.reset(&history).is_ok());
         assert_eq!(scroll.pos, 0);
     }
+
+    #[test]
+    fn test_rewrap_keeps_position() {
+        let mut history = History::new();
+        for _ in 0..10 {
+            history.append("a line long enough to wrap");
+        }
+        let mut scroll = ScrollData::new();
+        scroll.active = true;
+        scroll.pos = 5;
+        scroll.cursor = Some(7);
+
+        scroll.rewrap(&mut history, 10, WrapOptions::default());
+        assert_eq!(scroll.pos, 20);
+        assert_eq!(scroll.cursor, Some(28));
+
+        scroll.rewrap(&mut history, 0, WrapOptions::default());
+        assert_eq!(scroll.pos, 5);
+        assert_eq!(scroll.cursor, Some(7));
+    }
 }
@@ -1,44 +1,55 @@
 use super::history::History;
-use super::scroll_data::ScrollData;
+use super::scroll_data::{ScrollData, ScrollMotion};
 use super::user_interface::TerminalSizeError;
-use super::wrap_line;
-use crate::io::SaveData;
-use crate::model::{Settings, HIDE_TOPBAR};
+use crate::io::{SaveData, ScrollbackSettings};
+use crate::model::{
+    highlight, CompletionMenu, Settings, Theme, WrapOptions, Wrapping, HIDE_TOPBAR, TIMESTAMPS,
+};
 use crate::{
-    model::Line, model::Regex, model::TagMask, model::ToLine,
+    model::Line, model::Regex, model::TagMask, model::ToLine, model::VtGrid,
     tools::printable_chars::PrintableCharsIterator, ui::ansi::*,
 };
 use anyhow::Result;
+use chrono::Local;
+use log::error;
 use std::collections::HashSet;
 use std::io::Write;
-use termion::color::{self, Bg, Fg};
+use termion::color::{Bg, Fg};
 use termion::cursor;
 
 use super::UserInterface;
 
 const SCROLL_LIVE_BUFFER_SIZE: u16 = 10;
-const PROMPT_HEIGHT: u16 = 1;
+const PROMPT_HEIGHT_MIN: u16 = 1;
+const PROMPT_HEIGHT_MAX: u16 = 5;
 const STATUS_HEIGHT_MIN: u16 = 0;
 const STATUS_HEIGHT_MAX: u16 = 5;
+const COMPLETION_MENU_HEIGHT: u16 = 8;
 
 struct StatusArea {
     start_line: u16,
     width: u16,
     status_lines: Vec<Option<String>>,
     scroll_marker: bool,
+    theme: Theme,
 }
 
 impl StatusArea {
-    fn new(height: u16, start_line: u16, width: u16) -> Self {
+    fn new(height: u16, start_line: u16, width: u16, theme: Theme) -> Self {
         let height = Self::clamp_height(height);
         Self {
             start_line,
             width,
             status_lines: vec![None; height],
             scroll_marker: false,
+            theme,
         }
     }
 
+    fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
     fn set_scroll_marker(&mut self, value: bool) {
         self.scroll_marker = value;
     }
@@ -92,7 +103,7 @@ impl StatusArea {
             info = if info.is_empty() {
                 custom_info.to_string()
             } else {
-                format!("{info}━ {custom_info} ")
+                format!("{info}{} {custom_info} ", self.theme.bar_glyph)
             };
         }
 
@@ -113,35 +124,30 @@ impl StatusArea {
     }
 
     fn draw_bar(&self, line: usize, screen: &mut impl Write, custom_info: &str) -> Result<()> {
+        let style = self.theme.escape(&self.theme.bar);
         write!(
             screen,
             "{}{}{}",
             termion::cursor::Goto(1, line as u16),
             termion::clear::CurrentLine,
-            Fg(color::Green),
+            style,
         )?;
 
         let custom_info = if !custom_info.trim().is_empty() {
             format!(
-                "━ {}{}{} ",
+                "{} {}{}{} ",
+                self.theme.bar_glyph,
                 custom_info.trim(),
-                Fg(color::Reset),
-                Fg(color::Green)
+                termion::style::Reset,
+                style
             )
         } else {
             "".to_string()
         };
 
-        let info_line = Line::from(&custom_info);
-        let stripped_chars = info_line.line().len() - info_line.clean_line().len();
-
-        write!(
-            screen,
-            "{:━<1$}",
-            &custom_info,
-            self.width as usize + stripped_chars
-        )?; // Print separator
-        write!(screen, "{}", Fg(color::Reset))?;
+        let bar = pad_bar(&custom_info, self.theme.bar_glyph, self.width as usize);
+        write!(screen, "{bar}")?; // Print separator
+        write!(screen, "{}", termion::style::Reset)?;
         Ok(())
     }
 
@@ -171,16 +177,31 @@ pub struct SplitScreen {
     mud_prompt_line: u16,
     mud_prompt: Line,
     prompt_line: u16,
+    prompt_height: u16,
     status_area: StatusArea,
+    cursor_prompt_line: u16,
     cursor_prompt_pos: u16,
     history: History,
+    /// The live output kept aside while a replayed log is shown
+    live: Option<History>,
+    replay: Option<String>,
     scroll_data: ScrollData,
     connection: Option<String>,
     tags: HashSet<String>,
     prompt_input: String,
     prompt_input_pos: usize,
     show_tags: bool,
+    show_timestamps: bool,
     tag_mask: TagMask,
+    completion_menu: Option<CompletionMenu>,
+    completion_menu_height: u16,
+    theme: Theme,
+    wrap: WrapOptions,
+    /// The top bar is highlighted as a visual bell
+    flash: bool,
+    top_bar: bool,
+    /// Draws screen drawing output in a pane above the output area
+    vt: Option<VtGrid>,
 }
 
 impl UserInterface for SplitScreen {
@@ -194,10 +215,16 @@ impl UserInterface for SplitScreen {
         if width > 0 && height > 0 {
             self.width = width;
             self.height = height;
-            self.output_line = height - self.status_area.height() - 2;
-            self.mud_prompt_line = height - self.status_area.height() - 1;
-            self.prompt_line = height;
-            self.output_start_line = if settings.get(HIDE_TOPBAR)? { 1 } else { 2 };
+            self.output_line = height - self.status_area.height() - self.prompt_height - 1;
+            self.mud_prompt_line = height - self.status_area.height() - self.prompt_height;
+            self.prompt_line = height - self.prompt_height + 1;
+            self.cursor_prompt_line = self.prompt_line;
+            self.top_bar = !settings.get(HIDE_TOPBAR)?;
+            self.output_start_line = self.vt_pane_line() + self.vt_pane_height();
+            self.show_timestamps = settings.get(TIMESTAMPS)?;
+            let wrap_width = (self.width as usize).saturating_sub(self.line_padding());
+            self.scroll_data
+                .rewrap(&mut self.history, wrap_width, self.wrap);
 
             write!(
                 self.screen,
@@ -207,8 +234,11 @@ impl UserInterface for SplitScreen {
             )
             .unwrap(); // Set scroll region, non origin mode
             self.redraw_top_bar()?;
+            self.draw_vt_pane()?;
             self.reset_scroll()?;
             self.redraw_status_area()?;
+            self.completion_menu_height = 0;
+            self.draw_completion_menu()?;
             self.screen.flush()?;
             write!(
                 self.screen,
@@ -223,43 +253,36 @@ impl UserInterface for SplitScreen {
     }
 
     fn print_error(&mut self, output: &str) {
-        let line = &format!("{}[!!] {}{}", Fg(color::Red), output, Fg(color::Reset));
+        let line = &format!("{} {}", self.theme.error_prefix, output);
+        let line = self.theme.paint(&self.theme.error, line);
         self.print_line(line.to_internal_line());
     }
 
     fn print_info(&mut self, output: &str) {
-        let line = &format!("[**] {output}");
+        let line = &format!("{} {}", self.theme.info_prefix, output);
+        let line = self.theme.paint(&self.theme.info, line);
         self.print_line(line.to_internal_line());
     }
 
     fn print_output(&mut self, line: &Line) {
         //debug!("UI: {:?}", line);
+        if let Some(live) = &mut self.live {
+            if line.print_line().is_some() {
+                live.append_line(line.clone());
+            }
+            return;
+        }
         // Handle screen clear request from server
         if line.flags.screen_clear {
             self.clear_output_area().ok();
         }
-        let raw = match line.print_line() {
-            Some(r) => r,
-            None => return,
-        };
-        if !line.is_utf8() || raw.trim().is_empty() {
-            self.print_line(line.clone());
-        } else {
-            let padding = if self.show_tags { 2 } else { 0 };
-            let segments: Vec<String> = wrap_line(raw, self.width as usize, padding)
-                .into_iter()
-                .map(str::to_string)
-                .collect();
-            let count = segments.len();
-            let cur_line = self.history.len();
-            for segment in segments {
-                let mut entry = line.clone();
-                entry.set_content(&segment);
-                self.print_line(entry);
-            }
-            if self.scroll_data.scroll_lock && count > self.height as usize {
-                self.scroll_to(cur_line).ok();
-            }
+        if line.print_line().is_none() {
+            return;
+        }
+        let cur_line = self.history.len();
+        let count = self.print_line(line.clone());
+        if self.scroll_data.scroll_lock && count > self.height as usize {
+            self.scroll_to(cur_line).ok();
         }
     }
 
@@ -267,6 +290,7 @@ impl UserInterface for SplitScreen {
         //debug!("UI: {:?}", prompt);
         self.mud_prompt = prompt.clone();
         self.redraw_prompt();
+        self.draw_completion_menu().ok();
     }
 
     fn print_prompt_input(&mut self, input: &str, pos: usize) {
@@ -276,56 +300,52 @@ impl UserInterface for SplitScreen {
         self.prompt_input = input.to_string();
         self.prompt_input_pos = pos;
 
-        // Calculate display width up to cursor position
-        let (byte_idx_at_cursor, _) = input.byte_index_at_display_width(pos);
-        let mut cursor_display_pos = (&input[..byte_idx_at_cursor]).display_width();
-
-        let mut input = input;
-        let width = self.width as usize;
-        let mut wrapped = false;
-
-        // Scroll the view when cursor goes past the visible width
-        while input.display_width() >= width && cursor_display_pos >= width {
-            wrapped = true;
-            let (byte_idx, skipped_width) = input.byte_index_at_display_width(width);
-            if byte_idx < input.len() {
-                input = input.split_at(byte_idx).1;
-                cursor_display_pos -= skipped_width;
-            } else {
-                input = "";
-                cursor_display_pos = 0;
+        // Locate the line holding the cursor and the cursor position on it
+        let lines: Vec<&str> = input.split('\n').collect();
+        let mut cursor_row = 0;
+        let mut cursor_col = pos;
+        for (i, line) in lines.iter().enumerate() {
+            let len = line.chars().count();
+            if cursor_col <= len || i == lines.len() - 1 {
+                cursor_row = i;
+                break;
             }
+            cursor_col -= len + 1;
         }
 
-        // When wrapped, reserve 1 column for the '>' indicator
-        let effective_width = if wrapped { width - 1 } else { width };
-
-        // Truncate input if it's still too wide for the display
-        if input.display_width() >= effective_width {
-            let (byte_idx, _) = input.byte_index_at_display_width(effective_width);
-            input = input.split_at(byte_idx).0;
+        // Grow the prompt area upwards to fit the input
+        let height = (lines.len() as u16).clamp(PROMPT_HEIGHT_MIN, PROMPT_HEIGHT_MAX);
+        if height != self.prompt_height {
+            self.resize_prompt(height).ok();
         }
 
-        // Adjust cursor position for the wrap indicator
-        let cursor_offset = if wrapped { 2 } else { 1 };
-        self.cursor_prompt_pos = cursor_display_pos as u16 + cursor_offset;
-
-        let wrap_indicator = if wrapped { ">" } else { "" };
-        write!(
-            self.screen,
-            "{}{}{}{}{}{}{}{}{}{}",
-            termion::cursor::Save,
-            termion::cursor::Goto(1, self.prompt_line),
-            Fg(termion::color::Reset),
-            Bg(termion::color::Reset),
-            termion::style::Reset,
-            termion::clear::CurrentLine,
-            wrap_indicator,
-            input,
-            termion::cursor::Restore,
-            self.goto_prompt(),
-        )
-        .unwrap();
+        // Only show the lines closest to the cursor if they don't all fit
+        let first = (cursor_row + 1).saturating_sub(height as usize);
+        let width = self.width as usize;
+        for (i, line) in lines.iter().skip(first).take(height as usize).enumerate() {
+            let row = self.prompt_line + i as u16;
+            let is_cursor_row = first + i == cursor_row;
+            let col = if is_cursor_row { cursor_col } else { 0 };
+            let (line, cursor_pos) = fit_prompt_input(line, col, width);
+            if is_cursor_row {
+                self.cursor_prompt_line = row;
+                self.cursor_prompt_pos = cursor_pos;
+            }
+            write!(
+                self.screen,
+                "{}{}{}{}{}{}{}{}",
+                termion::cursor::Save,
+                termion::cursor::Goto(1, row),
+                Fg(termion::color::Reset),
+                Bg(termion::color::Reset),
+                termion::style::Reset,
+                termion::clear::CurrentLine,
+                line,
+                termion::cursor::Restore,
+            )
+            .unwrap();
+        }
+        write!(self.screen, "{}", self.goto_prompt()).unwrap();
     }
 
     fn print_send(&mut self, send: &Line) {
@@ -334,22 +354,33 @@ impl UserInterface for SplitScreen {
         }
         if let Some(line) = send.print_line() {
             let line = &format!(
-                "{}{}> {}{}",
+                "{}{}",
                 termion::style::Reset,
-                Fg(color::LightYellow),
-                line,
-                Fg(color::Reset),
+                self.theme.paint(
+                    &self.theme.echo,
+                    &format!("{} {}", self.theme.echo_prefix, line)
+                ),
             );
-            for line in wrap_line(
-                line,
-                self.width as usize,
-                if self.show_tags { 2 } else { 0 },
-            ) {
-                self.print_line(line.to_internal_line());
-            }
+            self.print_line(line.to_internal_line());
         }
     }
 
+    fn show_completions(&mut self, menu: Option<CompletionMenu>) -> Result<()> {
+        let old_height = self.completion_menu_height;
+        self.completion_menu = menu;
+        self.completion_menu_height = 0;
+        self.draw_completion_menu()?;
+        if old_height > self.completion_menu_height {
+            let bottom = self.mud_prompt_line;
+            self.restore_rows(
+                bottom.saturating_sub(old_height)
+                    ..bottom.saturating_sub(self.completion_menu_height),
+            )?;
+        }
+        write!(self.screen, "{}", self.goto_prompt())?;
+        Ok(())
+    }
+
     fn reset(&mut self) -> Result<()> {
         write!(self.screen, "{}{}", termion::clear::All, ResetScrollRegion)?;
         Ok(())
@@ -486,6 +517,10 @@ impl UserInterface for SplitScreen {
     }
 
     fn find_up(&mut self, pattern: &Regex) -> Result<()> {
+        if self.scroll_data.cursor.is_some() {
+            self.scroll_data.hilite = Some(pattern.clone());
+            return self.move_scroll_cursor(ScrollMotion::PrevMatch);
+        }
         self.scroll_data.clamp(&self.history);
         let pos = if self.scroll_data.active {
             self.scroll_data.pos
@@ -502,6 +537,10 @@ impl UserInterface for SplitScreen {
     }
 
     fn find_down(&mut self, pattern: &Regex) -> Result<()> {
+        if self.scroll_data.cursor.is_some() {
+            self.scroll_data.hilite = Some(pattern.clone());
+            return self.move_scroll_cursor(ScrollMotion::NextMatch);
+        }
         self.scroll_data.clamp(&self.history);
         if self.scroll_data.active {
             if let Some(line) = self
@@ -515,6 +554,51 @@ impl UserInterface for SplitScreen {
         Ok(())
     }
 
+    fn set_scroll_mode(&mut self, enabled: bool) -> Result<()> {
+        if enabled {
+            if self.scroll_data.cursor.is_none() && !self.history.is_empty() {
+                let last = self.history.len() - 1;
+                self.scroll_data.cursor = if self.scroll_data.active {
+                    Some(last.min(self.scroll_data.pos + self.scroll_range() as usize - 1))
+                } else {
+                    Some(last)
+                };
+                self.draw_scroll_cursor()?;
+            }
+        } else if self.scroll_data.cursor.take().is_some() {
+            self.reset_scroll()?;
+        }
+        Ok(())
+    }
+
+    fn move_scroll_cursor(&mut self, motion: ScrollMotion) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        let range = self.scroll_range() as usize;
+        self.scroll_data.move_cursor(&motion, &self.history, range);
+        self.draw_scroll_cursor()
+    }
+
+    fn set_scroll_mark(&mut self, name: char) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        if let Some(index) = self
+            .scroll_data
+            .cursor
+            .or(self.history.len().checked_sub(1))
+        {
+            self.history.set_mark(name, index);
+        }
+        Ok(())
+    }
+
+    fn yank_scroll_line(&mut self) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        if let Some(index) = self.scroll_data.cursor {
+            let line = self.history.get(index).clean_line().to_string();
+            write!(self.screen, "{}", SetClipboard(&line))?;
+        }
+        Ok(())
+    }
+
     fn set_host(&mut self, host: &str, port: u16) -> Result<()> {
         self.connection = if !host.is_empty() {
             Some(format!("{host}:{port}"))
@@ -542,13 +626,40 @@ impl UserInterface for SplitScreen {
     fn set_status_area_height(&mut self, height: u16) -> Result<()> {
         let height = StatusArea::clamp_height(height) as u16;
         self.status_area
-            .set_height(height, self.height - height - PROMPT_HEIGHT);
+            .set_height(height, self.height - height - self.prompt_height);
         self.setup()?;
         let input_str = self.prompt_input.as_str().to_owned();
         self.print_prompt_input(&input_str, self.prompt_input_pos);
         Ok(())
     }
 
+    fn set_vt_pane(&mut self, size: Option<(u16, u16)>) -> Result<()> {
+        let keep = matches!((&self.vt, size), (Some(vt), Some(size)) if vt.size() == size);
+        if !keep {
+            self.vt = size.map(|(cols, rows)| VtGrid::new(cols, rows));
+        }
+        self.setup()?;
+        let input_str = self.prompt_input.as_str().to_owned();
+        self.print_prompt_input(&input_str, self.prompt_input_pos);
+        Ok(())
+    }
+
+    fn print_vt(&mut self, line: &Line) {
+        if let Some(vt) = &mut self.vt {
+            vt.feed(line);
+            let copy = line.without_cursor_sequences();
+            match &mut self.live {
+                Some(live) => live.append_line(copy),
+                None => self.history.append_line(copy),
+            };
+            if let Err(err) = self.draw_vt_pane() {
+                error!("Failed to draw the VT pane: {err}");
+            }
+        } else {
+            self.print_output(line);
+        }
+    }
+
     fn set_show_tags(&mut self, show: bool) -> Result<()> {
         self.show_tags = show;
         self.setup()
@@ -560,6 +671,90 @@ impl UserInterface for SplitScreen {
         self.setup().ok();
     }
 
+    fn set_filter(&mut self, filter: Option<Regex>) -> Result<()> {
+        if filter.is_none() && self.history.filter().is_none() {
+            return Ok(());
+        }
+        // The cursor line is shown in context once the filter is cleared
+        let source = self
+            .scroll_data
+            .cursor
+            .map(|row| self.history.line_of_row(row));
+        self.history.set_filter(filter.clone());
+        self.scroll_data.cursor = None;
+        self.setup()?;
+        if filter.is_none() {
+            if let Some(source) = source {
+                self.scroll_data.cursor = Some(self.history.first_row_of(source));
+                self.draw_scroll_cursor()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_theme(&mut self, theme: Theme) -> Result<()> {
+        self.status_area.set_theme(theme.clone());
+        self.theme = theme;
+        self.setup()?;
+        let input_str = self.prompt_input.as_str().to_owned();
+        self.print_prompt_input(&input_str, self.prompt_input_pos);
+        Ok(())
+    }
+
+    fn set_history_capacity(&mut self, capacity: usize) -> Result<()> {
+        self.history.set_capacity(capacity);
+        self.scroll_data.clamp(&self.history);
+        if self.scroll_data.active {
+            self.draw_scroll()?;
+        }
+        Ok(())
+    }
+
+    fn output_lines(&self, count: Option<usize>) -> Vec<Line> {
+        self.live
+            .as_ref()
+            .unwrap_or(&self.history)
+            .last_lines(count)
+    }
+
+    fn set_replay(&mut self, name: Option<String>) -> Result<()> {
+        let live = self.live.take();
+        if name.is_some() {
+            let mut replay = History::with_capacity(ScrollbackSettings::load().capacity);
+            replay.set_tag_mask(self.tag_mask.clone());
+            let current = std::mem::replace(&mut self.history, replay);
+            self.live = Some(live.unwrap_or(current));
+        } else if let Some(live) = live {
+            self.history = live;
+        } else {
+            return Ok(());
+        }
+        self.replay = name;
+        self.scroll_data.cursor = None;
+        self.setup()?;
+        let input_str = self.prompt_input.as_str().to_owned();
+        self.print_prompt_input(&input_str, self.prompt_input_pos);
+        Ok(())
+    }
+
+    fn print_replay(&mut self, line: &Line) {
+        if self.live.is_some() && line.print_line().is_some() {
+            let cur_line = self.history.len();
+            let count = self.print_line(line.clone());
+            if self.scroll_data.scroll_lock && count > self.height as usize {
+                self.scroll_to(cur_line).ok();
+            }
+        }
+    }
+
+    fn set_wrapping(&mut self, wrapping: Wrapping) -> Result<()> {
+        self.wrap = wrapping.output;
+        self.setup()?;
+        let input_str = self.prompt_input.as_str().to_owned();
+        self.print_prompt_input(&input_str, self.prompt_input_pos);
+        Ok(())
+    }
+
     fn set_status_line(&mut self, line: usize, info: String) -> Result<()> {
         self.status_area.set_status_line(line, info);
         self.status_area.redraw_line(&mut self.screen, line)?;
@@ -567,6 +762,39 @@ impl UserInterface for SplitScreen {
         Ok(())
     }
 
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        write!(self.screen, "{}", SetTitle(title))?;
+        Ok(())
+    }
+
+    fn notify(&mut self, title: &str, body: &str, method: NotifyMethod) -> Result<()> {
+        write!(
+            self.screen,
+            "{}",
+            Notify {
+                title,
+                body,
+                method
+            }
+        )?;
+        Ok(())
+    }
+
+    fn ring_bell(&mut self) -> Result<()> {
+        write!(self.screen, "\x07")?;
+        Ok(())
+    }
+
+    fn flash(&mut self, on: bool) -> Result<()> {
+        self.flash = on;
+        if self.top_bar {
+            self.redraw_top_bar()
+        } else {
+            write!(self.screen, "{}", ReverseVideo(on))?;
+            Ok(())
+        }
+    }
+
     fn flush(&mut self) {
         self.screen.flush().unwrap();
     }
@@ -581,7 +809,7 @@ impl UserInterface for SplitScreen {
 
     fn destroy(mut self: Box<Self>) -> Result<(Box<dyn Write>, History)> {
         self.reset()?;
-        Ok((self.screen, self.history))
+        Ok((self.screen, self.live.unwrap_or(self.history)))
     }
 }
 
@@ -595,7 +823,13 @@ impl SplitScreen {
         let mud_prompt_line = height - status_area_height - 1;
         let prompt_line = height;
 
-        let status_area = StatusArea::new(status_area_height, mud_prompt_line + 1, width);
+        let theme = Theme::load();
+        let status_area = StatusArea::new(
+            status_area_height,
+            mud_prompt_line + 1,
+            width,
+            theme.clone(),
+        );
 
         Ok(Self {
             screen,
@@ -607,48 +841,172 @@ impl SplitScreen {
             mud_prompt: Line::from(""),
             status_area,
             prompt_line,
+            prompt_height: PROMPT_HEIGHT_MIN,
+            cursor_prompt_line: prompt_line,
             cursor_prompt_pos: 1,
             history,
+            live: None,
+            replay: None,
             scroll_data: ScrollData::new(),
             connection: None,
             tags: HashSet::new(),
             prompt_input: String::new(),
             prompt_input_pos: 0,
             show_tags: false,
+            show_timestamps: false,
             tag_mask: TagMask::default(),
+            completion_menu: None,
+            completion_menu_height: 0,
+            theme,
+            wrap: Wrapping::load().output,
+            flash: false,
+            top_bar: true,
+            vt: None,
         })
     }
 
+    /// The first line of the VT pane, right below the top bar.
+    fn vt_pane_line(&self) -> u16 {
+        if self.top_bar {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// The rows of the VT grid that fit in the pane, leaving at least half of
+    /// the screen to the output area.
+    fn vt_pane_rows(&self) -> u16 {
+        let available = self.output_line.saturating_sub(self.vt_pane_line()) / 2;
+        self.vt
+            .as_ref()
+            .map_or(0, |vt| vt.size().1.min(available.saturating_sub(1)))
+    }
+
+    /// The lines taken by the VT pane, including the bar below it.
+    fn vt_pane_height(&self) -> u16 {
+        match self.vt_pane_rows() {
+            0 => 0,
+            rows => rows + 1,
+        }
+    }
+
+    fn draw_vt_pane(&mut self) -> Result<()> {
+        let rows = self.vt_pane_rows();
+        if let (Some(vt), true) = (&self.vt, rows > 0) {
+            let start = self.vt_pane_line();
+            for (line, row) in (start..).zip(vt.visible_rows(rows as usize)) {
+                write!(
+                    self.screen,
+                    "{}{}{}{}",
+                    termion::cursor::Goto(1, line),
+                    termion::clear::CurrentLine,
+                    vt.render_row(row, self.width as usize),
+                    termion::style::Reset,
+                )?;
+            }
+            let glyph = self.theme.bar_glyph;
+            write!(
+                self.screen,
+                "{}{}{}{}{}{}",
+                termion::cursor::Goto(1, start + rows),
+                termion::clear::CurrentLine,
+                self.theme.escape(&self.theme.bar),
+                pad_bar("", glyph, self.width as usize),
+                termion::style::Reset,
+                self.goto_prompt(),
+            )?;
+        }
+        Ok(())
+    }
+
     fn render_history_line(&self, index: usize) -> String {
         let line = self.history.get(index);
+        let mut body = self.render_body(line);
+        if let Some(filter) = self.history.filter() {
+            body = self.highlight(filter, &body);
+        }
+        format!("{}{}", self.gutter(index), body)
+    }
+
+    /// Restyles the matches of `pattern` in the text of a rendered line,
+    /// leaving its escape sequences alone.
+    fn highlight(&self, pattern: &Regex, body: &str) -> String {
+        highlight(body, pattern, &self.theme.style(&self.theme.search_match)).0
+    }
+
+    fn render_body(&self, line: &Line) -> String {
         if self.show_tags {
-            line.tagged_line().unwrap_or_default()
+            line.tagged_line(self.theme.tag_symbol).unwrap_or_default()
         } else {
             line.print_line().unwrap_or_default().to_string()
         }
     }
 
-    fn print_line(&mut self, line: Line) {
-        let masked = line.is_masked(&self.tag_mask);
-        self.history.append_line(line.clone());
-        if masked {
-            return;
+    fn render_cursor_line(&self, index: usize) -> String {
+        let line = self.history.get(index);
+        format!(
+            "{}{}{}",
+            self.gutter(index),
+            if self.show_tags { "  " } else { "" },
+            self.theme.paint(&self.theme.selection, line.clean_line()),
+        )
+    }
+
+    /// The receive time of a history row, when the timestamp gutter is
+    /// shown. Rows continuing a wrapped line get an empty gutter.
+    fn gutter(&self, index: usize) -> String {
+        let line = self.history.get(index);
+        if self.show_timestamps && line.print_line().is_some() {
+            let time = self.theme.format_time(&line.time());
+            if self.history.is_continuation(index) {
+                format!("{:1$} ", "", time.as_str().display_width())
+            } else {
+                format!("{} ", self.theme.paint(&self.theme.timestamp, &time))
+            }
+        } else {
+            String::new()
         }
-        let rendered = if self.show_tags {
-            line.tagged_line().unwrap_or_default()
+    }
+
+    /// Columns taken up left of the line content by the gutter and tags.
+    fn line_padding(&self) -> usize {
+        let gutter = if self.show_timestamps {
+            self.theme
+                .format_time(&Local::now())
+                .as_str()
+                .display_width()
+                + 1
         } else {
-            line.print_line().unwrap_or_default().to_string()
+            0
         };
-        if self.scroll_data.not_scrolled_or_split() {
-            write!(
-                self.screen,
-                "{}\r\n{}{}",
-                termion::cursor::Goto(1, self.output_line),
-                &rendered,
-                self.goto_prompt(),
-            )
-            .unwrap();
+        gutter + if self.show_tags { 2 } else { 0 }
+    }
+
+    /// Adds a line to the history and prints the rows it wraps into,
+    /// returning the number of rows.
+    fn print_line(&mut self, line: Line) -> usize {
+        let count = self.history.append_line(line);
+        if count > 0 && self.scroll_data.not_scrolled_or_split() {
+            for index in self.history.len() - count..self.history.len() {
+                let rendered = self.render_history_line(index);
+                write!(
+                    self.screen,
+                    "{}\r\n{}",
+                    termion::cursor::Goto(1, self.output_line),
+                    &rendered,
+                )
+                .unwrap();
+            }
+            write!(self.screen, "{}", self.goto_prompt()).unwrap();
+            // New output scrolls the menu along with it
+            self.draw_completion_menu().ok();
         }
+        if count > 0 && self.history.filter().is_some() {
+            // Keep the match count up to date
+            self.redraw_top_bar().ok();
+        }
+        count
     }
 
     fn clear_prompt(&mut self) {
@@ -664,7 +1022,9 @@ impl SplitScreen {
 
     fn redraw_prompt(&mut self) {
         let prompt_line = if self.show_tags {
-            self.mud_prompt.tagged_line().unwrap_or_default()
+            self.mud_prompt
+                .tagged_line(self.theme.tag_symbol)
+                .unwrap_or_default()
         } else {
             self.mud_prompt.print_line().unwrap_or("").to_string()
         };
@@ -683,16 +1043,20 @@ impl SplitScreen {
     }
 
     fn redraw_top_bar(&mut self) -> Result<()> {
-        if self.output_start_line > 1 {
+        if self.top_bar {
             write!(
                 self.screen,
                 "{}{}{}",
                 termion::cursor::Goto(1, 1),
                 termion::clear::CurrentLine,
-                Fg(color::Green),
+                self.theme.escape(&self.theme.bar),
             )?;
+            if self.flash {
+                write!(self.screen, "{}", termion::style::Invert)?;
+            }
+            let glyph = self.theme.top_bar_glyph;
             let host = if let Some(connection) = &self.connection {
-                format!("═ {connection} ")
+                format!("{glyph} {connection} ")
             } else {
                 "".to_string()
             };
@@ -703,12 +1067,32 @@ impl SplitScreen {
                 .collect::<Vec<String>>();
             tags.sort();
             let tags = tags.join("");
-            let mut output = format!("{host}{tags}");
+            let filter = if let Some(filter) = self.history.filter() {
+                format!(
+                    "[grep: {} ({} lines)]",
+                    filter.as_str(),
+                    self.history.line_count()
+                )
+            } else {
+                String::new()
+            };
+            let replay = if let Some(replay) = &self.replay {
+                format!("[replay: {replay}]")
+            } else {
+                String::new()
+            };
+            let mut output = format!("{host}{tags}{replay}{filter}");
             if !output.is_empty() {
                 output.push(' ');
             }
-            write!(self.screen, "{:═<1$}", output, self.width as usize)?; // Print separator
-            write!(self.screen, "{}{}", Fg(color::Reset), self.goto_prompt(),)?;
+            let bar = pad_bar(&output, glyph, self.width as usize);
+            write!(self.screen, "{bar}")?; // Print separator
+            write!(
+                self.screen,
+                "{}{}",
+                termion::style::Reset,
+                self.goto_prompt(),
+            )?;
         }
         Ok(())
     }
@@ -724,7 +1108,7 @@ impl SplitScreen {
     fn goto_prompt(&self) -> String {
         format!(
             "{}",
-            termion::cursor::Goto(self.cursor_prompt_pos, self.prompt_line),
+            termion::cursor::Goto(self.cursor_prompt_pos, self.cursor_prompt_line),
         )
     }
 
@@ -740,14 +1124,13 @@ impl SplitScreen {
                 ScrollRegion(scroll_range + 3, self.output_line),
                 DisableOriginMode
             )?;
+            let glyph = self.theme.bar_glyph;
+            let bar = pad_bar(&format!("{glyph} (scroll) "), glyph, self.width as usize);
             write!(
                 self.screen,
-                "{}{}{:━<4$}{}",
+                "{}{}",
                 cursor::Goto(1, scroll_range + self.output_start_line),
-                color::Fg(color::Green),
-                "━ (scroll) ",
-                color::Fg(color::Reset),
-                self.width as usize
+                self.theme.paint(&self.theme.bar, &bar),
             )?;
         } else {
             self.status_area.set_scroll_marker(true);
@@ -761,28 +1144,31 @@ impl SplitScreen {
         let output_range = self.scroll_range();
         for i in 0..output_range {
             let index = self.scroll_data.pos + i as usize;
+            let line_no = self.output_start_line + i;
             if index >= self.history.len() {
-                // History has been trimmed during scrolling
+                // History has been trimmed during scrolling or is shorter than
+                // the scroll area.
                 // TODO: It should be possible to lock history during render perhaps?
                 // The lock would prevent the drain function until scrolls is done.
-                break;
-            }
-            let line_no = self.output_start_line + i;
-            let mut rendered = self.render_history_line(index);
-            if let Some(pattern) = &self.scroll_data.hilite {
-                rendered = pattern
-                    .replace_all(
-                        &rendered,
-                        format!(
-                            "{}{}$0{}{}",
-                            Fg(color::LightWhite),
-                            Bg(color::Blue),
-                            Bg(color::Reset),
-                            Fg(color::Reset)
-                        ),
-                    )
-                    .to_string();
+                write!(
+                    self.screen,
+                    "{}{}",
+                    termion::cursor::Goto(1, line_no),
+                    termion::clear::CurrentLine,
+                )?;
+                continue;
             }
+            let rendered = if self.scroll_data.cursor == Some(index) {
+                self.render_cursor_line(index)
+            } else {
+                // Only the line content is searched, not the gutter
+                let line = self.history.get(index);
+                let mut body = self.render_body(line);
+                if let Some(pattern) = self.scroll_data.hilite.as_ref().or(self.history.filter()) {
+                    body = self.highlight(pattern, &body);
+                }
+                format!("{}{}", self.gutter(index), body)
+            };
             write!(
                 self.screen,
                 "{}{}{}",
@@ -794,6 +1180,153 @@ impl SplitScreen {
         Ok(())
     }
 
+    fn draw_scroll_cursor(&mut self) -> Result<()> {
+        if self.scroll_data.cursor.is_some() {
+            if !self.scroll_data.active {
+                self.init_scroll()?;
+            }
+            let range = self.scroll_range() as usize;
+            self.scroll_data.follow_cursor(&self.history, range);
+            self.draw_scroll()?;
+        }
+        Ok(())
+    }
+
+    /// Draws the completion menu on the lines directly above the mud prompt,
+    /// showing the page of candidates holding the selected one.
+    fn draw_completion_menu(&mut self) -> Result<()> {
+        let menu = match &self.completion_menu {
+            Some(menu) if !menu.options.is_empty() => menu,
+            _ => return Ok(()),
+        };
+        // The header and the entries sit between the output top and the prompt
+        let available = (self.mud_prompt_line - self.output_start_line).saturating_sub(1);
+        let rows = (menu.options.len() as u16)
+            .min(COMPLETION_MENU_HEIGHT)
+            .min(available);
+        if rows == 0 {
+            return Ok(());
+        }
+
+        let width = self.width as usize;
+        let selected = menu.selected.unwrap_or(0);
+        let first = selected / rows as usize * rows as usize;
+        let page = &menu.options[first..(first + rows as usize).min(menu.options.len())];
+        let word_width = page
+            .iter()
+            .map(|(word, _)| word.as_str().display_width())
+            .max()
+            .unwrap_or(0);
+
+        let top = self.mud_prompt_line - rows - 1;
+        let position = match menu.selected {
+            Some(selected) => format!("{}/{}", selected + 1, menu.options.len()),
+            None => format!("{}", menu.options.len()),
+        };
+        let glyph = self.theme.bar_glyph;
+        let header = pad_bar(&format!("{glyph} completions ({position}) "), glyph, width);
+        write!(
+            self.screen,
+            "{}{}{}",
+            cursor::Goto(1, top),
+            termion::clear::CurrentLine,
+            self.theme.paint(&self.theme.bar, &header),
+        )?;
+
+        for i in 0..rows {
+            write!(
+                self.screen,
+                "{}{}",
+                cursor::Goto(1, top + 1 + i),
+                termion::clear::CurrentLine,
+            )?;
+            if let Some((word, description)) = page.get(i as usize) {
+                let padding = word_width - word.as_str().display_width();
+                let mut entry = format!(" {word}{:padding$}", "");
+                if let Some(description) = description {
+                    entry.push_str(&format!("  {description}"));
+                }
+                let entry: String = entry.chars().take(width).collect();
+                if menu.selected == Some(first + i as usize) {
+                    write!(
+                        self.screen,
+                        "{}",
+                        self.theme.paint(&self.theme.selection, &entry)
+                    )?;
+                } else {
+                    write!(self.screen, "{entry}")?;
+                }
+            }
+        }
+        self.completion_menu_height = rows + 1;
+        write!(self.screen, "{}", self.goto_prompt())?;
+        Ok(())
+    }
+
+    /// Moves the bottom of the output to fit a prompt of `height` rows. The
+    /// output is scrolled along so only the rows it uncovers are drawn again,
+    /// along with the prompt and status area below it.
+    fn resize_prompt(&mut self, height: u16) -> Result<()> {
+        let old_output_line = self.output_line;
+        self.prompt_height = height;
+        self.output_line = self.height - self.status_area.height() - height - 1;
+        self.mud_prompt_line = self.height - self.status_area.height() - height;
+        self.prompt_line = self.height - height + 1;
+        self.cursor_prompt_line = self.prompt_line;
+        let region = ScrollRegion(self.output_start_line, self.output_line);
+        if self.scroll_data.active {
+            write!(self.screen, "{region}{DisableOriginMode}")?;
+            self.restore_rows(self.output_start_line..self.mud_prompt_line)?;
+        } else if self.output_line < old_output_line {
+            // Scroll the newest output up out of the way before shrinking
+            let rows = old_output_line - self.output_line;
+            write!(
+                self.screen,
+                "{}{region}{DisableOriginMode}",
+                termion::scroll::Up(rows)
+            )?;
+        } else {
+            let rows = self.output_line - old_output_line;
+            write!(
+                self.screen,
+                "{region}{DisableOriginMode}{}",
+                termion::scroll::Down(rows)
+            )?;
+            self.restore_rows(self.output_start_line..self.output_start_line + rows)?;
+        }
+        self.redraw_prompt();
+        self.redraw_status_area()?;
+        let menu = self.completion_menu.take();
+        self.show_completions(menu)
+    }
+
+    /// Redraws the given screen rows after an overlay has been removed.
+    fn restore_rows(&mut self, rows: std::ops::Range<u16>) -> Result<()> {
+        let scroll_end = self.output_start_line + self.scroll_range();
+        let mut redraw_scroll = false;
+        for row in rows {
+            write!(
+                self.screen,
+                "{}{}",
+                cursor::Goto(1, row),
+                termion::clear::CurrentLine
+            )?;
+            if self.scroll_data.active && row <= scroll_end {
+                redraw_scroll = true;
+            } else if let Some(index) =
+                (self.history.len() + row as usize).checked_sub(self.output_line as usize + 1)
+            {
+                let rendered = self.render_history_line(index);
+                write!(self.screen, "{}{}", cursor::Goto(1, row), rendered)?;
+            }
+        }
+        if redraw_scroll {
+            self.init_scroll()?;
+            self.draw_scroll()?;
+        }
+        Ok(())
+    }
+
     fn scroll_range(&self) -> u16 {
         if self.scroll_data.allow_split && self.height > SCROLL_LIVE_BUFFER_SIZE * 2 {
             self.output_line - self.output_start_line - SCROLL_LIVE_BUFFER_SIZE + 1
@@ -807,6 +1340,54 @@ impl SplitScreen {
     }
 }
 
+/// Pads a bar to `width` columns with `glyph`, ignoring escape sequences.
+fn pad_bar(content: &str, glyph: char, width: usize) -> String {
+    let visible = Line::from(content).clean_line().display_width();
+    let padding: String = std::iter::repeat_n(glyph, width.saturating_sub(visible)).collect();
+    format!("{content}{padding}")
+}
+
+/// Fits a line of prompt input into `width` columns, scrolling it horizontally
+/// to keep the cursor visible. Returns the visible part and the cursor column.
+fn fit_prompt_input(input: &str, pos: usize, width: usize) -> (String, u16) {
+    // Calculate display width up to cursor position
+    let (byte_idx_at_cursor, _) = input.byte_index_at_display_width(pos);
+    let mut cursor_display_pos = (&input[..byte_idx_at_cursor]).display_width();
+
+    let mut input = input;
+    let mut wrapped = false;
+
+    // Scroll the view when cursor goes past the visible width
+    while input.display_width() >= width && cursor_display_pos >= width {
+        wrapped = true;
+        let (byte_idx, skipped_width) = input.byte_index_at_display_width(width);
+        if byte_idx < input.len() {
+            input = input.split_at(byte_idx).1;
+            cursor_display_pos -= skipped_width;
+        } else {
+            input = "";
+            cursor_display_pos = 0;
+        }
+    }
+
+    // When wrapped, reserve 1 column for the '>' indicator
+    let effective_width = if wrapped { width - 1 } else { width };
+
+    // Truncate input if it's still too wide for the display
+    if input.display_width() >= effective_width {
+        let (byte_idx, _) = input.byte_index_at_display_width(effective_width);
+        input = input.split_at(byte_idx).0;
+    }
+
+    // Adjust cursor position for the wrap indicator
+    let cursor_offset = if wrapped { 2 } else { 1 };
+    let wrap_indicator = if wrapped { ">" } else { "" };
+    (
+        format!("{wrap_indicator}{input}"),
+        cursor_display_pos as u16 + cursor_offset,
+    )
+}
+
 #[cfg(test)]
 mod screen_test {
     use super::*;
@@ -846,22 +1427,19 @@ mod screen_test {
 
     #[test]
     fn test_drain_history() {
-        let mut history = History::new();
-        history.capacity = 20;
-        history.drain_length = 10;
+        let mut history = History::with_capacity(20);
         assert!(history.is_empty());
         for _ in 0..19 {
             history.append("test");
         }
         assert_eq!(history.len(), 19);
         history.append("test");
-        assert_eq!(history.len(), 10);
+        assert_eq!(history.len(), 20);
         for _ in 0..9 {
             history.append("test");
         }
-        assert_eq!(history.len(), 19);
-        history.append("test");
-        assert_eq!(history.len(), 10);
+        assert_eq!(history.len(), 20);
+        assert_eq!(history.dropped_rows(), 9);
     }
 
     // Tests for print_line tag mask behaviour. SplitScreen::new requires a real
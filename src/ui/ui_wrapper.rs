@@ -4,24 +4,34 @@ use std::{
 };
 
 use crate::{
-    io::SaveData,
-    model::{Settings, MOUSE_ENABLED, READER_MODE},
+    io::{SaveData, ScrollbackSettings},
+    model::{
+        ColorDepth, CompletionMenu, Palette, Settings, Theme, Wrapping, MOUSE_ENABLED, READER_MODE,
+    },
     session::Session,
     tts::TTSController,
 };
 
-use super::{history::History, HeadlessScreen, ReaderScreen, SplitScreen, UserInterface};
+use super::{
+    color_writer::ColorWriter, history::History, HeadlessScreen, ReaderScreen, ScrollMotion,
+    SplitScreen, UserInterface,
+};
 use anyhow::Result;
 use termion::{input::MouseTerminal, raw::IntoRawMode, screen::IntoAlternateScreen};
 
 /// Creates the io::Write terminal handler we draw to.
-fn create_screen_writer(mouse_support: bool) -> Result<Box<dyn Write>> {
+fn create_screen_writer(
+    mouse_support: bool,
+    color_depth: Arc<Mutex<ColorDepth>>,
+    palette: Arc<Mutex<Palette>>,
+) -> Result<Box<dyn Write>> {
     let screen = stdout().into_raw_mode()?.into_alternate_screen()?;
-    if mouse_support {
-        Ok(Box::new(MouseTerminal::from(screen)))
+    let screen: Box<dyn Write> = if mouse_support {
+        Box::new(MouseTerminal::from(screen))
     } else {
-        Ok(Box::new(screen))
-    }
+        Box::new(screen)
+    };
+    Ok(Box::new(ColorWriter::new(screen, color_depth, palette)))
 }
 
 pub struct UiWrapper {
@@ -33,15 +43,20 @@ impl UiWrapper {
     pub fn new(session: &Session) -> Result<Self> {
         let settings = Settings::try_load()?;
         let reader_mode = settings.get(READER_MODE)?;
+        let history = History::with_capacity(ScrollbackSettings::load().capacity);
         let screen: Box<dyn UserInterface> = if reader_mode {
             Box::new(ReaderScreen::new(
-                create_screen_writer(false)?,
-                History::new(),
+                create_screen_writer(false, session.color_depth.clone(), session.palette.clone())?,
+                history,
             )?)
         } else {
             Box::new(SplitScreen::new(
-                create_screen_writer(settings.get(MOUSE_ENABLED)?)?,
-                History::new(),
+                create_screen_writer(
+                    settings.get(MOUSE_ENABLED)?,
+                    session.color_depth.clone(),
+                    session.palette.clone(),
+                )?,
+                history,
             )?)
         };
         let tts_ctrl = session.tts_ctrl.clone();
@@ -111,6 +126,10 @@ impl UserInterface for UiWrapper {
         self.screen.print_send(send);
     }
 
+    fn show_completions(&mut self, menu: Option<CompletionMenu>) -> Result<()> {
+        self.screen.show_completions(menu)
+    }
+
     fn reset(&mut self) -> Result<()> {
         self.screen.reset()
     }
@@ -151,6 +170,22 @@ impl UserInterface for UiWrapper {
         self.screen.find_down(pattern)
     }
 
+    fn set_scroll_mode(&mut self, enabled: bool) -> Result<()> {
+        self.screen.set_scroll_mode(enabled)
+    }
+
+    fn move_scroll_cursor(&mut self, motion: ScrollMotion) -> Result<()> {
+        self.screen.move_scroll_cursor(motion)
+    }
+
+    fn set_scroll_mark(&mut self, name: char) -> Result<()> {
+        self.screen.set_scroll_mark(name)
+    }
+
+    fn yank_scroll_line(&mut self) -> Result<()> {
+        self.screen.yank_scroll_line()
+    }
+
     fn set_host(&mut self, host: &str, port: u16) -> Result<()> {
         self.screen.set_host(host, port)
     }
@@ -171,6 +206,15 @@ impl UserInterface for UiWrapper {
         self.screen.set_status_area_height(height)
     }
 
+    fn set_vt_pane(&mut self, size: Option<(u16, u16)>) -> Result<()> {
+        self.screen.set_vt_pane(size)
+    }
+
+    fn print_vt(&mut self, line: &crate::model::Line) {
+        // Screens drawn with cursor movement don't read well as speech
+        self.screen.print_vt(line);
+    }
+
     fn set_show_tags(&mut self, show: bool) -> Result<()> {
         self.screen.set_show_tags(show)
     }
@@ -179,10 +223,55 @@ impl UserInterface for UiWrapper {
         self.screen.set_tag_mask(mask);
     }
 
+    fn set_filter(&mut self, filter: Option<crate::model::Regex>) -> Result<()> {
+        self.screen.set_filter(filter)
+    }
+
+    fn set_theme(&mut self, theme: Theme) -> Result<()> {
+        self.screen.set_theme(theme)
+    }
+
+    fn set_wrapping(&mut self, wrapping: Wrapping) -> Result<()> {
+        self.screen.set_wrapping(wrapping)
+    }
+
+    fn set_history_capacity(&mut self, capacity: usize) -> Result<()> {
+        self.screen.set_history_capacity(capacity)
+    }
+
+    fn output_lines(&self, count: Option<usize>) -> Vec<crate::model::Line> {
+        self.screen.output_lines(count)
+    }
+
+    fn set_replay(&mut self, name: Option<String>) -> Result<()> {
+        self.screen.set_replay(name)
+    }
+
+    fn print_replay(&mut self, line: &crate::model::Line) {
+        self.tts_ctrl.lock().unwrap().speak_line(line);
+        self.screen.print_replay(line)
+    }
+
     fn set_status_line(&mut self, line: usize, info: String) -> Result<()> {
         self.screen.set_status_line(line, info)
     }
 
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        self.screen.set_title(title)
+    }
+
+    fn notify(&mut self, title: &str, body: &str, method: super::NotifyMethod) -> Result<()> {
+        self.screen.notify(title, body, method)
+    }
+
+    fn ring_bell(&mut self) -> Result<()> {
+        self.screen.ring_bell()
+    }
+
+    fn flash(&mut self, on: bool) -> Result<()> {
+        self.screen.flash(on)
+    }
+
     fn flush(&mut self) {
         self.screen.flush();
     }
@@ -3,12 +3,13 @@ use std::{error, fmt, io::Write};
 #[cfg(test)]
 use mockall::automock;
 
-use crate::model::{Line, Regex, TagMask};
-use crate::tools::printable_chars::PrintableCharsIterator;
+use crate::model::{CompletionMenu, Line, Regex, TagMask, Theme, Wrapping};
 
 use anyhow::Result;
 
+use super::ansi::NotifyMethod;
 use super::history::History;
+use super::scroll_data::ScrollMotion;
 
 #[derive(Debug)]
 pub struct TerminalSizeError;
@@ -40,6 +41,8 @@ pub trait UserInterface {
     fn print_prompt(&mut self, prompt: &Line);
     fn print_prompt_input(&mut self, input: &str, pos: usize);
     fn print_send(&mut self, send: &Line);
+    /// Shows the menu of tab completion candidates, or hides it on `None`.
+    fn show_completions(&mut self, menu: Option<CompletionMenu>) -> Result<()>;
     fn reset(&mut self) -> Result<()>;
     fn reset_scroll(&mut self) -> Result<()>;
     /// Clears the output area (scroll region) without affecting the rest of the UI.
@@ -52,170 +55,52 @@ pub trait UserInterface {
     fn scroll_up(&mut self) -> Result<()>;
     fn find_up(&mut self, pattern: &Regex) -> Result<()>;
     fn find_down(&mut self, pattern: &Regex) -> Result<()>;
+    /// Enters or leaves scroll mode where a cursor is moved through the scrollback.
+    fn set_scroll_mode(&mut self, enabled: bool) -> Result<()>;
+    fn move_scroll_cursor(&mut self, motion: ScrollMotion) -> Result<()>;
+    fn set_scroll_mark(&mut self, name: char) -> Result<()>;
+    /// Copies the line under the scroll cursor to the terminal clipboard.
+    fn yank_scroll_line(&mut self) -> Result<()>;
     fn set_host(&mut self, host: &str, port: u16) -> Result<()>;
     fn add_tag(&mut self, proto: &str) -> Result<()>;
     fn remove_tag(&mut self, proto: &str) -> Result<()>;
     fn clear_tags(&mut self) -> Result<()>;
     fn set_status_area_height(&mut self, height: u16) -> Result<()>;
+    /// Shows a VT pane of `(cols, rows)` for screen drawing output, or
+    /// removes it on `None`.
+    fn set_vt_pane(&mut self, size: Option<(u16, u16)>) -> Result<()>;
+    /// Draws a line moving the cursor around in the VT pane, printing it as
+    /// output when there is none.
+    fn print_vt(&mut self, line: &Line);
     fn set_show_tags(&mut self, show: bool) -> Result<()>;
     fn set_tag_mask(&mut self, mask: TagMask);
+    /// Shows only the scrollback lines matching `filter`. Clearing it while
+    /// the scroll cursor is on a line shows that line in its full context.
+    fn set_filter(&mut self, filter: Option<Regex>) -> Result<()>;
+    /// Switches the UI chrome to the given theme and redraws the screen.
+    fn set_theme(&mut self, theme: Theme) -> Result<()>;
+    /// Changes how output is wrapped and rewraps the scrollback.
+    fn set_wrapping(&mut self, wrapping: Wrapping) -> Result<()>;
+    /// Changes how many lines of output are kept to scroll back through.
+    fn set_history_capacity(&mut self, capacity: usize) -> Result<()>;
+    /// The last `count` lines of output shown, or all of them, oldest first.
+    fn output_lines(&self, count: Option<usize>) -> Vec<Line>;
+    /// Shows a separate view for a replayed log, or the live output again
+    /// on `None`. Output received meanwhile is kept for the live view.
+    fn set_replay(&mut self, name: Option<String>) -> Result<()>;
+    /// Prints a line in the replay view, if it's shown.
+    fn print_replay(&mut self, line: &Line);
     fn set_status_line(&mut self, line: usize, info: String) -> Result<()>;
+    /// Sets the title of the terminal window.
+    fn set_title(&mut self, title: &str) -> Result<()>;
+    /// Raises a desktop notification through the terminal.
+    fn notify(&mut self, title: &str, body: &str, method: NotifyMethod) -> Result<()>;
+    /// Rings the terminal bell.
+    fn ring_bell(&mut self) -> Result<()>;
+    /// Highlights the screen as a visual bell, until called again with `false`.
+    fn flash(&mut self, on: bool) -> Result<()>;
     fn flush(&mut self);
     fn width(&self) -> u16;
     fn height(&self) -> u16;
     fn destroy(self: Box<Self>) -> Result<(Box<dyn Write>, History)>;
 }
-
-pub fn wrap_line(line: &str, width: usize, padding: usize) -> Vec<&str> {
-    let width = width.saturating_sub(padding);
-    let mut lines: Vec<&str> = vec![];
-
-    for line in line.lines() {
-        // If the line is empty just push and continue
-        if line.trim().is_empty() {
-            lines.push(line);
-            continue;
-        }
-
-        let mut last_cut: usize = 0;
-        let mut last_space: usize = 0;
-        let mut print_length = 0;
-        let mut print_length_since_space = 0;
-        for (length, c) in line.printable_char_indices() {
-            // Keep track of printable line length
-            print_length += 1;
-
-            // Keep track of last occurence of <space> and how many printable
-            // characters followed it
-            print_length_since_space += 1;
-            if c == ' ' && print_length < width {
-                last_space = length;
-                print_length_since_space = 0;
-            }
-
-            // Split the line if it's print length reaches screen width
-            if print_length >= width {
-                // Cut from last space if there is any. Otherwise just cut.
-                if last_cut < last_space {
-                    lines.push(&line[last_cut..last_space]);
-                    print_length = print_length_since_space;
-                    last_cut = last_space + 1;
-                } else {
-                    lines.push(&line[last_cut..length + c.len_utf8()]);
-                    print_length = 0;
-                    last_cut = length + c.len_utf8();
-                }
-            }
-        }
-
-        // Push the rest of the line if there is anything left
-        if last_cut < line.len() && !line[last_cut..].trim().is_empty() {
-            lines.push(&line[last_cut..]);
-        }
-    }
-    lines
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_wrap_line() {
-        let line: &'static str =
-            "\x1b[34mSomething \x1b[0mthat's pretty \x1b[32mlong and annoying\x1b[0m";
-        let lines = wrap_line(line, 11, 0);
-        let mut iter = lines.iter();
-        assert_eq!(iter.next(), Some(&"\u{1b}[34mSomething"));
-        assert_eq!(iter.next(), Some(&"\u{1b}[0mthat's"));
-        assert_eq!(iter.next(), Some(&"pretty"));
-        assert_eq!(iter.next(), Some(&"\u{1b}[32mlong and"));
-        assert_eq!(iter.next(), Some(&"annoying\u{1b}[0m"));
-    }
-
-    #[test]
-    fn test_wrap_line_with_padding() {
-        // "hello world!!" is 13 printable chars.
-        // At width=14 with no padding it fits on one line.
-        // With padding=2 the effective width is 12, so it must wrap at the space.
-        let line = "hello world!!";
-        let lines = wrap_line(line, 14, 0);
-        assert_eq!(lines.len(), 1);
-        let lines = wrap_line(line, 14, 2);
-        assert_eq!(lines.len(), 2);
-        assert_eq!(lines[0], "hello");
-        assert_eq!(lines[1], "world!!");
-    }
-
-    #[test]
-    fn test_long_line_no_space() {
-        let mut line = String::new();
-        for _ in 0..1000 {
-            for i in 0..10 {
-                let num = format!("{}", i);
-                line = format!("{}{}", line, num.repeat(15));
-            }
-        }
-        let lines = wrap_line(&line, 15, 0);
-        assert_eq!(lines.len(), 1000 * 10);
-        for (i, line) in lines.iter().enumerate() {
-            let num = format!("{}", i % 10);
-            assert_eq!(line, &num.repeat(15).to_string());
-        }
-    }
-
-    #[test]
-    fn test_wrap_line_with_osc8_hyperlink() {
-        // Simulates mdcat OSC 8 hyperlink output: ESC]8;;url ESC\ visible_text ESC]8;; ESC\
-        let line = "Visit \x1b]8;;https://example.com\x1b\\\x1b[34mhttps://example.com\x1b[0m\x1b]8;;\x1b\\ for info";
-        let lines = wrap_line(line, 80, 0);
-        // The entire line fits in 80 columns (printable: "Visit https://example.com for info" = 34 chars)
-        assert_eq!(lines.len(), 1);
-        assert_eq!(lines[0], line);
-    }
-
-    #[test]
-    fn test_wrap_line_osc8_not_eaten() {
-        // With the old SGR-only parser, everything between ESC] and the next 'm' would be eaten.
-        // This test ensures the visible link text is counted toward print width.
-        let link = "\x1b]8;;http://x.co\x1b\\click here\x1b]8;;\x1b\\";
-        // "click here" = 10 printable chars; at width 5 it must wrap.
-        let lines = wrap_line(link, 5, 0);
-        // 2 pieces: OSC-open + "click", " here" — trailing OSC-close escape bytes
-        // are emitted as a separate segment (zero printable width).
-        assert_eq!(lines.len(), 3);
-        assert!(lines[0].ends_with("click"));
-        assert!(lines[1].contains("here"));
-    }
-
-    #[test]
-    fn test_wrap_line_csi_non_sgr() {
-        // CSI sequences other than SGR (e.g. cursor movement ESC[H, erase ESC[K)
-        // should also be skipped.
-        let line = "\x1b[Hsome text\x1b[K";
-        let lines = wrap_line(line, 80, 0);
-        assert_eq!(lines.len(), 1);
-        assert_eq!(lines[0], line);
-    }
-
-    #[test]
-    fn test_wrap_line_charset_designation() {
-        // Charset designation sequences: ESC ( B (ASCII), ESC ) 0 (DEC Special Graphics),
-        // ESC * A, ESC + C, ESC % @ etc. — should all be skipped without consuming
-        // visible text.
-        let line = "\x1b(Bhello \x1b)0world\x1b*A!\x1b+C\x1b%@";
-        let lines = wrap_line(line, 80, 0);
-        // Printable: "hello world!" = 12 chars, fits in 80 columns
-        assert_eq!(lines.len(), 1);
-        assert_eq!(lines[0], line);
-    }
-
-    #[test]
-    fn test_wrap_line_charset_designation_wrap() {
-        // Ensure charset designation sequences don't affect wrap width calculation.
-        let line = "\x1b(Babcde\x1b)0fghij";
-        // "abcdefghij" = 10 printable chars; at width 5 it must wrap.
-        let lines = wrap_line(line, 5, 0);
-        assert_eq!(lines.len(), 2);
-    }
-}
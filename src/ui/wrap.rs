@@ -0,0 +1,352 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::model::WrapOptions;
+use crate::tools::printable_chars::PrintableCharsIterator;
+
+const RESET: &str = "\x1b[0m";
+const CLOSE_LINK: &str = "\x1b]8;;\x1b\\";
+
+/// Wraps a line of output into rows of at most `width` columns.
+///
+/// Continuation rows start with the colors and OSC 8 hyperlink active where
+/// the previous row was cut and rows ending in a colored state or inside a
+/// hyperlink are reset, so a row can be drawn on its own. A `width` of 0 disables wrapping.
+pub fn wrap(line: &str, width: usize, options: &WrapOptions) -> Vec<String> {
+    if width == 0 {
+        return line.lines().map(str::to_string).collect();
+    }
+    let indent = options.indent.min(width / 2);
+    let mut rows = vec![];
+    for line in line.lines() {
+        if line.trim().is_empty() {
+            rows.push(line.to_string());
+            continue;
+        }
+        Wrapper::new(line, width, indent, options.words).wrap_into(&mut rows);
+    }
+    rows
+}
+
+struct Wrapper<'a> {
+    line: &'a str,
+    width: usize,
+    indent: usize,
+    words: bool,
+    /// SGR sequences applied since the last reset
+    style: Vec<&'a str>,
+    /// The OSC 8 sequence opening the hyperlink we're inside of
+    link: Option<&'a str>,
+    /// Where the current row starts and the style and link it starts with
+    row_start: usize,
+    row_style: Vec<&'a str>,
+    row_link: Option<&'a str>,
+    continuation: bool,
+    /// Columns used by the current row
+    column: usize,
+    /// The last whitespace in the current row along with the columns used
+    /// up to and including it and the style and link at that point
+    last_break: Option<(usize, usize, Vec<&'a str>, Option<&'a str>)>,
+}
+
+impl<'a> Wrapper<'a> {
+    fn new(line: &'a str, width: usize, indent: usize, words: bool) -> Self {
+        Self {
+            line,
+            width,
+            indent,
+            words,
+            style: vec![],
+            link: None,
+            row_start: 0,
+            row_style: vec![],
+            row_link: None,
+            continuation: false,
+            column: 0,
+            last_break: None,
+        }
+    }
+
+    fn available(&self) -> usize {
+        if self.continuation {
+            self.width - self.indent
+        } else {
+            self.width
+        }
+    }
+
+    fn wrap_into(mut self, rows: &mut Vec<String>) {
+        let mut parsed = 0;
+        for (index, c) in self.line.printable_char_indices() {
+            self.apply_escapes(&self.line[parsed..index]);
+            parsed = index + c.len_utf8();
+
+            let char_width = c.width().unwrap_or(0);
+            while self.column > 0 && self.column + char_width > self.available() {
+                match self.last_break.take() {
+                    Some((at, column, style, link)) if self.words => {
+                        self.push_row(rows, at, &style, link);
+                        self.row_start =
+                            at + self.line[at..].chars().next().map_or(0, char::len_utf8);
+                        self.row_style = style;
+                        self.row_link = link;
+                        self.column -= column;
+                    }
+                    _ => {
+                        let style = self.style.clone();
+                        self.push_row(rows, index, &style, self.link);
+                        self.row_start = index;
+                        self.row_style = style;
+                        self.row_link = self.link;
+                        self.column = 0;
+                    }
+                }
+                self.continuation = true;
+            }
+
+            // Continuation rows don't start with the whitespace they were cut at
+            if self.continuation && self.column == 0 && c.is_whitespace() {
+                self.row_start = parsed;
+                continue;
+            }
+
+            self.column += char_width;
+            if c.is_whitespace() {
+                self.last_break = Some((index, self.column, self.style.clone(), self.link));
+            }
+        }
+
+        if self.row_start < self.line.len() && !self.line[self.row_start..].trim().is_empty() {
+            let rest = &self.line[self.row_start..];
+            rows.push(self.row(rest, false, false));
+        }
+    }
+
+    /// Tracks the SGR sequences and OSC 8 hyperlinks found in a run of escape
+    /// codes.
+    fn apply_escapes(&mut self, escapes: &'a str) {
+        let mut rest = escapes;
+        while let Some(start) = rest.find('\x1b') {
+            if rest[start..].starts_with("\x1b]8;") {
+                rest = self.apply_link(&rest[start..]);
+                continue;
+            }
+            if !rest[start..].starts_with("\x1b[") {
+                rest = &rest[start + 1..];
+                continue;
+            }
+            let params_start = start + 2;
+            let params_len = rest[params_start..]
+                .find(|c: char| !(c.is_ascii_digit() || c == ';' || c == ':'))
+                .unwrap_or(rest.len() - params_start);
+            let end = params_start + params_len;
+            if rest[end..].starts_with('m') {
+                let params = &rest[params_start..end];
+                let first = params.split([';', ':']).next().unwrap_or_default();
+                if first.is_empty() || first.trim_start_matches('0').is_empty() {
+                    self.style.clear();
+                }
+                if params
+                    .split([';', ':'])
+                    .any(|p| !p.trim_start_matches('0').is_empty())
+                {
+                    self.style.push(&rest[start..=end]);
+                }
+                rest = &rest[end + 1..];
+            } else {
+                rest = &rest[params_start..];
+            }
+        }
+    }
+
+    /// Opens or closes the hyperlink of the OSC 8 sequence `osc` starts with
+    /// and returns what follows it.
+    fn apply_link(&mut self, osc: &'a str) -> &'a str {
+        let (end, terminator) = match (osc.find('\x07'), osc.find("\x1b\\")) {
+            (Some(bel), Some(st)) if st < bel => (st, 2),
+            (Some(bel), _) => (bel, 1),
+            (None, Some(st)) => (st, 2),
+            (None, None) => return "",
+        };
+        let uri = osc[..end].splitn(3, ';').nth(2).unwrap_or_default();
+        self.link = if uri.is_empty() {
+            None
+        } else {
+            Some(&osc[..end + terminator])
+        };
+        &osc[end + terminator..]
+    }
+
+    fn push_row(
+        &self,
+        rows: &mut Vec<String>,
+        end: usize,
+        end_style: &[&str],
+        end_link: Option<&str>,
+    ) {
+        let content = &self.line[self.row_start..end];
+        rows.push(self.row(content, !end_style.is_empty(), end_link.is_some()));
+    }
+
+    fn row(&self, content: &str, reset: bool, close_link: bool) -> String {
+        let indent = if self.continuation { self.indent } else { 0 };
+        format!(
+            "{:indent$}{}{}{}{}{}",
+            "",
+            self.row_link.unwrap_or_default(),
+            self.row_style.concat(),
+            content.trim_end(),
+            if reset { RESET } else { "" },
+            if close_link { CLOSE_LINK } else { "" },
+        )
+    }
+}
+
+#[cfg(test)]
+mod wrap_test {
+    use super::wrap;
+    use crate::model::WrapOptions;
+
+    const WORDS: WrapOptions = WrapOptions {
+        words: true,
+        indent: 0,
+    };
+
+    #[test]
+    fn test_word_wrap() {
+        let rows = wrap("the quick brown fox jumps", 10, &WORDS);
+        assert_eq!(rows, vec!["the quick", "brown fox", "jumps"]);
+        let rows = wrap("a verylongwordthatdoesnotfit", 10, &WORDS);
+        assert_eq!(rows, vec!["a", "verylongwo", "rdthatdoes", "notfit"]);
+        assert_eq!(wrap("fits", 10, &WORDS), vec!["fits"]);
+        assert_eq!(wrap("no wrapping", 0, &WORDS), vec!["no wrapping"]);
+    }
+
+    #[test]
+    fn test_character_wrap() {
+        let options = WrapOptions {
+            words: false,
+            indent: 0,
+        };
+        let rows = wrap("the quick brown fox", 10, &options);
+        assert_eq!(rows, vec!["the quick", "brown fox"]);
+        let rows = wrap("the quickest fox", 10, &options);
+        assert_eq!(rows, vec!["the quicke", "st fox"]);
+    }
+
+    #[test]
+    fn test_hanging_indent() {
+        let options = WrapOptions {
+            words: true,
+            indent: 2,
+        };
+        let rows = wrap("the quick brown fox jumps over", 10, &options);
+        assert_eq!(
+            rows,
+            vec!["the quick", "  brown", "  fox", "  jumps", "  over"]
+        );
+    }
+
+    #[test]
+    fn test_keeps_colors() {
+        let rows = wrap("\x1b[31mred text \x1b[1mand bold\x1b[0m plain", 9, &WORDS);
+        assert_eq!(
+            rows,
+            vec![
+                "\x1b[31mred text\x1b[0m",
+                "\x1b[31m\x1b[1mand bold\x1b[0m",
+                "plain",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wide_chars() {
+        let rows = wrap("漢字 漢字漢字", 5, &WORDS);
+        assert_eq!(rows, vec!["漢字", "漢字", "漢字"]);
+    }
+
+    #[test]
+    fn test_wrap_colored_line() {
+        let line = "\x1b[34mSomething \x1b[0mthat's pretty \x1b[32mlong and annoying\x1b[0m";
+        let rows = wrap(line, 11, &WORDS);
+        assert_eq!(
+            rows,
+            vec![
+                "\x1b[34mSomething\x1b[0m",
+                "\x1b[34m\x1b[0mthat's",
+                "pretty",
+                "\x1b[32mlong and\x1b[0m",
+                "\x1b[32mannoying\x1b[0m",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_wide_chars_without_spaces() {
+        assert_eq!(wrap("ab漢字cd", 5, &WORDS), vec!["ab漢", "字cd"]);
+        assert_eq!(wrap("漢字漢字", 3, &WORDS), vec!["漢", "字", "漢", "字"]);
+    }
+
+    #[test]
+    fn test_long_line_no_space() {
+        let mut line = String::new();
+        for _ in 0..1000 {
+            for i in 0..10 {
+                line.push_str(&i.to_string().repeat(15));
+            }
+        }
+        let rows = wrap(&line, 15, &WORDS);
+        assert_eq!(rows.len(), 1000 * 10);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row, &(i % 10).to_string().repeat(15));
+        }
+    }
+
+    #[test]
+    fn test_wrap_osc8_hyperlink() {
+        // Simulates mdcat OSC 8 hyperlink output: ESC]8;;url ESC\ visible_text ESC]8;; ESC\
+        let line = "Visit \x1b]8;;https://example.com\x1b\\\x1b[34mhttps://example.com\x1b[0m\x1b]8;;\x1b\\ for info";
+        assert_eq!(wrap(line, 80, &WORDS), vec![line]);
+
+        // The visible link text counts towards the print width
+        let link = "\x1b]8;;http://x.co\x1b\\click here\x1b]8;;\x1b\\";
+        let rows = wrap(link, 5, &WORDS);
+        assert_eq!(
+            rows,
+            vec![
+                "\x1b]8;;http://x.co\x1b\\click\x1b]8;;\x1b\\",
+                "\x1b]8;;http://x.co\x1b\\here\x1b]8;;\x1b\\",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_reopens_hyperlink() {
+        let options = WrapOptions {
+            words: true,
+            indent: 2,
+        };
+        let line = "see \x1b]8;id=1;http://x.co\x07\x1b[4mthe long link\x1b[0m\x1b]8;;\x07 now";
+        let rows = wrap(line, 8, &options);
+        assert_eq!(
+            rows,
+            vec![
+                "see \x1b]8;id=1;http://x.co\x07\x1b[4mthe\x1b[0m\x1b]8;;\x1b\\",
+                "  \x1b]8;id=1;http://x.co\x07\x1b[4mlong\x1b[0m\x1b]8;;\x1b\\",
+                "  \x1b]8;id=1;http://x.co\x07\x1b[4mlink\x1b[0m\x1b]8;;\x07",
+                "  now",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_other_escapes() {
+        // CSI sequences other than SGR and charset designations are skipped
+        let line = "\x1b[Hsome text\x1b[K";
+        assert_eq!(wrap(line, 80, &WORDS), vec![line]);
+        let line = "\x1b(Bhello \x1b)0world\x1b*A!\x1b+C\x1b%@";
+        assert_eq!(wrap(line, 80, &WORDS), vec![line]);
+        let rows = wrap("\x1b(Babcde\x1b)0fghij", 5, &WORDS);
+        assert_eq!(rows, vec!["\x1b(Babcde\x1b)0", "fghij"]);
+    }
+}
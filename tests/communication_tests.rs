@@ -8,6 +8,8 @@ mod common;
 
 #[test]
 fn test_ttype_negotiation() -> std::io::Result<()> {
+    // The color flags of MTTS follow the detected color depth
+    env::set_var("COLORTERM", "truecolor");
     let (mut connection, handle) = setup(None);
 
     connection.send(&[IAC, WILL, TTYPE]);